@@ -0,0 +1,22 @@
+use lora_mesh::capture::{CaptureSink, FileHeader};
+use std::{fs::File, io, io::Write, path::Path};
+use zerocopy::IntoBytes;
+
+/// Appends capture records to a capture file
+pub struct FileSink(File);
+
+impl FileSink {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		let mut file = File::create(path)?;
+		file.write_all(FileHeader::CURRENT.as_bytes())?;
+		Ok(Self(file))
+	}
+}
+
+impl CaptureSink for FileSink {
+	fn record(&mut self, record: &[u8]) {
+		if let Err(e) = self.0.write_all(record) {
+			eprintln!("Writing capture failed: {e}");
+		}
+	}
+}
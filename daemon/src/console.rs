@@ -0,0 +1,254 @@
+use crate::flash::FileFlash;
+use embassy_futures::block_on;
+use lora_mesh::{
+	config::store::KvStore,
+	meshcore::{
+		channels::Channel,
+		client::{Client, Event},
+		packet::{
+			U16, U32,
+			group_data::DataType,
+			plain_message::TextType,
+			request::{NeighbourOrder, NeighboursRequest, RequestType},
+		},
+	},
+};
+use std::{
+	io::{self, BufRead},
+	process,
+};
+use zerocopy::IntoBytes;
+
+const HELP: &str = "\
+Commands:
+  advert [flood]         announce this node to neighbours, or the whole mesh
+  send <pubkey> <text>   send a direct text to a contact, by 64 hex digit public key
+  channel <slot> #<tag>  join a hashtag channel
+  channel <slot> <name> <secret>
+                         join a private channel, by 32 hex digit secret
+  channel <slot> clear   leave the channel in a slot, slot 0 starts as the public channel
+  group <slot> <text>    send a text to a channel
+  data <slot> <type> <hex>
+                         send binary data to a channel, with a type from 0 to 255
+  request <pubkey> status|keepalive|telemetry|acl|neighbours
+                         ask a contact, such as a repeater, for information
+  login <pubkey> [password]
+                         log in to a repeater or room server with its admin or guest password
+  cli <pubkey> <command> run a command on a repeater or room server logged in to as an admin,
+                         such as set name|freq|bw|sf|cr|tx|lat|lon, set advert.interval,
+                         set flood.advert.interval, set feature1|feature2 <n>|off, password,
+                         advert, neighbors, stats or reboot
+  help";
+
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+	if hex.len() % 2 != 0 {
+		return None;
+	}
+	hex.as_bytes()
+		.chunks(2)
+		.map(|digits| u8::from_str_radix(str::from_utf8(digits).ok()?, 16).ok())
+		.collect()
+}
+
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> { parse_bytes(hex)?.try_into().ok() }
+
+/// Parses the arguments of the request command into the type and data to send
+fn parse_request(args: &str) -> Result<([u8; 32], RequestType, Vec<u8>), &'static str> {
+	let (key, name) = args.split_once(' ').ok_or("Missing request type")?;
+	let key = parse_hex(key).ok_or("Invalid public key")?;
+	let (request_type, data) = match name.trim() {
+		"status" => (RequestType::GetStatus, Vec::new()),
+		"keepalive" => (RequestType::KeepAlive, Vec::new()),
+		"telemetry" => (RequestType::GetTelemetry, Vec::new()),
+		"acl" => (RequestType::GetAccessList, Vec::new()),
+		"neighbours" => {
+			let request = NeighboursRequest {
+				version: 0,
+				count: 16,
+				offset: U16::from(0),
+				order: NeighbourOrder::NewestFirst as u8,
+				prefix_len: 4,
+				random: U32::from(rand::random::<u32>()),
+			};
+			(RequestType::GetNeighbours, request.as_bytes().to_vec())
+		}
+		_ => return Err("Unknown request type"),
+	};
+	Ok((key, request_type, data))
+}
+
+/// Parses the arguments of the channel command, `None` clearing the slot
+fn parse_channel(args: &str) -> Result<(u8, Option<Channel>), &'static str> {
+	let mut args = args.split_whitespace();
+	let slot = args
+		.next()
+		.and_then(|slot| slot.parse().ok())
+		.ok_or("Invalid slot")?;
+	let channel = match (args.next(), args.next()) {
+		(Some("clear"), None) => None,
+		(Some(tag), None) => Some(Channel::hashtag(tag).map_err(|_| "Invalid hashtag")?),
+		(Some(name), Some(secret)) => {
+			let secret = parse_hex(secret).ok_or("Invalid secret")?;
+			Some(Channel::new(name, secret).map_err(|_| "Channel name too long")?)
+		}
+		_ => return Err("Missing channel"),
+	};
+	Ok((slot, channel))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
+
+/// Reads commands from stdin and queues them for the MeshCore task, until stdin closes.
+/// Runs on its own thread since reading stdin blocks.
+pub fn command_loop(client: &Client) {
+	for line in io::stdin().lock().lines() {
+		let Ok(line) = line
+		else {
+			break;
+		};
+		let line = line.trim();
+		let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+		match command {
+			"" => (),
+			"advert" => block_on(client.send_advert(args.trim() == "flood")),
+			"send" => {
+				let (key, text) = args.split_once(' ').unwrap_or((args, ""));
+				let Some(key) = parse_hex(key)
+				else {
+					println!("Invalid public key");
+					continue;
+				};
+				match block_on(client.send_direct_text(&key, text)) {
+					Ok(id) => println!("Queued message {id}"),
+					Err(e) => println!("Can't send message: {e}"),
+				}
+			}
+			"channel" => match parse_channel(args) {
+				Ok((slot, channel)) => {
+					if let Err(e) = block_on(client.set_channel(slot, channel)) {
+						println!("Can't set channel: {e}");
+					}
+				}
+				Err(e) => println!("{e}"),
+			},
+			"group" => {
+				let (slot, text) = args.split_once(' ').unwrap_or((args, ""));
+				let Ok(slot) = slot.parse()
+				else {
+					println!("Invalid slot");
+					continue;
+				};
+				if let Err(e) = block_on(client.send_group_text(slot, text)) {
+					println!("Can't send group text: {e}");
+				}
+			}
+			"data" => {
+				let mut args = args.split_whitespace();
+				let (Some(Ok(slot)), Some(Ok(data_type)), Some(Some(data))) = (
+					args.next().map(str::parse),
+					args.next().map(str::parse::<u8>),
+					args.next().map(parse_bytes),
+				)
+				else {
+					println!("Usage: data <slot> <type> <hex>");
+					continue;
+				};
+				if let Err(e) =
+					block_on(client.send_group_data(slot, DataType::from(data_type), &data))
+				{
+					println!("Can't send group data: {e}");
+				}
+			}
+			"request" => match parse_request(args) {
+				Ok((key, request_type, data)) => {
+					match block_on(client.send_request(&key, request_type, &data)) {
+						Ok(id) => println!("Queued request {id}"),
+						Err(e) => println!("Can't send request: {e}"),
+					}
+				}
+				Err(e) => println!("{e}"),
+			},
+			"login" => {
+				let (key, password) = args.split_once(' ').unwrap_or((args, ""));
+				let Some(key) = parse_hex(key)
+				else {
+					println!("Invalid public key");
+					continue;
+				};
+				match block_on(client.send_login(&key, password.as_bytes())) {
+					Ok(id) => println!("Queued login {id}"),
+					Err(e) => println!("Can't send login: {e}"),
+				}
+			}
+			"cli" => {
+				let (key, command) = args.split_once(' ').unwrap_or((args, ""));
+				let Some(key) = parse_hex(key)
+				else {
+					println!("Invalid public key");
+					continue;
+				};
+				if let Err(e) = block_on(client.send_cli_command(&key, command)) {
+					println!("Can't send command: {e}");
+				}
+			}
+			"help" => println!("{HELP}"),
+			_ => println!("Unknown command, try help"),
+		}
+	}
+}
+
+/// Prints events from the MeshCore task, saving settings that admins change to `store`
+pub async fn event_loop(client: &Client, mut store: KvStore<FileFlash>) -> ! {
+	loop {
+		match client.events.receive().await {
+			Event::MessageDelivered { id } => println!("Message {id} delivered"),
+			Event::MessageFailed { id } => println!("Message {id} failed"),
+			Event::TextMessage {
+				sender,
+				text_type,
+				signer,
+				text,
+				..
+			} => match (text_type, signer) {
+				(TextType::CliData, _) => {
+					println!("Output from {}..:\n{}", to_hex(&sender[..4]), text.as_str())
+				}
+				(TextType::SignedPlain, Some(signer)) => println!(
+					"{}.. via {}..: {}",
+					to_hex(&signer),
+					to_hex(&sender[..4]),
+					text.as_str()
+				),
+				_ => println!("{}..: {}", to_hex(&sender[..4]), text.as_str()),
+			},
+			Event::GroupText { channel, text, .. } => {
+				println!("Channel {channel}: {}", text.as_str())
+			}
+			Event::Response { id, data } => {
+				println!("Response to request {id}: {}", to_hex(data.as_bytes()))
+			}
+			Event::LoggedIn { id, permissions } => {
+				println!("Login {id} accepted as {permissions:?}")
+			}
+			Event::RequestFailed { id } => println!("Request {id} failed"),
+			Event::GroupData {
+				channel,
+				data_type,
+				data,
+			} => println!(
+				"Channel {channel} data, type {}: {}",
+				data_type.as_raw(),
+				to_hex(data.as_bytes())
+			),
+			Event::SettingsChanged { settings } => match settings.save(&mut store).await {
+				Ok(()) => println!("Settings changed remotely and saved"),
+				Err(e) => println!("Can't save settings: {e}"),
+			},
+			// Exiting is as close as the daemon gets, something like systemd starts it again
+			Event::RebootRequested => {
+				println!("Reboot requested, exiting");
+				process::exit(0);
+			}
+		}
+	}
+}
@@ -0,0 +1,96 @@
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+use std::{fs, io, path::PathBuf};
+
+pub const PAGE_SIZE: usize = 4096;
+const ERASED: u8 = 0xff;
+
+/// NOR flash emulated by a file, so the config store keeps the same layout and wear levelling
+/// as on the device. Every change is written straight through to the file.
+pub struct FileFlash {
+	path: PathBuf,
+	image: Vec<u8>,
+}
+
+impl FileFlash {
+	/// Opens the image at `path`, creating an erased one of `pages` pages if it doesn't exist
+	pub fn open(path: impl Into<PathBuf>, pages: usize) -> io::Result<Self> {
+		let path = path.into();
+		let size = pages * PAGE_SIZE;
+		let image = match fs::read(&path) {
+			Ok(mut image) => {
+				image.resize(size, ERASED);
+				image
+			}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => vec![ERASED; size],
+			Err(e) => return Err(e),
+		};
+		let flash = Self { path, image };
+		flash.sync()?;
+		Ok(flash)
+	}
+
+	/// Checks that `offset..offset + len` lies within the image and is aligned to `align`
+	fn check(&self, offset: u32, len: usize, align: usize) -> Result<(), NorFlashErrorKind> {
+		let offset = offset as usize;
+		if !offset.is_multiple_of(align) || !len.is_multiple_of(align) {
+			return Err(NorFlashErrorKind::NotAligned);
+		}
+		if offset + len > self.image.len() {
+			return Err(NorFlashErrorKind::OutOfBounds);
+		}
+		Ok(())
+	}
+
+	fn sync(&self) -> io::Result<()> { fs::write(&self.path, &self.image) }
+
+	fn sync_or_fail(&self) -> Result<(), NorFlashErrorKind> {
+		self.sync().map_err(|e| {
+			eprintln!("Writing {} failed: {e}", self.path.display());
+			NorFlashErrorKind::Other
+		})
+	}
+}
+
+impl ErrorType for FileFlash {
+	type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for FileFlash {
+	const READ_SIZE: usize = 1;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len(), Self::READ_SIZE)?;
+		let offset = offset as usize;
+		bytes.copy_from_slice(&self.image[offset..offset + bytes.len()]);
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize { self.image.len() }
+}
+
+impl NorFlash for FileFlash {
+	const WRITE_SIZE: usize = 4;
+	const ERASE_SIZE: usize = PAGE_SIZE;
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		if to < from {
+			return Err(NorFlashErrorKind::OutOfBounds);
+		}
+		self.check(from, (to - from) as usize, Self::ERASE_SIZE)?;
+		self.image[from as usize..to as usize].fill(ERASED);
+		self.sync_or_fail()
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+		// Like real NOR flash, writing can only clear bits
+		let offset = offset as usize;
+		for (cell, byte) in self.image[offset..offset + bytes.len()]
+			.iter_mut()
+			.zip(bytes)
+		{
+			*cell &= byte;
+		}
+		self.sync_or_fail()
+	}
+}
@@ -0,0 +1,341 @@
+#![feature(impl_trait_in_assoc_type)]
+
+//! Runs the mesh protocol stacks as a Linux daemon, with UDP multicast on localhost standing in
+//! for the LoRa radio. Many daemons on one machine form a mesh, and a client can drive one from
+//! stdin without any hardware.
+
+mod capture;
+mod console;
+mod flash;
+mod udp;
+
+use crate::{
+	capture::FileSink,
+	flash::FileFlash,
+	udp::{Inbox, UdpRadio},
+};
+use embassy_executor::Spawner;
+use lora_mesh::{
+	capture::CapturingRadio,
+	config::{Settings, store::KvStore},
+	meshcore::{
+		self, MESHCORE_SYNCWORD,
+		client::Client,
+		crypto::SigningKeys,
+		packet::{
+			U32,
+			advert::{AdvType, LatLong},
+		},
+		repeater::RepeaterConfig,
+	},
+	meshtastic::{self, MESHTASTIC_SYNCWORD},
+};
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+use std::{
+	env, fs, io,
+	net::SocketAddrV4,
+	os::unix::fs::OpenOptionsExt,
+	path::{Path, PathBuf},
+	process, thread,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+const USAGE: &str = "\
+Usage: lora-meshd [options]
+  --protocol meshcore|meshtastic  (default meshcore)
+  --group ADDR:PORT               multicast group shared by the mesh (default 239.255.76.67:4403)
+  --node ID                       unique id within the group (default random)
+  --state DIR                     where the identity and config are kept (default .)
+  --name NAME                     node name to advertise, saved to the config
+  --admin-password-file FILE      password for admin logins, saved to the config
+  --guest-password-file FILE      password for guest logins, saved to the config, empty for none
+  --role chat|repeater|room       what to advertise as, saved to the config, room hosts a room
+  --advert-interval MINUTES       between adverts to neighbours, saved to the config, 0 for none
+  --flood-advert-interval HOURS   between flooded adverts, saved to the config, 0 for none
+  --location LAT,LON              in degrees, to advertise, saved to the config
+  --features A,B                  values of the advert's optional battery and temperature fields,
+                                  saved to the config, - leaves one out
+  --capture FILE                  record every frame sent and received
+
+The passwords can also be given in the LORA_MESHD_ADMIN_PASSWORD and LORA_MESHD_GUEST_PASSWORD
+environment variables, keeping them out of the process list and shell history.";
+
+const DEFAULT_GROUP: &str = "239.255.76.67:4403";
+/// Erase pages in the config image, as on the device
+const CONFIG_PAGES: usize = 4;
+
+static CLIENT: Client = Client::new();
+static INBOX: Inbox = Inbox::new();
+
+type Radio = CapturingRadio<UdpRadio, Option<FileSink>>;
+
+#[derive(PartialEq)]
+enum Protocol {
+	MeshCore,
+	Meshtastic,
+}
+
+struct Args {
+	protocol: Protocol,
+	group: SocketAddrV4,
+	node: Option<u32>,
+	state: PathBuf,
+	name: Option<String>,
+	admin_password: Option<String>,
+	guest_password: Option<String>,
+	role: Option<AdvType>,
+	advert_interval: Option<u16>,
+	flood_advert_interval: Option<u16>,
+	location: Option<LatLong>,
+	features: Option<[Option<u16>; 2]>,
+	capture: Option<PathBuf>,
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{message}\n\n{USAGE}");
+	process::exit(2);
+}
+
+/// Reads a password from the first line of a file
+fn read_password(path: &str) -> String {
+	let contents =
+		fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("Can't read {path}: {e}")));
+	contents.lines().next().unwrap_or_default().to_owned()
+}
+
+fn parse_args() -> Args {
+	let mut args = Args {
+		protocol: Protocol::MeshCore,
+		group: DEFAULT_GROUP.parse().unwrap(),
+		node: None,
+		state: PathBuf::from("."),
+		name: None,
+		admin_password: env::var("LORA_MESHD_ADMIN_PASSWORD").ok(),
+		guest_password: env::var("LORA_MESHD_GUEST_PASSWORD").ok(),
+		role: None,
+		advert_interval: None,
+		flood_advert_interval: None,
+		location: None,
+		features: None,
+		capture: None,
+	};
+
+	let mut argv = env::args().skip(1);
+	while let Some(arg) = argv.next() {
+		let mut value = || {
+			argv.next()
+				.unwrap_or_else(|| fail(&format!("Missing value for {arg}")))
+		};
+		match arg.as_str() {
+			"--protocol" => {
+				args.protocol = match value().as_str() {
+					"meshcore" => Protocol::MeshCore,
+					"meshtastic" => Protocol::Meshtastic,
+					_ => fail("Unknown protocol"),
+				}
+			}
+			"--group" => args.group = value().parse().unwrap_or_else(|_| fail("Invalid group")),
+			"--node" => args.node = Some(value().parse().unwrap_or_else(|_| fail("Invalid node"))),
+			"--state" => args.state = PathBuf::from(value()),
+			"--name" => args.name = Some(value()),
+			"--admin-password-file" => args.admin_password = Some(read_password(&value())),
+			"--guest-password-file" => args.guest_password = Some(read_password(&value())),
+			"--role" => {
+				args.role = Some(match value().as_str() {
+					"chat" => AdvType::Chat,
+					"repeater" => AdvType::Repeater,
+					"room" => AdvType::Room,
+					_ => fail("Unknown role"),
+				})
+			}
+			"--advert-interval" => {
+				args.advert_interval = Some(
+					value()
+						.parse()
+						.unwrap_or_else(|_| fail("Invalid advert interval")),
+				)
+			}
+			"--flood-advert-interval" => {
+				args.flood_advert_interval = Some(
+					value()
+						.parse()
+						.unwrap_or_else(|_| fail("Invalid flood advert interval")),
+				)
+			}
+			"--location" => {
+				args.location =
+					Some(parse_location(&value()).unwrap_or_else(|| fail("Invalid location")))
+			}
+			"--features" => {
+				args.features =
+					Some(parse_features(&value()).unwrap_or_else(|| fail("Invalid features")))
+			}
+			"--capture" => args.capture = Some(PathBuf::from(value())),
+			"--help" | "-h" => {
+				println!("{USAGE}");
+				process::exit(0);
+			}
+			_ => fail(&format!("Unknown argument {arg}")),
+		}
+	}
+	args
+}
+
+/// Parses `LAT,LON` in degrees into the millionths adverts carry
+fn parse_location(value: &str) -> Option<LatLong> {
+	let (lat, long) = value.split_once(',')?;
+	let millionths = |degrees: &str, limit: f64| {
+		let degrees: f64 = degrees.trim().parse().ok()?;
+		(degrees.abs() <= limit).then(|| U32::from((degrees * 1e6).round() as i32 as u32))
+	};
+	Some(LatLong {
+		lat: millionths(lat, 90.0)?,
+		long: millionths(long, 180.0)?,
+	})
+}
+
+/// Parses `A,B` into the advert's optional fields, `-` leaving one out
+fn parse_features(value: &str) -> Option<[Option<u16>; 2]> {
+	let (first, second) = value.split_once(',')?;
+	let feature = |value: &str| match value.trim() {
+		"-" => Some(None),
+		value => value.parse().ok().map(Some),
+	};
+	Some([feature(first)?, feature(second)?])
+}
+
+/// Reads the private key from `path`, generating and saving a new one if there isn't one
+fn load_or_generate_identity(path: &Path, rng: &mut StdRng) -> io::Result<SigningKeys> {
+	match fs::read(path) {
+		Ok(bytes) => {
+			let private_key = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+				io::Error::new(io::ErrorKind::InvalidData, "identity file must be 32 bytes")
+			})?;
+			Ok(SigningKeys::from_bytes(&private_key))
+		}
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			let mut private_key = [0; 32];
+			rng.fill_bytes(&mut private_key);
+			fs::OpenOptions::new()
+				.write(true)
+				.create_new(true)
+				.mode(0o600)
+				.open(path)
+				.and_then(|mut file| io::Write::write_all(&mut file, &private_key))?;
+			Ok(SigningKeys::from_bytes(&private_key))
+		}
+		Err(e) => Err(e),
+	}
+}
+
+#[embassy_executor::task]
+async fn meshcore_loop(radio: Radio, rng: StdRng, identity: SigningKeys, settings: Settings) -> ! {
+	let repeater = RepeaterConfig {
+		enabled: settings.repeater_enabled,
+		..Default::default()
+	};
+	meshcore::lora::lora_loop(radio, rng, &CLIENT, identity, settings, repeater).await
+}
+
+#[embassy_executor::task]
+async fn meshtastic_loop(radio: Radio, rng: StdRng) -> ! {
+	meshtastic::lora::lora_loop(radio, rng).await
+}
+
+#[embassy_executor::task]
+async fn event_loop(store: KvStore<FileFlash>) -> ! { console::event_loop(&CLIENT, store).await }
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+	let args = parse_args();
+	let mut rng = StdRng::from_os_rng();
+
+	if let Err(e) = fs::create_dir_all(&args.state) {
+		fail(&format!("Can't create {}: {e}", args.state.display()));
+	}
+
+	let node = args.node.unwrap_or_else(|| rng.next_u32());
+	let radio = UdpRadio::open(args.group, node, &INBOX)
+		.unwrap_or_else(|e| fail(&format!("Can't join {}: {e}", args.group)));
+	println!("Node {node} joined {}", args.group);
+
+	let capture = args.capture.as_ref().map(|path| {
+		FileSink::create(path)
+			.unwrap_or_else(|e| fail(&format!("Can't create {}: {e}", path.display())))
+	});
+	let syncword = match args.protocol {
+		Protocol::MeshCore => MESHCORE_SYNCWORD,
+		Protocol::Meshtastic => MESHTASTIC_SYNCWORD,
+	};
+	let radio = CapturingRadio::new(radio, syncword, capture);
+
+	if args.protocol == Protocol::Meshtastic {
+		spawner.must_spawn(meshtastic_loop(radio, rng));
+		return;
+	}
+
+	let identity = load_or_generate_identity(&args.state.join("identity.bin"), &mut rng)
+		.unwrap_or_else(|e| fail(&format!("Can't load identity: {e}")));
+	println!("Public key: {}", console::to_hex(&identity.public_key()));
+
+	let flash = FileFlash::open(args.state.join("config.bin"), CONFIG_PAGES)
+		.unwrap_or_else(|e| fail(&format!("Can't open config: {e}")));
+	let mut store = KvStore::open(flash, 0, CONFIG_PAGES as u32)
+		.await
+		.unwrap_or_else(|e| fail(&format!("Can't open config: {e}")));
+	let mut settings = Settings::load(&mut store)
+		.await
+		.unwrap_or_else(|e| fail(&format!("Can't load config: {e}")));
+	if let Some(name) = &args.name {
+		settings
+			.set_name(name.as_bytes())
+			.unwrap_or_else(|_| fail("Invalid name"));
+	}
+	if let Some(password) = &args.admin_password {
+		settings
+			.set_admin_password(password.as_bytes())
+			.unwrap_or_else(|_| fail("Invalid admin password"));
+	}
+	if let Some(password) = &args.guest_password {
+		settings
+			.set_guest_password(password.as_bytes())
+			.unwrap_or_else(|_| fail("Invalid guest password"));
+	}
+	if let Some(role) = args.role {
+		settings.role = role;
+	}
+	if let Some(minutes) = args.advert_interval {
+		settings.advert_interval_mins = minutes;
+	}
+	if let Some(hours) = args.flood_advert_interval {
+		settings.flood_advert_interval_hours = hours;
+	}
+	if let Some(location) = &args.location {
+		settings.lat_long = Some(location.clone());
+	}
+	if let Some(features) = args.features {
+		settings.features = features;
+	}
+	if (args.name.is_some()
+		|| args.admin_password.is_some()
+		|| args.guest_password.is_some()
+		|| args.role.is_some()
+		|| args.advert_interval.is_some()
+		|| args.flood_advert_interval.is_some()
+		|| args.location.is_some()
+		|| args.features.is_some())
+		&& let Err(e) = settings.save(&mut store).await
+	{
+		fail(&format!("Can't save config: {e}"));
+	}
+
+	// Unlike the device, the daemon knows the time from the start
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |since_epoch| since_epoch.as_secs() as u32);
+	CLIENT.set_time(now).await;
+
+	spawner.must_spawn(meshcore_loop(radio, rng, identity, settings));
+	spawner.must_spawn(event_loop(store));
+	thread::spawn(|| console::command_loop(&CLIENT));
+}
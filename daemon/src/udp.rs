@@ -0,0 +1,173 @@
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::Timer;
+use lora_mesh::{
+	error::Result,
+	radio::{MeshRadio, Modulation},
+};
+use lora_phy::mod_params::PacketStatus;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+	io,
+	net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+	thread,
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned, little_endian::U32};
+
+pub const MAX_FRAME_SIZE: usize = 255;
+const INBOX_SIZE: usize = 8;
+
+/// Signal quality reported for every frame, there is no real channel to measure
+const LINK_STATUS: PacketStatus = PacketStatus { rssi: -60, snr: 10 };
+
+/// Precedes every frame in a datagram so receivers can drop their own transmissions and
+/// frames on a channel they aren't listening to
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+#[repr(C)]
+struct DatagramHeader {
+	node: U32,
+	frequency: U32,
+	bandwidth_hz: U32,
+	spreading_factor: u8,
+}
+
+struct Datagram {
+	modulation: Modulation,
+	len: usize,
+	bytes: [u8; MAX_FRAME_SIZE],
+}
+
+/// Frames received by the socket thread, waiting for the radio task
+pub struct Inbox(Channel<CriticalSectionRawMutex, Datagram, INBOX_SIZE>);
+
+impl Inbox {
+	pub const fn new() -> Self { Self(Channel::new()) }
+}
+
+impl Default for Inbox {
+	fn default() -> Self { Self::new() }
+}
+
+/// Stands in for a LoRa transceiver by exchanging frames with other nodes over UDP multicast.
+///
+/// Every node in the group hears every other, transmissions still take their real time on air
+/// so the protocol loops see realistic timing.
+pub struct UdpRadio {
+	socket: UdpSocket,
+	group: SocketAddrV4,
+	node: u32,
+	inbox: &'static Inbox,
+	modulation: Option<Modulation>,
+}
+
+impl UdpRadio {
+	/// Joins `group` on the loopback interface and starts the thread feeding `inbox`.
+	/// `node` must be unique within the group.
+	pub fn open(group: SocketAddrV4, node: u32, inbox: &'static Inbox) -> io::Result<Self> {
+		let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+		// Several daemons on the same host all bind the group's port
+		socket.set_reuse_address(true)?;
+		#[cfg(unix)]
+		socket.set_reuse_port(true)?;
+		socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())?;
+		socket.join_multicast_v4(group.ip(), &Ipv4Addr::LOCALHOST)?;
+		socket.set_multicast_if_v4(&Ipv4Addr::LOCALHOST)?;
+		socket.set_multicast_loop_v4(true)?;
+		let socket = UdpSocket::from(socket);
+
+		let receiver = socket.try_clone()?;
+		thread::spawn(move || receive_loop(receiver, node, inbox));
+
+		Ok(Self {
+			socket,
+			group,
+			node,
+			inbox,
+			modulation: None,
+		})
+	}
+}
+
+fn receive_loop(socket: UdpSocket, node: u32, inbox: &Inbox) {
+	let mut buffer = [0; size_of::<DatagramHeader>() + MAX_FRAME_SIZE];
+	loop {
+		let len = match socket.recv(&mut buffer) {
+			Ok(len) => len,
+			Err(e) => {
+				eprintln!("UDP receive failed: {e}");
+				continue;
+			}
+		};
+		let Ok((header, frame)) = DatagramHeader::ref_from_prefix(&buffer[..len])
+		else {
+			continue;
+		};
+		if header.node.get() == node || frame.len() > MAX_FRAME_SIZE {
+			continue;
+		}
+
+		let mut datagram = Datagram {
+			modulation: Modulation {
+				frequency: header.frequency.get(),
+				spreading_factor: header.spreading_factor,
+				bandwidth_hz: header.bandwidth_hz.get(),
+				// Not needed to decide whether a receiver hears the frame
+				coding_rate: 0,
+				preamble_len: 0,
+				crc: false,
+				tx_power: 0,
+			},
+			len: frame.len(),
+			bytes: [0; MAX_FRAME_SIZE],
+		};
+		datagram.bytes[..frame.len()].copy_from_slice(frame);
+		if inbox.0.try_send(datagram).is_err() {
+			eprintln!("Inbox full, dropping frame");
+		}
+	}
+}
+
+impl MeshRadio for UdpRadio {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.modulation = Some(*modulation);
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		let Some(modulation) = self.modulation
+		else {
+			return Ok(());
+		};
+
+		let header = DatagramHeader {
+			node: self.node.into(),
+			frequency: modulation.frequency.into(),
+			bandwidth_hz: modulation.bandwidth_hz.into(),
+			spreading_factor: modulation.spreading_factor,
+		};
+		let datagram = [header.as_bytes(), frame].concat();
+		if let Err(e) = self.socket.send_to(&datagram, self.group) {
+			eprintln!("UDP send failed: {e}");
+		}
+
+		Timer::after(modulation.airtime().time_on_air(frame.len())).await;
+		Ok(())
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		loop {
+			let datagram = self.inbox.0.receive().await;
+			if !self
+				.modulation
+				.is_some_and(|m| m.hears(&datagram.modulation))
+			{
+				continue;
+			}
+
+			let len = datagram.len.min(buffer.len());
+			buffer[..len].copy_from_slice(&datagram.bytes[..len]);
+			return Ok((len, LINK_STATUS));
+		}
+	}
+
+	async fn cad(&mut self) -> Result<bool> { Ok(!self.inbox.0.is_empty()) }
+}
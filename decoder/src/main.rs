@@ -0,0 +1,211 @@
+//! Decodes MeshCore and Meshtastic frames for debugging, from hex copied out of a log or from a
+//! capture file. Given keys, it checks MACs and signatures and decrypts what it can.
+
+mod meshcore;
+mod meshtastic;
+
+use ed25519_dalek::VerifyingKey;
+use lora_mesh::{
+	capture::{CAPTURE_MAGIC, Direction, Records},
+	meshcore::{
+		MESHCORE_SYNCWORD,
+		channels::Channel,
+		crypto::{PUBLIC_GROUP_PSK, SigningKeys},
+	},
+	meshtastic::{LONGFAST_KEY, MESHTASTIC_SYNCWORD},
+};
+use std::{env, fs, path::Path, process};
+
+const USAGE: &str = "\
+Usage: lora-decode [options] <frame|file>...
+  --protocol meshcore|meshtastic  protocol of hex frames (default meshcore)
+  --identity KEY                  our private key, as hex or a 32 byte key file
+  --contact PUBKEY                public key of a peer, as hex
+  --psk KEY                       MeshCore group secret, as hex or a #hashtag channel name
+  --meshtastic-psk KEY            Meshtastic channel key, as hex
+
+A frame is hex, with or without separators. A file is a capture file, or text with a hex frame
+on each line. The public MeshCore group and the Meshtastic LongFast key are always tried, and
+the keys of adverts seen earlier in the input are added to the contacts.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Protocol {
+	MeshCore,
+	Meshtastic,
+}
+
+/// Everything the decoder can use to check and decrypt frames
+pub struct Keys {
+	pub identities: Vec<SigningKeys>,
+	pub contacts: Vec<VerifyingKey>,
+	pub group_psks: Vec<[u8; 16]>,
+	pub meshtastic_psks: Vec<[u8; 16]>,
+}
+
+impl Keys {
+	/// Adds a contact, unless it is known already
+	pub fn add_contact(&mut self, contact: VerifyingKey) -> bool {
+		if self.contacts.contains(&contact) {
+			return false;
+		}
+		self.contacts.push(contact);
+		true
+	}
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{message}\n\n{USAGE}");
+	process::exit(2);
+}
+
+pub fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
+
+/// Parses hex digits, ignoring the brackets, commas, spaces and `0x` prefixes of log output
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+	let digits: String = text
+		.replace("0x", "")
+		.chars()
+		.filter(|c| !matches!(c, '[' | ']' | ',' | ':' | ' ' | '\t'))
+		.collect();
+	if digits.is_empty() || digits.len() % 2 != 0 {
+		return None;
+	}
+	digits
+		.as_bytes()
+		.chunks(2)
+		.map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+		.collect()
+}
+
+fn parse_key<const N: usize>(text: &str) -> [u8; N] {
+	parse_hex(text)
+		.and_then(|bytes| <[u8; N]>::try_from(bytes).ok())
+		.unwrap_or_else(|| fail(&format!("{text} isn't {N} bytes of hex")))
+}
+
+/// Hashtag channels derive their secret from the name
+fn parse_psk(arg: &str) -> [u8; 16] {
+	if !arg.starts_with('#') {
+		return parse_key(arg);
+	}
+	let channel = Channel::hashtag(arg).unwrap_or_else(|_| fail(&format!("Invalid hashtag {arg}")));
+	*channel.secret()
+}
+
+/// Reads a private key from a key file, such as the daemon's identity.bin, or from hex
+fn parse_identity(arg: &str) -> SigningKeys {
+	let private_key = match fs::read(arg) {
+		Ok(bytes) => <[u8; 32]>::try_from(bytes)
+			.unwrap_or_else(|_| fail(&format!("{arg} isn't a 32 byte key file"))),
+		Err(_) => parse_key(arg),
+	};
+	SigningKeys::from_bytes(&private_key)
+}
+
+fn decode_frame(protocol: Protocol, frame: &[u8], keys: &mut Keys) {
+	println!("Frame: {} bytes, {}", frame.len(), to_hex(frame));
+	match protocol {
+		Protocol::MeshCore => meshcore::decode(frame, keys),
+		Protocol::Meshtastic => meshtastic::decode(frame, keys),
+	}
+	println!();
+}
+
+fn decode_capture(bytes: &[u8], keys: &mut Keys) {
+	let records = Records::from_file(bytes).unwrap_or_else(|e| fail(&format!("{e}")));
+	for record in records {
+		let Ok(record) = record
+		else {
+			println!("Capture ends with a malformed record");
+			return;
+		};
+		let direction = match record.direction() {
+			Ok(Direction::Rx) => "received",
+			Ok(Direction::Tx) => "sent",
+			Err(_) => "unknown direction",
+		};
+		let status = record.status();
+		println!(
+			"At {:.6} s, {direction}, RSSI {}, SNR {}",
+			record.timestamp_us() as f64 / 1e6,
+			status.rssi,
+			status.snr
+		);
+		match record.header.syncword {
+			MESHCORE_SYNCWORD => decode_frame(Protocol::MeshCore, record.frame, keys),
+			MESHTASTIC_SYNCWORD => decode_frame(Protocol::Meshtastic, record.frame, keys),
+			syncword => println!("Unknown syncword {syncword:02x}\n"),
+		}
+	}
+}
+
+fn decode_input(protocol: Protocol, input: &str, keys: &mut Keys) {
+	if !Path::new(input).is_file() {
+		let frame =
+			parse_hex(input).unwrap_or_else(|| fail(&format!("{input} isn't a file or hex")));
+		decode_frame(protocol, &frame, keys);
+		return;
+	}
+
+	let bytes = fs::read(input).unwrap_or_else(|e| fail(&format!("Can't read {input}: {e}")));
+	if bytes.starts_with(&CAPTURE_MAGIC) {
+		decode_capture(&bytes, keys);
+		return;
+	}
+	let text = str::from_utf8(&bytes).unwrap_or_else(|_| fail(&format!("{input} isn't text")));
+	for line in text.lines().filter(|line| !line.trim().is_empty()) {
+		match parse_hex(line) {
+			Some(frame) => decode_frame(protocol, &frame, keys),
+			None => println!("Not hex: {line}\n"),
+		}
+	}
+}
+
+fn main() {
+	let mut protocol = Protocol::MeshCore;
+	let mut keys = Keys {
+		identities: Vec::new(),
+		contacts: Vec::new(),
+		group_psks: vec![PUBLIC_GROUP_PSK],
+		meshtastic_psks: vec![LONGFAST_KEY],
+	};
+	let mut inputs = Vec::new();
+
+	let mut argv = env::args().skip(1);
+	while let Some(arg) = argv.next() {
+		let mut value = || {
+			argv.next()
+				.unwrap_or_else(|| fail(&format!("Missing value for {arg}")))
+		};
+		match arg.as_str() {
+			"--protocol" => {
+				protocol = match value().as_str() {
+					"meshcore" => Protocol::MeshCore,
+					"meshtastic" => Protocol::Meshtastic,
+					other => fail(&format!("Unknown protocol {other}")),
+				}
+			}
+			"--identity" => keys.identities.push(parse_identity(&value())),
+			"--contact" => {
+				let contact = VerifyingKey::from_bytes(&parse_key(&value()))
+					.unwrap_or_else(|_| fail("Invalid contact public key"));
+				keys.add_contact(contact);
+			}
+			"--psk" => keys.group_psks.push(parse_psk(&value())),
+			"--meshtastic-psk" => keys.meshtastic_psks.push(parse_key(&value())),
+			"--help" | "-h" => {
+				println!("{USAGE}");
+				process::exit(0);
+			}
+			_ if arg.starts_with("--") => fail(&format!("Unknown option {arg}")),
+			_ => inputs.push(arg),
+		}
+	}
+	if inputs.is_empty() {
+		fail("Nothing to decode");
+	}
+
+	for input in &inputs {
+		decode_input(protocol, input, &mut keys);
+	}
+}
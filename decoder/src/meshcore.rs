@@ -0,0 +1,320 @@
+use crate::{Keys, to_hex};
+use ed25519_dalek::VerifyingKey;
+use lora_mesh::meshcore::{
+	PACKET_BUFFER_SIZE,
+	crypto::{SharedSecret, calculate_channel_hash, decrypt_message, msg_mac_16, msg_mac_32},
+	packet::{
+		Packet, Payload, PayloadType,
+		advert::Advert,
+		direct_packets::{AnonReqPayload, DirectPayload},
+		group_data::GroupData,
+		group_packets::GroupPayload,
+		login::LoginRequest,
+		path::ReturnedPath,
+		plain_message::PlainMessage,
+		request::{Request, Response},
+	},
+};
+
+/// Text up to the zero padding, with invalid UTF-8 shown as replacement characters
+fn text(bytes: &[u8]) -> String {
+	let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+	String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn decrypt<'a>(
+	key: &[u8; 16],
+	ciphertext: &[u8],
+	buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> &'a [u8] {
+	buffer[..ciphertext.len()].copy_from_slice(ciphertext);
+	decrypt_message(key, buffer, ciphertext.len())
+}
+
+fn print_plain_message(plaintext: &[u8]) {
+	let Ok(message) = PlainMessage::from_bytes(plaintext)
+	else {
+		println!("  Invalid message: {}", to_hex(plaintext));
+		return;
+	};
+	let flags = &message.header.flags;
+	println!(
+		"  Message: timestamp {}, flags {:02x}, {:?}, attempt {}",
+		message.header.timestamp.0.get(),
+		flags.as_raw(),
+		message.text_type,
+		flags.attempt()
+	);
+	if let Some(signer) = message.signer {
+		println!("  Signer: {}..", to_hex(signer));
+	}
+	println!("  Text: \"{}\"", text(message.text));
+}
+
+fn print_request(plaintext: &[u8]) {
+	let Ok(request) = Request::from_bytes(plaintext)
+	else {
+		println!("  Invalid request: {}", to_hex(plaintext));
+		return;
+	};
+	let tag = request.tag.0.get();
+	match request.request_type() {
+		Ok(request_type) => println!("  Request: tag {tag}, {request_type:?}"),
+		Err(_) => println!("  Request: tag {tag}, type {:02x}", request.request_type),
+	}
+	println!("  Data: {}", to_hex(request.data));
+}
+
+fn print_response(plaintext: &[u8]) {
+	let Ok(response) = Response::from_bytes(plaintext)
+	else {
+		println!("  Invalid response: {}", to_hex(plaintext));
+		return;
+	};
+	println!("  Response: tag {}", response.tag.0.get());
+	println!("  Data: {}", to_hex(response.data));
+}
+
+fn print_login(plaintext: &[u8]) {
+	let Ok(login) = LoginRequest::from_bytes(plaintext)
+	else {
+		println!("  Invalid login: {}", to_hex(plaintext));
+		return;
+	};
+	println!(
+		"  Login: timestamp {}, password {:?}",
+		login.timestamp.0.get(),
+		text(login.password)
+	);
+	// Logins to room servers carry a sync timestamp first, which only the server can tell apart
+	if let Ok(login) = LoginRequest::from_room_bytes(plaintext)
+		&& let Some(sync_since) = login.sync_since
+	{
+		println!(
+			"  Or to a room server: sync since {}, password {:?}",
+			sync_since.0.get(),
+			text(login.password)
+		);
+	}
+}
+
+fn print_advert(advert: &Advert, keys: &mut Keys) {
+	let header = &advert.header;
+	println!("  Public key: {}", to_hex(&header.pub_key));
+	println!(
+		"  Timestamp: {}, type {:?}, flags {:02x}",
+		header.timestamp.0.get(),
+		header.flags.adv_type(),
+		header.flags.as_raw()
+	);
+	if let Some(lat_long) = &advert.lat_long {
+		println!(
+			"  Location: {:.6}, {:.6}",
+			lat_long.lat.0.get() as i32 as f64 / 1e6,
+			lat_long.long.0.get() as i32 as f64 / 1e6
+		);
+	}
+	if let Some(battery) = &advert.battery {
+		println!("  Battery: {}", battery.0.0.get());
+	}
+	if let Some(temperature) = &advert.temperature {
+		println!("  Temperature: {}", temperature.0.0.get());
+	}
+	if let Some(name) = advert.name {
+		println!("  Name: \"{}\"", text(name));
+	}
+
+	if advert.verify_signature().is_err() {
+		println!("  Signature: invalid");
+		return;
+	}
+	println!("  Signature: valid");
+	if let Ok(contact) = VerifyingKey::from_bytes(&header.pub_key)
+		&& keys.add_contact(contact)
+	{
+		println!("  Added to contacts");
+	}
+}
+
+/// Finds the secret between one of our identities and a contact whose hashes match the header
+/// in either direction, and whose MAC matches
+fn find_direct_secret(payload: &DirectPayload, keys: &Keys) -> Option<SharedSecret> {
+	let DirectPayload { header, ciphertext } = payload;
+	for identity in &keys.identities {
+		let ours = identity.public_key()[0];
+		for contact in &keys.contacts {
+			let theirs = contact.as_bytes()[0];
+			if (ours, theirs) != (header.dest_hash, header.src_hash)
+				&& (ours, theirs) != (header.src_hash, header.dest_hash)
+			{
+				continue;
+			}
+			let secret = identity.calc_shared_secret(contact);
+			let mac = msg_mac_32(ciphertext, secret.as_bytes()).ok()?;
+			if mac[..2] == header.mac {
+				println!(
+					"  MAC: valid, between {}.. and {}..",
+					to_hex(&identity.public_key()[..4]),
+					to_hex(&contact.as_bytes()[..4])
+				);
+				return Some(secret);
+			}
+		}
+	}
+	println!("  MAC: no identity and contact pair matches");
+	None
+}
+
+fn print_direct(payload_type: PayloadType, payload: &DirectPayload, keys: &Keys) {
+	let header = &payload.header;
+	println!(
+		"  Destination hash: {:02x}, source hash: {:02x}, MAC: {}",
+		header.dest_hash,
+		header.src_hash,
+		to_hex(&header.mac)
+	);
+	let Some(secret) = find_direct_secret(payload, keys)
+	else {
+		return;
+	};
+
+	let mut buffer = [0; PACKET_BUFFER_SIZE];
+	let plaintext = decrypt(&secret.aes_key(), payload.ciphertext, &mut buffer);
+	match payload_type {
+		PayloadType::Txt => print_plain_message(plaintext),
+		PayloadType::Req => print_request(plaintext),
+		PayloadType::Resp => print_response(plaintext),
+		PayloadType::Path => match ReturnedPath::from_bytes(plaintext) {
+			Ok(returned) => {
+				println!("  Returned path: {}", to_hex(returned.path));
+				match returned.extra_payload_type() {
+					Ok(PayloadType::Resp) => print_response(returned.extra),
+					Ok(extra_type) => {
+						println!("  Extra: {extra_type:?}, {}", to_hex(returned.extra))
+					}
+					Err(_) => println!("  Extra: type {:02x}", returned.extra_type),
+				}
+			}
+			Err(_) => println!("  Invalid returned path: {}", to_hex(plaintext)),
+		},
+		_ => println!("  Plaintext: {}", to_hex(plaintext)),
+	}
+}
+
+fn print_anon_req(payload: &AnonReqPayload, keys: &Keys) {
+	let header = &payload.header;
+	println!(
+		"  Destination hash: {:02x}, sender: {}, MAC: {}",
+		header.dest_hash,
+		to_hex(&header.pub_key),
+		to_hex(&header.mac)
+	);
+	let Ok(sender) = VerifyingKey::from_bytes(&header.pub_key)
+	else {
+		println!("  Invalid sender public key");
+		return;
+	};
+	for identity in keys
+		.identities
+		.iter()
+		.filter(|identity| identity.public_key()[0] == header.dest_hash)
+	{
+		let secret = identity.calc_shared_secret(&sender);
+		let Ok(mac) = msg_mac_32(payload.ciphertext, secret.as_bytes())
+		else {
+			continue;
+		};
+		if mac[..2] != header.mac {
+			continue;
+		}
+		println!("  MAC: valid, to {}..", to_hex(&identity.public_key()[..4]));
+		let mut buffer = [0; PACKET_BUFFER_SIZE];
+		print_login(decrypt(&secret.aes_key(), payload.ciphertext, &mut buffer));
+		return;
+	}
+	println!("  MAC: no identity matches");
+}
+
+fn print_group(payload_type: PayloadType, payload: &GroupPayload, keys: &Keys) {
+	let header = &payload.header;
+	println!(
+		"  Channel hash: {:02x}, MAC: {}",
+		header.channel_hash,
+		to_hex(&header.mac)
+	);
+	for psk in keys
+		.group_psks
+		.iter()
+		.filter(|psk| calculate_channel_hash(psk) == header.channel_hash)
+	{
+		let Ok(mac) = msg_mac_16(payload.ciphertext, psk)
+		else {
+			continue;
+		};
+		if mac[..2] != header.mac {
+			continue;
+		}
+		println!("  MAC: valid, with secret {}", to_hex(psk));
+		let mut buffer = [0; PACKET_BUFFER_SIZE];
+		let plaintext = decrypt(psk, payload.ciphertext, &mut buffer);
+		match payload_type {
+			PayloadType::GrpText => print_plain_message(plaintext),
+			PayloadType::GrpData => match GroupData::from_bytes(plaintext) {
+				Ok(group_data) => println!(
+					"  Data: timestamp {}, type {:02x}, {}",
+					group_data.header.timestamp.0.get(),
+					group_data.header.data_type.as_raw(),
+					to_hex(group_data.data)
+				),
+				Err(_) => println!("  Invalid group data: {}", to_hex(plaintext)),
+			},
+			_ => println!("  Plaintext: {}", to_hex(plaintext)),
+		}
+		return;
+	}
+	println!("  MAC: no group secret matches");
+}
+
+pub fn decode(frame: &[u8], keys: &mut Keys) {
+	let packet = match Packet::from_bytes(frame) {
+		Ok(packet) => packet,
+		Err(e) => {
+			println!("  Malformed packet: {e}");
+			return;
+		}
+	};
+	let flags = &packet.header.flags;
+	println!(
+		"  Flags: {:02x}, route {:?}, version {:?}",
+		flags.0,
+		flags.route_type(),
+		flags.payload_version()
+	);
+	println!(
+		"  Path: {} hops, {}",
+		packet.path.len(),
+		to_hex(packet.path)
+	);
+	println!("  Hash: {}", to_hex(&packet.hash()));
+
+	let payload = match packet.parse_payload() {
+		Ok(payload) => payload,
+		Err(e) => {
+			println!("  Payload type {:x}: {e}", (flags.0 >> 2) & 0xf);
+			println!("  Payload: {}", to_hex(packet.payload));
+			return;
+		}
+	};
+	let payload_type = payload.payload_type();
+	println!("  Payload: {payload_type:?}");
+	match payload {
+		Payload::Req(x) | Payload::Resp(x) | Payload::Txt(x) | Payload::Path(x) => {
+			print_direct(payload_type, &x, keys)
+		}
+		Payload::Ack(ack) => println!("  ACK hash: {}", to_hex(&ack.hash)),
+		Payload::Advert(advert) => print_advert(&advert, keys),
+		Payload::GrpText(x) | Payload::GrpData(x) => print_group(payload_type, &x, keys),
+		Payload::AnonReq(x) => print_anon_req(&x, keys),
+		Payload::RawCustom(bytes) => println!("  Data: {}", to_hex(bytes)),
+	}
+}
@@ -0,0 +1,73 @@
+use crate::{Keys, to_hex};
+use femtopb::{EnumValue, Message};
+use lora_mesh::{
+	meshtastic::{
+		crypto::{crypt_data_128, generate_nonce},
+		packet::PacketHeader,
+	},
+	protobuf::{Data, PortNum},
+};
+use zerocopy::FromBytes;
+
+fn print_data(data: &Data) {
+	let port = match data.portnum {
+		EnumValue::Known(port) => format!("{port:?}"),
+		EnumValue::Unknown(port) => format!("unknown ({port})"),
+	};
+	println!("  Port: {port}");
+	match data.portnum {
+		EnumValue::Known(PortNum::TextMessageApp) => {
+			println!("  Text: \"{}\"", String::from_utf8_lossy(data.payload))
+		}
+		_ => println!("  Payload: {}", to_hex(data.payload)),
+	}
+	println!(
+		"  Want response: {}, dest: {:08x}, source: {:08x}",
+		data.want_response, data.dest, data.source
+	);
+	println!(
+		"  Request id: {:08x}, reply id: {:08x}, emoji: {}",
+		data.request_id, data.reply_id, data.emoji
+	);
+	if let Some(bitfield) = data.bitfield {
+		println!("  Bitfield: {bitfield:02x}");
+	}
+}
+
+pub fn decode(frame: &[u8], keys: &Keys) {
+	let Ok((header, body)) = PacketHeader::ref_from_prefix(frame)
+	else {
+		println!("  Too short for a packet header");
+		return;
+	};
+	println!(
+		"  Destination: {:08x}, sender: {:08x}, packet id: {:08x}",
+		header.dest.id(),
+		header.sender.id(),
+		header.packet_id
+	);
+	println!(
+		"  Hop limit: {}, hop start: {}, want ACK: {}, via MQTT: {}",
+		header.flags.get_hop_limit(),
+		header.flags.get_hop_start(),
+		header.flags.get_want_ack(),
+		header.flags.get_via_mqtt()
+	);
+	println!(
+		"  Channel hash: {:02x}, next hop: {:02x}, relay node: {:02x}",
+		header.channel_hash, header.next_hop, header.relay_node
+	);
+
+	// There's no MAC, so a key is right if the plaintext decodes
+	let nonce = generate_nonce(header.packet_id, header.sender.id());
+	for psk in &keys.meshtastic_psks {
+		let mut plaintext = body.to_vec();
+		crypt_data_128(&mut plaintext, *psk, nonce);
+		if let Ok(data) = Data::decode(&plaintext) {
+			println!("  Key: {}", to_hex(psk));
+			print_data(&data);
+			return;
+		}
+	}
+	println!("  No key decrypts the payload: {}", to_hex(body));
+}
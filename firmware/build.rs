@@ -11,8 +11,6 @@
 use std::{env, fs::File, io::Write, path::PathBuf};
 
 fn main() {
-	femtopb_build::compile_protos(&["protobufs/meshtastic/mesh.proto"], &["protobufs"]).unwrap();
-
 	// Put `memory.x` in our output directory and ensure it's
 	// on the linker search path.
 	let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
@@ -0,0 +1,13 @@
+MEMORY
+{
+  /* NOTE 1 K = 1 KiBi = 1024 bytes */
+  /* NRF52840 with Softdevice S140 7.3.0 */
+  FLASH : ORIGIN = 0x00000000 + 112K, LENGTH = 1024K - 112K - 24K
+  /* Copy of the identity while it's being replaced, see IDENTITY_BACKUP_ADDRESS */
+  IDENTITY_BACKUP : ORIGIN = 1024K - 24K, LENGTH = 4K
+  /* Wear levelled config store, see CONFIG_FLASH_ADDRESS */
+  CONFIG : ORIGIN = 1024K - 20K, LENGTH = 16K
+  /* Last page holds the device identity, see IDENTITY_FLASH_ADDRESS */
+  IDENTITY : ORIGIN = 1024K - 4K, LENGTH = 4K
+  RAM : ORIGIN = 0x20000000 + 0x6e28, LENGTH = 256K - 0x6e28
+}
\ No newline at end of file
@@ -0,0 +1,210 @@
+use crate::{CLIENT, identity::IMPORT_REQUESTS};
+use core::cell::Cell;
+use defmt::*;
+use lora_mesh::meshcore::client::Command;
+use nrf_softdevice::{
+	Softdevice,
+	ble::{
+		Connection, EncryptionInfo, IdentityKey, MasterId, SecurityMode,
+		advertisement_builder::{
+			Flag, LegacyAdvertisementBuilder, LegacyAdvertisementPayload, ServiceList,
+		},
+		gatt_server, peripheral,
+		security::{IoCapabilities, SecurityHandler},
+	},
+};
+use static_cell::StaticCell;
+use zeroize::Zeroize;
+
+#[nrf_softdevice::gatt_service(uuid = "9e7312e0-2354-11eb-9f10-fbc30a62cf38")]
+pub struct FooService {
+	#[characteristic(
+		uuid = "9e7312e0-2354-11eb-9f10-fbc30a63cf38",
+		read,
+		write,
+		notify,
+		indicate
+	)]
+	foo: u16,
+}
+
+/// Migration of our identity between devices. Writing a private key replaces the stored identity
+/// and reboots, and writing to `export` notifies ours on `private_key`, which is never readable.
+/// Both need a link encrypted after passkey pairing, which the softdevice enforces and the handler
+/// checks again.
+#[nrf_softdevice::gatt_service(uuid = "9e7312e0-2354-11eb-9f10-fbc30a62cf39")]
+pub struct IdentityService {
+	#[characteristic(
+		uuid = "9e7312e0-2354-11eb-9f10-fbc30a63cf39",
+		write,
+		notify,
+		security = "mitm"
+	)]
+	private_key: [u8; 32],
+	#[characteristic(
+		uuid = "9e7312e0-2354-11eb-9f10-fbc30a63cf3c",
+		write,
+		security = "mitm"
+	)]
+	export: u8,
+}
+
+/// Control of the MeshCore task. Writing 0 to `advert` sends an advert to our neighbours and 1
+/// floods it across the mesh. Writing seconds since the Unix epoch to `time` sets our clock,
+/// which restarts near zero on every boot, so that peers don't drop our adverts as stale.
+#[nrf_softdevice::gatt_service(uuid = "9e7312e0-2354-11eb-9f10-fbc30a62cf3a")]
+pub struct ControlService {
+	#[characteristic(uuid = "9e7312e0-2354-11eb-9f10-fbc30a63cf3a", write)]
+	advert: u8,
+	#[characteristic(uuid = "9e7312e0-2354-11eb-9f10-fbc30a63cf3b", write)]
+	time: u32,
+}
+
+#[nrf_softdevice::gatt_server]
+pub struct Server {
+	foo: FooService,
+	identity: IdentityService,
+	control: ControlService,
+}
+
+/// Pairs with a passkey shown in the log, bonding for as long as we're powered
+struct Bonder {
+	bond: Cell<Option<(MasterId, EncryptionInfo)>>,
+}
+
+impl SecurityHandler for Bonder {
+	fn io_capabilities(&self) -> IoCapabilities { IoCapabilities::DisplayOnly }
+
+	fn can_bond(&self, _conn: &Connection) -> bool { true }
+
+	fn display_passkey(&self, passkey: &[u8; 6]) { info!("Pairing passkey: {=[u8]:a}", passkey) }
+
+	fn on_bonded(
+		&self,
+		_conn: &Connection,
+		master_id: MasterId,
+		key: EncryptionInfo,
+		_peer_id: IdentityKey,
+	) {
+		self.bond.set(Some((master_id, key)));
+	}
+
+	fn get_key(&self, _conn: &Connection, master_id: MasterId) -> Option<EncryptionInfo> {
+		self.bond
+			.get()
+			.and_then(|(bonded, key)| (bonded == master_id).then_some(key))
+	}
+}
+
+/// Whether `conn` is encrypted with keys from authenticated pairing, security mode 1 level 3 or up
+fn is_authenticated(conn: &Connection) -> bool {
+	matches!(
+		conn.security_mode(),
+		SecurityMode::Mitm | SecurityMode::LescMitm
+	)
+}
+
+/// Queues `command` for the MeshCore task, as GATT events can't wait for room
+fn send_command(command: Command) {
+	if CLIENT.commands.try_send(command).is_err() {
+		warn!("Command queue full, dropping BLE command");
+	}
+}
+
+/// Runs the GATT server, with our `private_key` for authenticated clients to export
+pub async fn bluetooth_loop(sd: &'static Softdevice, server: Server, private_key: [u8; 32]) -> ! {
+	static ADV_DATA: LegacyAdvertisementPayload = LegacyAdvertisementBuilder::new()
+		.flags(&[Flag::GeneralDiscovery, Flag::LE_Only])
+		.full_name("HelloRust")
+		.build();
+
+	static SCAN_DATA: LegacyAdvertisementPayload = LegacyAdvertisementBuilder::new()
+		.services_128(
+			ServiceList::Complete,
+			&[0x9e7312e0_2354_11eb_9f10_fbc30a62cf38_u128.to_le_bytes()],
+		)
+		.build();
+
+	static BONDER: StaticCell<Bonder> = StaticCell::new();
+	let bonder = BONDER.init(Bonder {
+		bond: Cell::new(None),
+	});
+
+	loop {
+		let config = peripheral::Config::default();
+		let adv = peripheral::ConnectableAdvertisement::ScannableUndirected {
+			adv_data: &ADV_DATA,
+			scan_data: &SCAN_DATA,
+		};
+		let conn = peripheral::advertise_pairable(sd, adv, &config, bonder)
+			.await
+			.unwrap();
+
+		info!("advertising done!");
+
+		let e = gatt_server::run(&conn, &server, |e| match e {
+			ServerEvent::Foo(e) => match e {
+				FooServiceEvent::FooWrite(val) => {
+					info!("wrote foo: {}", val);
+					if let Err(e) = server.foo.foo_notify(&conn, &(val + 1)) {
+						info!("send notification error: {:?}", e);
+					}
+				}
+				FooServiceEvent::FooCccdWrite {
+					indications,
+					notifications,
+				} => {
+					info!(
+						"foo indications: {}, notifications: {}",
+						indications, notifications
+					)
+				}
+			},
+			ServerEvent::Identity(e) => match e {
+				IdentityServiceEvent::PrivateKeyWrite(mut private_key) => {
+					if is_authenticated(&conn) {
+						info!("Identity import requested");
+						IMPORT_REQUESTS.signal(private_key);
+					}
+					else {
+						warn!("Rejected identity import over an unauthenticated link");
+					}
+					private_key.zeroize();
+					// Don't leave the key in the attribute table
+					if let Err(e) = server.identity.private_key_set(&[0; 32]) {
+						warn!("Failed to clear written key: {:?}", e);
+					}
+				}
+				IdentityServiceEvent::ExportWrite(_) => {
+					if is_authenticated(&conn) {
+						info!("Identity export requested");
+						if let Err(e) = server.identity.private_key_notify(&conn, &private_key) {
+							warn!("Failed to send key: {:?}", e);
+						}
+						// Notifying also stores the value, so clear it as after an import
+						if let Err(e) = server.identity.private_key_set(&[0; 32]) {
+							warn!("Failed to clear sent key: {:?}", e);
+						}
+					}
+					else {
+						warn!("Rejected identity export over an unauthenticated link");
+					}
+				}
+				IdentityServiceEvent::PrivateKeyCccdWrite { .. } => (),
+			},
+			ServerEvent::Control(e) => match e {
+				ControlServiceEvent::AdvertWrite(flood @ (0 | 1)) => {
+					send_command(Command::SendAdvert { flood: flood == 1 })
+				}
+				ControlServiceEvent::AdvertWrite(value) => warn!("Invalid advert value {}", value),
+				ControlServiceEvent::TimeWrite(timestamp) => {
+					info!("Time set to {}", timestamp);
+					send_command(Command::SetTime { timestamp });
+				}
+			},
+		})
+		.await;
+
+		info!("gatt_server run exited with error: {:?}", e);
+	}
+}
@@ -0,0 +1,10 @@
+use lora_mesh::capture::CaptureSink;
+
+/// Streams capture records over RTT as log lines, which `lora-capture convert` extracts
+pub struct DefmtSink;
+
+impl CaptureSink for DefmtSink {
+	fn record(&mut self, record: &[u8]) {
+		defmt::println!("CAPTURE {=[u8]:02x}", record);
+	}
+}
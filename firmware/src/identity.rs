@@ -0,0 +1,155 @@
+use defmt::*;
+use embassy_sync::{
+	blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex},
+	mutex::Mutex,
+	signal::Signal,
+};
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+use lora_mesh::{
+	error::{Error, Result},
+	meshcore::{crypto::SigningKeys, packet::U32},
+};
+use nrf_softdevice::{Flash, Softdevice, random_bytes};
+use sha2::{Digest, Sha256};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+use zeroize::Zeroize;
+
+/// Start of the flash page reserved for the identity in `memory.x`
+pub const IDENTITY_FLASH_ADDRESS: u32 = 0xff000;
+/// Start of the flash page holding the new identity while the first is rewritten, also reserved
+/// in `memory.x`
+pub const IDENTITY_BACKUP_ADDRESS: u32 = 0xfa000;
+const IDENTITY_MAGIC: u32 = 0x544e_4449;
+
+/// Private keys received from a client to replace ours, e.g. when migrating devices
+pub static IMPORT_REQUESTS: Signal<CriticalSectionRawMutex, [u8; 32]> = Signal::new();
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct StoredIdentity {
+	magic: U32,
+	private_key: [u8; 32],
+	checksum: [u8; 4],
+}
+
+impl StoredIdentity {
+	fn new(private_key: &[u8; 32]) -> Self {
+		Self {
+			magic: U32::from(IDENTITY_MAGIC),
+			private_key: *private_key,
+			checksum: checksum(private_key),
+		}
+	}
+
+	fn is_valid(&self) -> bool {
+		self.magic.0.get() == IDENTITY_MAGIC && self.checksum == checksum(&self.private_key)
+	}
+}
+
+impl Drop for StoredIdentity {
+	fn drop(&mut self) { self.private_key.zeroize(); }
+}
+
+fn checksum(private_key: &[u8; 32]) -> [u8; 4] {
+	let sha = Sha256::new().chain_update(private_key).finalize();
+	let out = <[u8; 32]>::from(sha);
+	out[..4].try_into().unwrap()
+}
+
+async fn read_slot(flash: &mut Flash, address: u32) -> Result<StoredIdentity> {
+	let mut stored = StoredIdentity::new_zeroed();
+	flash
+		.read(address, stored.as_mut_bytes())
+		.await
+		.map_err(|_| Error::Flash)?;
+	Ok(stored)
+}
+
+/// Writes the key to the page at `address`, checking that it reads back intact
+async fn write_slot(flash: &mut Flash, address: u32, private_key: &[u8; 32]) -> Result<()> {
+	flash
+		.erase(address, address + Flash::ERASE_SIZE as u32)
+		.await
+		.map_err(|_| Error::Flash)?;
+	flash
+		.write(address, StoredIdentity::new(private_key).as_bytes())
+		.await
+		.map_err(|_| Error::Flash)?;
+	let written = read_slot(flash, address).await?;
+	if !written.is_valid() || written.private_key != *private_key {
+		return Err(Error::Flash);
+	}
+	Ok(())
+}
+
+/// Moves a verified backup into the identity page, then erases the backup so the key isn't left
+/// in two places
+async fn commit(flash: &mut Flash, private_key: &[u8; 32]) -> Result<()> {
+	write_slot(flash, IDENTITY_FLASH_ADDRESS, private_key).await?;
+	flash
+		.erase(
+			IDENTITY_BACKUP_ADDRESS,
+			IDENTITY_BACKUP_ADDRESS + Flash::ERASE_SIZE as u32,
+		)
+		.await
+		.map_err(|_| Error::Flash)
+}
+
+/// Replaces the stored identity. The key is written to the backup page first and takes over
+/// once it verifies there, so losing power part way leaves either the old key or the new one.
+async fn store(flash: &mut Flash, private_key: &[u8; 32]) -> Result<()> {
+	write_slot(flash, IDENTITY_BACKUP_ADDRESS, private_key).await?;
+	commit(flash, private_key).await
+}
+
+/// Loads our keys from flash, generating and storing a new identity on first boot
+pub async fn load_or_generate(
+	sd: &Softdevice,
+	flash: &Mutex<NoopRawMutex, Flash>,
+) -> Result<SigningKeys> {
+	let mut flash = flash.lock().await;
+
+	let backup = read_slot(&mut flash, IDENTITY_BACKUP_ADDRESS).await?;
+	if backup.is_valid() {
+		info!("Finishing an interrupted identity update");
+		commit(&mut flash, &backup.private_key).await?;
+		return Ok(SigningKeys::from_bytes(&backup.private_key));
+	}
+
+	let stored = read_slot(&mut flash, IDENTITY_FLASH_ADDRESS).await?;
+	if stored.is_valid() {
+		info!("Loaded identity from flash");
+		return Ok(SigningKeys::from_bytes(&stored.private_key));
+	}
+
+	info!("No stored identity, generating a new one");
+	let mut private_key = [0u8; 32];
+	random_bytes(sd, &mut private_key).map_err(|_| Error::CryptoError)?;
+	store(&mut flash, &private_key).await?;
+
+	let identity = SigningKeys::from_bytes(&private_key);
+	private_key.zeroize();
+	Ok(identity)
+}
+
+/// Replaces the stored identity, which takes effect on the next boot
+pub async fn import(flash: &Mutex<NoopRawMutex, Flash>, private_key: &[u8; 32]) -> Result<()> {
+	let mut flash = flash.lock().await;
+	store(&mut flash, private_key).await
+}
+
+pub async fn import_loop(flash: &Mutex<NoopRawMutex, Flash>) -> ! {
+	loop {
+		let mut private_key = IMPORT_REQUESTS.wait().await;
+		let result = import(flash, &private_key).await;
+		private_key.zeroize();
+
+		match result {
+			Ok(()) => {
+				info!("Imported identity, rebooting");
+				cortex_m::peripheral::SCB::sys_reset();
+			}
+			Err(e) => warn!("Failed to import identity: {}", Display2Format(&e)),
+		}
+	}
+}
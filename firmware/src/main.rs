@@ -0,0 +1,238 @@
+#![feature(impl_trait_in_assoc_type)]
+#![no_std]
+#![no_main]
+
+pub mod bluetooth;
+#[cfg(feature = "capture")]
+pub mod capture;
+pub mod identity;
+
+use crate::bluetooth::Server;
+use defmt::*;
+use defmt_rtt as _;
+use embassy_executor::Spawner;
+use embassy_nrf::{
+	bind_interrupts,
+	gpio::{Input, Level, Output, OutputDrive, Pull},
+	interrupt::{self, InterruptExt, Priority},
+	peripherals, spim,
+};
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use lora_mesh::{
+	config::{Settings, store::KvStore},
+	meshcore::{
+		self, MESHCORE_SYNCWORD,
+		client::{Client, Event},
+		crypto::SigningKeys,
+		repeater::RepeaterConfig,
+	},
+	meshtastic::MESHTASTIC_SYNCWORD,
+	radio::PhyRadio,
+};
+use lora_phy::{
+	LoRa,
+	iv::GenericSx126xInterfaceVariant,
+	sx126x::{self, Sx126x, Sx1262, TcxoCtrlVoltage},
+};
+use nrf_softdevice::{self as _, Flash, Softdevice, random_bytes, raw};
+use panic_probe as _;
+use rand::{SeedableRng, rngs::StdRng};
+use static_cell::StaticCell;
+
+type LoraRadio = PhyRadio<
+	Sx126x<
+		ExclusiveDevice<spim::Spim<'static, peripherals::TWISPI1>, Output<'static>, Delay>,
+		GenericSx126xInterfaceVariant<Output<'static>, Input<'static>>,
+		Sx1262,
+	>,
+	Delay,
+>;
+
+/// Records every frame to the log for the host capture tool
+#[cfg(feature = "capture")]
+type TaskRadio = lora_mesh::capture::CapturingRadio<LoraRadio, capture::DefmtSink>;
+#[cfg(not(feature = "capture"))]
+type TaskRadio = LoraRadio;
+
+bind_interrupts!(struct Irqs {
+	TWISPI1 => spim::InterruptHandler<peripherals::TWISPI1>;
+});
+
+/// Start of the flash pages reserved for the config store in `memory.x`
+const CONFIG_FLASH_ADDRESS: u32 = 0xfb000;
+const CONFIG_FLASH_PAGES: u32 = 4;
+
+/// Connects the BLE and serial clients to the MeshCore task
+static CLIENT: Client = Client::new();
+
+static FLASH: StaticCell<Mutex<NoopRawMutex, Flash>> = StaticCell::new();
+
+#[embassy_executor::task]
+async fn softdevice_task(sd: &'static Softdevice) -> ! { sd.run().await }
+
+#[embassy_executor::task]
+async fn lora_loop(radio: TaskRadio, rng: StdRng, identity: SigningKeys, settings: Settings) -> ! {
+	let repeater = RepeaterConfig {
+		enabled: settings.repeater_enabled,
+		..Default::default()
+	};
+	meshcore::lora::lora_loop(radio, rng, &CLIENT, identity, settings, repeater).await
+}
+
+#[embassy_executor::task]
+async fn identity_import_loop(flash: &'static Mutex<NoopRawMutex, Flash>) -> ! {
+	identity::import_loop(flash).await
+}
+
+/// Saves settings that admins change remotely and reboots when they ask.
+/// Other events have no client to go to yet.
+#[embassy_executor::task]
+async fn client_event_loop(flash: &'static Mutex<NoopRawMutex, Flash>) -> ! {
+	loop {
+		match CLIENT.events.receive().await {
+			Event::SettingsChanged { settings } => {
+				let mut flash = flash.lock().await;
+				let saved = match KvStore::open(
+					&mut *flash,
+					CONFIG_FLASH_ADDRESS,
+					CONFIG_FLASH_PAGES,
+				)
+				.await
+				{
+					Ok(mut store) => settings.save(&mut store).await,
+					Err(e) => Err(e),
+				};
+				match saved {
+					Ok(()) => info!("Saved settings: {}", settings),
+					Err(e) => warn!("Failed to save settings: {}", Display2Format(&e)),
+				}
+			}
+			Event::RebootRequested => {
+				info!("Reboot requested");
+				cortex_m::peripheral::SCB::sys_reset();
+			}
+			_ => (),
+		}
+	}
+}
+
+#[embassy_executor::task]
+async fn bluetooth_loop(sd: &'static Softdevice, server: Server, private_key: [u8; 32]) -> ! {
+	bluetooth::bluetooth_loop(sd, server, private_key).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+	// Reconfigure interrupt priorities to not clash with softdevice
+	let mut config = embassy_nrf::config::Config::default();
+	config.gpiote_interrupt_priority = Priority::P2;
+	config.time_interrupt_priority = Priority::P2;
+	let p = embassy_nrf::init(config);
+	interrupt::TWISPI1.set_priority(Priority::P2);
+
+	// Configure softdevice
+	let config = nrf_softdevice::Config {
+		clock: Some(raw::nrf_clock_lf_cfg_t {
+			source: raw::NRF_CLOCK_LF_SRC_RC as u8,
+			rc_ctiv: 16,
+			rc_temp_ctiv: 2,
+			accuracy: raw::NRF_CLOCK_LF_ACCURACY_500_PPM as u8,
+		}),
+		conn_gap: Some(raw::ble_gap_conn_cfg_t {
+			conn_count: 6,
+			event_length: 24,
+		}),
+		conn_gatt: Some(raw::ble_gatt_conn_cfg_t { att_mtu: 256 }),
+		gatts_attr_tab_size: Some(raw::ble_gatts_cfg_attr_tab_size_t {
+			attr_tab_size: raw::BLE_GATTS_ATTR_TAB_SIZE_DEFAULT,
+		}),
+		gap_role_count: Some(raw::ble_gap_cfg_role_count_t {
+			adv_set_count: 1,
+			periph_role_count: 3,
+		}),
+		gap_device_name: Some(raw::ble_gap_cfg_device_name_t {
+			p_value: b"HelloRust" as *const u8 as _,
+			current_len: 9,
+			max_len: 9,
+			write_perm: unsafe { core::mem::zeroed() },
+			_bitfield_1: raw::ble_gap_cfg_device_name_t::new_bitfield_1(
+				raw::BLE_GATTS_VLOC_STACK as u8,
+			),
+		}),
+		..Default::default()
+	};
+
+	let sd = Softdevice::enable(&config);
+
+	// Configure bluetooth
+	let server = Server::new(sd).unwrap();
+
+	// Flash operations complete through softdevice events, so it must be running first
+	spawner.must_spawn(softdevice_task(sd));
+
+	// Configure LORA radio
+	let nss = Output::new(p.P1_10, Level::High, OutputDrive::Standard);
+	let reset = Output::new(p.P1_06, Level::High, OutputDrive::Standard);
+	let dio1 = Input::new(p.P1_15, Pull::Down);
+	let busy = Input::new(p.P1_14, Pull::None);
+	let rf_switch_rx = Output::new(p.P1_05, Level::Low, OutputDrive::Standard);
+	let rf_switch_tx = Output::new(p.P1_07, Level::Low, OutputDrive::Standard);
+
+	let mut spi_config = spim::Config::default();
+	spi_config.frequency = spim::Frequency::M16;
+	let spim = spim::Spim::new(p.TWISPI1, Irqs, p.P1_11, p.P1_13, p.P1_12, spi_config);
+	let spi = ExclusiveDevice::new(spim, nss, Delay).unwrap();
+
+	let config = sx126x::Config {
+		chip: Sx1262,
+		tcxo_ctrl: Some(TcxoCtrlVoltage::Ctrl1V7),
+		use_dcdc: true,
+		rx_boost: false,
+	};
+	let iv = GenericSx126xInterfaceVariant::new(
+		reset,
+		dio1,
+		busy,
+		Some(rf_switch_rx),
+		Some(rf_switch_tx),
+	)
+	.unwrap();
+
+	let lora = LoRa::with_syncword(Sx126x::new(spi, iv, config), MESHCORE_SYNCWORD, Delay)
+		.await
+		.unwrap();
+
+	// Load or create our identity
+	let flash = FLASH.init(Mutex::new(Flash::take(sd)));
+	let identity = identity::load_or_generate(sd, flash).await.unwrap();
+
+	// Load settings
+	let settings = {
+		let mut flash = flash.lock().await;
+		let mut store = KvStore::open(&mut *flash, CONFIG_FLASH_ADDRESS, CONFIG_FLASH_PAGES)
+			.await
+			.unwrap();
+		Settings::load(&mut store).await.unwrap()
+	};
+	info!("Settings: {}", settings);
+
+	let radio = PhyRadio::new(lora, &settings.radio.modulation()).unwrap();
+	#[cfg(feature = "capture")]
+	let radio =
+		lora_mesh::capture::CapturingRadio::new(radio, MESHCORE_SYNCWORD, capture::DefmtSink);
+
+	// Configure RNG
+	let mut buf = [0u8; 32];
+	random_bytes(sd, &mut buf).unwrap();
+	let rng = StdRng::from_seed(buf);
+
+	info!("Setup complete");
+
+	let private_key = identity.private_key();
+	spawner.must_spawn(lora_loop(radio, rng, identity, settings));
+	spawner.must_spawn(bluetooth_loop(sd, server, private_key));
+	spawner.must_spawn(identity_import_loop(flash));
+	spawner.must_spawn(client_event_loop(flash));
+}
@@ -0,0 +1,39 @@
+//! Compares deriving the ECDH secret for every packet with reusing the one a contact caches.
+//! Run with `cargo bench -p lora-mesh`.
+
+use lora_mesh::meshcore::{contacts::Contacts, crypto::SigningKeys};
+use std::{
+	hint::black_box,
+	time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 1000;
+
+fn time_per_packet(mut f: impl FnMut()) -> Duration {
+	let start = Instant::now();
+	for _ in 0..ITERATIONS {
+		f();
+	}
+	start.elapsed() / ITERATIONS
+}
+
+fn main() {
+	let identity = SigningKeys::from_bytes(&[1; 32]);
+	let peer = SigningKeys::from_bytes(&[2; 32]).public_key();
+	let mut contacts = Contacts::new();
+	let contact = contacts.get_or_insert(&peer).unwrap();
+
+	let uncached = time_per_packet(|| {
+		black_box(identity.calc_shared_secret(black_box(contact.verifying_key())));
+	});
+	let cached = time_per_packet(|| {
+		black_box(black_box(&*contact).shared_secret(&identity));
+	});
+
+	println!("shared secret per packet, derived every time: {uncached:?}");
+	println!("shared secret per packet, cached per contact: {cached:?}");
+	println!(
+		"speedup: {:.0}x",
+		uncached.as_secs_f64() / cached.as_secs_f64().max(f64::EPSILON)
+	);
+}
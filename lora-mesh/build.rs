@@ -0,0 +1,4 @@
+fn main() {
+	femtopb_build::compile_protos(&["../protobufs/meshtastic/mesh.proto"], &["../protobufs"])
+		.unwrap();
+}
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshcore::{PACKET_BUFFER_SIZE, packet::advert::Advert};
+
+fuzz_target!(|data: &[u8]| {
+	let Ok((advert, _)) = Advert::from_bytes(data)
+	else {
+		return;
+	};
+	let _ = advert.verify_signature();
+
+	let mut buffer = [0; PACKET_BUFFER_SIZE];
+	let _ = advert.write_bytes(&mut buffer);
+});
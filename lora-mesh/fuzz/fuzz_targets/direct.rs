@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshcore::packet::{
+	direct_packets::{AnonReqPayload, DirectPayload},
+	login::LoginRequest,
+	path::ReturnedPath,
+};
+
+fuzz_target!(|data: &[u8]| {
+	let _ = DirectPayload::from_bytes(data);
+	let _ = AnonReqPayload::from_bytes(data);
+
+	// Decrypted contents of an `AnonReq` packet
+	let _ = LoginRequest::from_bytes(data);
+
+	// Decrypted contents of a `Path` packet
+	if let Ok(returned) = ReturnedPath::from_bytes(data) {
+		let _ = returned.extra_payload_type();
+	}
+});
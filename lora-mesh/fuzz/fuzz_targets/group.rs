@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshcore::packet::{group_data::GroupData, group_packets::GroupPayload};
+
+fuzz_target!(|data: &[u8]| {
+	let _ = GroupPayload::from_bytes(data);
+	let _ = GroupData::from_bytes(data);
+});
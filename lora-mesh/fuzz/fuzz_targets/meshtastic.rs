@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshtastic::lora::decode_packet;
+
+fuzz_target!(|data: &[u8]| {
+	let _ = decode_packet(&mut data.to_vec());
+});
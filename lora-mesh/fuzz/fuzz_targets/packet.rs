@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshcore::packet::Packet;
+
+fuzz_target!(|data: &[u8]| {
+	let Ok(packet) = Packet::from_bytes(data)
+	else {
+		return;
+	};
+	packet.hash();
+	let _ = packet.parse_payload();
+
+	// Whatever parses must encode back to the same bytes
+	let mut buffer = vec![0; data.len()];
+	assert_eq!(packet.to_bytes(&mut buffer).unwrap(), data);
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lora_mesh::meshcore::{
+	client::Text, crypto::msg_ack_hash, packet::plain_message::PlainMessage,
+};
+
+fuzz_target!(|data: &[u8]| {
+	let Ok(message) = PlainMessage::from_bytes(data)
+	else {
+		return;
+	};
+	// Every received text is ACKed and handed to clients
+	msg_ack_hash(&message.header, message.signer, message.text, &[0; 32]);
+	let text = Text::from_lossy(message.text);
+	assert!(str::from_utf8(text.as_bytes()).is_ok());
+});
@@ -0,0 +1,36 @@
+use embassy_time::Duration;
+
+/// LoRa modulation settings needed to work out how long a packet occupies the channel
+#[derive(Clone)]
+pub struct AirtimeParams {
+	pub spreading_factor: u8,
+	pub bandwidth_hz: u32,
+	/// Denominator of the 4/x coding rate
+	pub coding_rate: u8,
+	pub preamble_len: u16,
+	pub explicit_header: bool,
+	pub crc: bool,
+}
+
+impl AirtimeParams {
+	pub fn symbol_time_us(&self) -> u64 {
+		(1_000_000u64 << self.spreading_factor) / self.bandwidth_hz as u64
+	}
+
+	/// Time on air from the Semtech SX1262 datasheet formula
+	pub fn time_on_air(&self, payload_len: usize) -> Duration {
+		let sf = self.spreading_factor as i64;
+		let symbol_time_us = self.symbol_time_us();
+		let low_data_rate = symbol_time_us >= 16_000;
+
+		let numerator = 8 * payload_len as i64 - 4 * sf + 28 + 16 * self.crc as i64
+			- 20 * !self.explicit_header as i64;
+		let denominator = 4 * (sf - 2 * low_data_rate as i64);
+		let payload_symbols =
+			8 + (numerator.max(0) as u64).div_ceil(denominator as u64) * (self.coding_rate as u64);
+
+		// Preamble is n + 4.25 symbols
+		let preamble_us = (self.preamble_len as u64 * 4 + 17) * symbol_time_us / 4;
+		Duration::from_micros(preamble_us + payload_symbols * symbol_time_us)
+	}
+}
@@ -0,0 +1,256 @@
+//! Record format for frames seen by a radio, so traffic can be analysed offline and replayed
+//! into the protocol stack.
+//!
+//! A capture file is a [`FileHeader`] followed by records. Each record is a [`RecordHeader`]
+//! followed by `len` bytes of the raw frame. Streams, such as capture lines in a firmware log,
+//! carry the records alone.
+
+use crate::{
+	error::{Error, Result},
+	radio::{MeshRadio, Modulation},
+};
+use embassy_time::Instant;
+use lora_phy::mod_params::PacketStatus;
+use zerocopy::{
+	FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned,
+	little_endian::{I16, U32, U64},
+};
+
+pub const CAPTURE_MAGIC: [u8; 4] = *b"LCAP";
+pub const CAPTURE_VERSION: u8 = 1;
+pub const MAX_FRAME_SIZE: usize = u8::MAX as usize;
+pub const MAX_RECORD_SIZE: usize = size_of::<RecordHeader>() + MAX_FRAME_SIZE;
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+#[repr(C)]
+pub struct FileHeader {
+	pub magic: [u8; 4],
+	pub version: u8,
+}
+
+impl FileHeader {
+	pub const CURRENT: Self = Self {
+		magic: CAPTURE_MAGIC,
+		version: CAPTURE_VERSION,
+	};
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Direction {
+	Rx = 0,
+	Tx = 1,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+#[repr(C)]
+pub struct RecordHeader {
+	/// Microseconds since the capturing device booted
+	pub timestamp_us: U64,
+	pub direction: u8,
+	/// Identifies the protocol, see `MESHCORE_SYNCWORD` and `MESHTASTIC_SYNCWORD`
+	pub syncword: u8,
+	pub frequency: U32,
+	pub bandwidth_hz: U32,
+	pub spreading_factor: u8,
+	pub coding_rate: u8,
+	/// Zero for transmitted frames
+	pub rssi: I16,
+	pub snr: I16,
+	pub len: u8,
+}
+
+pub struct Record<'a> {
+	pub header: &'a RecordHeader,
+	pub frame: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+	/// Parses the record at the start of `bytes`, returning it and the bytes after it
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, &'a [u8])> {
+		let (header, tail) = RecordHeader::ref_from_prefix(bytes).map_err(|_| Error::ZeroCopy)?;
+		if tail.len() < header.len as usize {
+			return Err(Error::PacketParse);
+		}
+		let (frame, rest) = tail.split_at(header.len as usize);
+		Ok((Self { header, frame }, rest))
+	}
+
+	pub fn direction(&self) -> Result<Direction> {
+		match self.header.direction {
+			0 => Ok(Direction::Rx),
+			1 => Ok(Direction::Tx),
+			_ => Err(Error::InvalidCapture),
+		}
+	}
+
+	pub fn timestamp_us(&self) -> u64 { self.header.timestamp_us.get() }
+
+	/// Modulation of the frame, other fields are left at their usual values
+	pub fn modulation(&self) -> Modulation {
+		Modulation {
+			frequency: self.header.frequency.get(),
+			spreading_factor: self.header.spreading_factor,
+			bandwidth_hz: self.header.bandwidth_hz.get(),
+			coding_rate: self.header.coding_rate,
+			preamble_len: 8,
+			crc: true,
+			tx_power: 0,
+		}
+	}
+
+	pub fn status(&self) -> PacketStatus {
+		PacketStatus {
+			rssi: self.header.rssi.get(),
+			snr: self.header.snr.get(),
+		}
+	}
+}
+
+/// Writes a record for `frame` into `buffer`, returning the encoded bytes
+pub fn write_record<'b>(
+	buffer: &'b mut [u8; MAX_RECORD_SIZE],
+	timestamp: Instant,
+	direction: Direction,
+	syncword: u8,
+	modulation: &Modulation,
+	status: PacketStatus,
+	frame: &[u8],
+) -> Result<&'b [u8]> {
+	if frame.len() > MAX_FRAME_SIZE {
+		return Err(Error::PacketBuild);
+	}
+
+	let header = RecordHeader {
+		timestamp_us: timestamp.as_micros().into(),
+		direction: direction as u8,
+		syncword,
+		frequency: modulation.frequency.into(),
+		bandwidth_hz: modulation.bandwidth_hz.into(),
+		spreading_factor: modulation.spreading_factor,
+		coding_rate: modulation.coding_rate,
+		rssi: status.rssi.into(),
+		snr: status.snr.into(),
+		len: frame.len() as u8,
+	};
+	let header_len = size_of::<RecordHeader>();
+	buffer[..header_len].copy_from_slice(header.as_bytes());
+	buffer[header_len..header_len + frame.len()].copy_from_slice(frame);
+	Ok(&buffer[..header_len + frame.len()])
+}
+
+/// Iterates over the records in a capture stream, stopping at the first malformed one
+pub struct Records<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Records<'a> {
+	/// Records in a stream without a file header
+	pub fn from_stream(bytes: &'a [u8]) -> Self { Self { bytes } }
+
+	/// Records in a capture file, checking its header
+	pub fn from_file(bytes: &'a [u8]) -> Result<Self> {
+		let (header, bytes) = FileHeader::ref_from_prefix(bytes).map_err(|_| Error::ZeroCopy)?;
+		if header.magic != CAPTURE_MAGIC || header.version != CAPTURE_VERSION {
+			return Err(Error::InvalidCapture);
+		}
+		Ok(Self { bytes })
+	}
+}
+
+impl<'a> Iterator for Records<'a> {
+	type Item = Result<Record<'a>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.bytes.is_empty() {
+			return None;
+		}
+		match Record::from_bytes(self.bytes) {
+			Ok((record, rest)) => {
+				self.bytes = rest;
+				Some(Ok(record))
+			}
+			Err(e) => {
+				self.bytes = &[];
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+/// Somewhere to send encoded capture records, such as a log or a file
+pub trait CaptureSink {
+	fn record(&mut self, record: &[u8]);
+}
+
+/// `None` captures nothing, for switching capturing on and off at runtime
+impl<S: CaptureSink> CaptureSink for Option<S> {
+	fn record(&mut self, record: &[u8]) {
+		if let Some(sink) = self {
+			sink.record(record);
+		}
+	}
+}
+
+/// Wraps a radio, sending a record of every frame it transmits or receives to a sink
+pub struct CapturingRadio<M, S> {
+	radio: M,
+	sink: S,
+	syncword: u8,
+	modulation: Option<Modulation>,
+	buffer: [u8; MAX_RECORD_SIZE],
+}
+
+impl<M: MeshRadio, S: CaptureSink> CapturingRadio<M, S> {
+	/// `syncword` labels the records with the protocol the radio is used for
+	pub fn new(radio: M, syncword: u8, sink: S) -> Self {
+		Self {
+			radio,
+			sink,
+			syncword,
+			modulation: None,
+			buffer: [0; MAX_RECORD_SIZE],
+		}
+	}
+
+	fn capture(&mut self, direction: Direction, status: PacketStatus, frame: &[u8]) {
+		let Some(modulation) = self.modulation
+		else {
+			return;
+		};
+		match write_record(
+			&mut self.buffer,
+			Instant::now(),
+			direction,
+			self.syncword,
+			&modulation,
+			status,
+			frame,
+		) {
+			Ok(record) => self.sink.record(record),
+			Err(_) => warn!("Frame too long to capture"),
+		}
+	}
+}
+
+impl<M: MeshRadio, S: CaptureSink> MeshRadio for CapturingRadio<M, S> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.radio.set_modulation(modulation).await?;
+		self.modulation = Some(*modulation);
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		self.capture(Direction::Tx, PacketStatus { rssi: 0, snr: 0 }, frame);
+		self.radio.tx(frame).await
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let (len, status) = self.radio.rx(buffer).await?;
+		self.capture(Direction::Rx, status, &buffer[..len]);
+		Ok((len, status))
+	}
+
+	async fn cad(&mut self) -> Result<bool> { self.radio.cad().await }
+}
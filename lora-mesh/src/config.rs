@@ -0,0 +1,494 @@
+pub mod store;
+
+use crate::{
+	config::store::KvStore,
+	error::{Error, Result},
+	meshcore::{
+		contacts::MAX_NAME_SIZE,
+		packet::advert::{AdvType, LatLong},
+	},
+	radio::Modulation,
+};
+use core::ops::RangeInclusive;
+use embedded_storage_async::nor_flash::NorFlash;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// Bump when the meaning or encoding of a stored value changes, and add a step to `migrate`
+pub const SCHEMA_VERSION: u16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Key {
+	SchemaVersion = 0,
+	Frequency = 1,
+	SpreadingFactor = 2,
+	Bandwidth = 3,
+	CodingRate = 4,
+	NodeName = 5,
+	RepeaterEnabled = 6,
+	AdminPassword = 7,
+	GuestPassword = 8,
+	Role = 9,
+	TxPower = 10,
+	AdvertInterval = 11,
+	FloodAdvertInterval = 12,
+	LatLong = 13,
+	Feature1 = 14,
+	Feature2 = 15,
+}
+
+pub const MAX_PASSWORD_SIZE: usize = 15;
+
+/// Login password, which logs only show the length of
+#[derive(Clone)]
+pub struct Password {
+	bytes: [u8; MAX_PASSWORD_SIZE],
+	len: u8,
+}
+
+impl Password {
+	pub fn new(password: &[u8]) -> Result<Self> {
+		let len = password.len();
+		if len > MAX_PASSWORD_SIZE {
+			return Err(Error::InvalidConfig);
+		}
+		let mut bytes = [0; MAX_PASSWORD_SIZE];
+		bytes[..len].copy_from_slice(password);
+		Ok(Self {
+			bytes,
+			len: len as _,
+		})
+	}
+
+	pub fn as_bytes(&self) -> &[u8] { &self.bytes[..self.len as usize] }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Password {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "<{} bytes>", self.len);
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RadioSettings {
+	pub frequency: u32,
+	pub spreading_factor: u8,
+	pub bandwidth_hz: u32,
+	pub coding_rate: u8,
+	/// In dBm
+	pub tx_power: i8,
+}
+
+impl Default for RadioSettings {
+	fn default() -> Self {
+		Self {
+			frequency: 910_525_000,
+			spreading_factor: 7,
+			bandwidth_hz: 62_500,
+			coding_rate: 5,
+			tx_power: 20,
+		}
+	}
+}
+
+impl RadioSettings {
+	/// Range of the SX1262's power amplifier
+	pub const TX_POWER_RANGE: RangeInclusive<i8> = -9..=22;
+	/// Range the SX1262's synthesizer can tune to, in Hz
+	pub const FREQUENCY_RANGE: RangeInclusive<u32> = 150_000_000..=960_000_000;
+
+	/// Fails on settings the radio can't be configured with
+	pub fn validate(&self) -> Result<()> {
+		if !Self::FREQUENCY_RANGE.contains(&self.frequency)
+			|| !Self::TX_POWER_RANGE.contains(&self.tx_power)
+		{
+			return Err(Error::InvalidConfig);
+		}
+		self.modulation().lora_params().map(|_| ())
+	}
+
+	/// MeshCore framing on top of the configured channel
+	pub fn modulation(&self) -> Modulation {
+		Modulation {
+			frequency: self.frequency,
+			spreading_factor: self.spreading_factor,
+			bandwidth_hz: self.bandwidth_hz,
+			coding_rate: self.coding_rate,
+			preamble_len: 8,
+			crc: true,
+			tx_power: self.tx_power as i32,
+		}
+	}
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Settings {
+	pub radio: RadioSettings,
+	name: [u8; MAX_NAME_SIZE],
+	name_len: u8,
+	pub repeater_enabled: bool,
+	/// What we advertise as, `AdvType::Room` also hosts a room server
+	pub role: AdvType,
+	/// Empty until one is set, which disables admin logins
+	admin_password: Password,
+	/// Empty disables guest logins
+	guest_password: Password,
+	/// Minutes between adverts to our neighbours, zero for none
+	pub advert_interval_mins: u16,
+	/// Hours between adverts flooded across the mesh, zero for none
+	pub flood_advert_interval_hours: u16,
+	/// Where we are, shown to others on a map
+	pub lat_long: Option<LatLong>,
+	/// Values for the advert's two optional 2-byte fields, flagged as battery and temperature in
+	/// `AdvertFlags`
+	pub features: [Option<u16>; 2],
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		let mut settings = Self {
+			radio: RadioSettings::default(),
+			name: [0; MAX_NAME_SIZE],
+			name_len: 0,
+			repeater_enabled: false,
+			role: AdvType::Chat,
+			admin_password: Password::new(b"").unwrap(),
+			guest_password: Password::new(b"").unwrap(),
+			advert_interval_mins: 60,
+			flood_advert_interval_hours: 12,
+			lat_long: None,
+			features: [None; 2],
+		};
+		settings.set_name(b"ROBOT").unwrap();
+		settings
+	}
+}
+
+impl Settings {
+	pub fn name(&self) -> &[u8] { &self.name[..self.name_len as usize] }
+
+	pub fn set_name(&mut self, name: &[u8]) -> Result<()> {
+		if name.is_empty() || name.len() > MAX_NAME_SIZE {
+			return Err(Error::InvalidConfig);
+		}
+		self.name[..name.len()].copy_from_slice(name);
+		self.name_len = name.len() as _;
+		Ok(())
+	}
+
+	pub fn admin_password(&self) -> &[u8] { self.admin_password.as_bytes() }
+
+	pub fn set_admin_password(&mut self, password: &[u8]) -> Result<()> {
+		if password.is_empty() {
+			return Err(Error::InvalidConfig);
+		}
+		self.admin_password = Password::new(password)?;
+		Ok(())
+	}
+
+	pub fn guest_password(&self) -> &[u8] { self.guest_password.as_bytes() }
+
+	pub fn set_guest_password(&mut self, password: &[u8]) -> Result<()> {
+		self.guest_password = Password::new(password)?;
+		Ok(())
+	}
+
+	/// Reads the settings from `store`, migrating older schemas first.
+	/// Missing or invalid values fall back to their defaults.
+	pub async fn load<F: NorFlash>(store: &mut KvStore<F>) -> Result<Self> {
+		migrate(store).await?;
+
+		let defaults = Self::default();
+		let mut settings = Self {
+			radio: RadioSettings {
+				frequency: get_or(store, Key::Frequency, defaults.radio.frequency).await?,
+				spreading_factor: get_or(
+					store,
+					Key::SpreadingFactor,
+					defaults.radio.spreading_factor,
+				)
+				.await?,
+				bandwidth_hz: get_or(store, Key::Bandwidth, defaults.radio.bandwidth_hz).await?,
+				coding_rate: get_or(store, Key::CodingRate, defaults.radio.coding_rate).await?,
+				tx_power: get_or(store, Key::TxPower, defaults.radio.tx_power).await?,
+			},
+			repeater_enabled: get_or(store, Key::RepeaterEnabled, defaults.repeater_enabled as u8)
+				.await?
+				!= 0,
+			advert_interval_mins: get_or(store, Key::AdvertInterval, defaults.advert_interval_mins)
+				.await?,
+			flood_advert_interval_hours: get_or(
+				store,
+				Key::FloodAdvertInterval,
+				defaults.flood_advert_interval_hours,
+			)
+			.await?,
+			lat_long: get_optional(store, Key::LatLong).await?,
+			features: [
+				get_optional(store, Key::Feature1).await?,
+				get_optional(store, Key::Feature2).await?,
+			],
+			..defaults
+		};
+
+		let role = get_or(store, Key::Role, defaults.role as u8).await?;
+		match AdvType::try_from(role) {
+			Ok(role) => settings.role = role,
+			Err(_) => warn!("Invalid stored role {}, using default", role),
+		}
+
+		if settings.radio.validate().is_err() {
+			warn!(
+				"Invalid stored radio settings {}, using defaults",
+				settings.radio
+			);
+			settings.radio = RadioSettings::default();
+		}
+
+		let mut name = [0; MAX_NAME_SIZE];
+		match store.get_bytes(Key::NodeName as u8, &mut name).await {
+			Ok(Some(name)) => settings.set_name(name)?,
+			Ok(None) => (),
+			Err(Error::InvalidConfig) => warn!("Invalid stored node name, using default"),
+			Err(e) => return Err(e),
+		}
+
+		let mut password = [0; MAX_PASSWORD_SIZE];
+		match store
+			.get_bytes(Key::AdminPassword as u8, &mut password)
+			.await
+		{
+			Ok(Some(password)) => settings.set_admin_password(password)?,
+			Ok(None) => (),
+			Err(Error::InvalidConfig) => warn!("Invalid stored admin password, using default"),
+			Err(e) => return Err(e),
+		}
+		match store
+			.get_bytes(Key::GuestPassword as u8, &mut password)
+			.await
+		{
+			Ok(Some(password)) => settings.set_guest_password(password)?,
+			Ok(None) => (),
+			Err(Error::InvalidConfig) => warn!("Invalid stored guest password, using default"),
+			Err(e) => return Err(e),
+		}
+
+		Ok(settings)
+	}
+
+	/// Writes the settings to `store`. Values which haven't changed don't touch the flash.
+	pub async fn save<F: NorFlash>(&self, store: &mut KvStore<F>) -> Result<()> {
+		store
+			.set(Key::Frequency as u8, &self.radio.frequency)
+			.await?;
+		store
+			.set(Key::SpreadingFactor as u8, &self.radio.spreading_factor)
+			.await?;
+		store
+			.set(Key::Bandwidth as u8, &self.radio.bandwidth_hz)
+			.await?;
+		store
+			.set(Key::CodingRate as u8, &self.radio.coding_rate)
+			.await?;
+		store.set(Key::TxPower as u8, &self.radio.tx_power).await?;
+		store.set(Key::NodeName as u8, self.name()).await?;
+		store
+			.set(Key::RepeaterEnabled as u8, &(self.repeater_enabled as u8))
+			.await?;
+		store.set(Key::Role as u8, &(self.role as u8)).await?;
+		store
+			.set(Key::AdvertInterval as u8, &self.advert_interval_mins)
+			.await?;
+		store
+			.set(
+				Key::FloodAdvertInterval as u8,
+				&self.flood_advert_interval_hours,
+			)
+			.await?;
+		set_optional(store, Key::LatLong, self.lat_long.as_ref()).await?;
+		set_optional(store, Key::Feature1, self.features[0].as_ref()).await?;
+		set_optional(store, Key::Feature2, self.features[1].as_ref()).await?;
+		// Empty values can't be stored, and no value means no password
+		match self.admin_password() {
+			[] => store.remove(Key::AdminPassword as u8).await?,
+			password => store.set(Key::AdminPassword as u8, password).await?,
+		}
+		match self.guest_password() {
+			[] => store.remove(Key::GuestPassword as u8).await,
+			password => store.set(Key::GuestPassword as u8, password).await,
+		}
+	}
+}
+
+async fn get_or<F: NorFlash, T: FromBytes>(
+	store: &mut KvStore<F>,
+	key: Key,
+	default: T,
+) -> Result<T> {
+	Ok(get_optional(store, key).await?.unwrap_or(default))
+}
+
+/// Reads a value that may be unset, treating an invalid one as unset
+async fn get_optional<F: NorFlash, T: FromBytes>(
+	store: &mut KvStore<F>,
+	key: Key,
+) -> Result<Option<T>> {
+	match store.get(key as u8).await {
+		Err(Error::InvalidConfig) => {
+			warn!("Invalid stored value for {}, using default", key);
+			Ok(None)
+		}
+		result => result,
+	}
+}
+
+async fn set_optional<F: NorFlash, T: IntoBytes + Immutable>(
+	store: &mut KvStore<F>,
+	key: Key,
+	value: Option<&T>,
+) -> Result<()> {
+	match value {
+		Some(value) => store.set(key as u8, value).await,
+		None => store.remove(key as u8).await,
+	}
+}
+
+/// Brings a store written by older firmware up to `SCHEMA_VERSION`
+async fn migrate<F: NorFlash>(store: &mut KvStore<F>) -> Result<()> {
+	let version = get_or(store, Key::SchemaVersion, 0u16).await?;
+	if version == SCHEMA_VERSION {
+		return Ok(());
+	}
+	if version > SCHEMA_VERSION {
+		warn!("Config schema {} is newer than this firmware", version);
+		return Ok(());
+	}
+
+	info!("Migrating config schema {} to {}", version, SCHEMA_VERSION);
+	// Version 0 is a freshly formatted store, so there is nothing to convert for it.
+	// Later migrations go here as `if version < N { ... }` steps, oldest first.
+	if version < 2 {
+		// Version 1 saved the old well-known default admin password, which now means no admin
+		let mut password = [0; MAX_PASSWORD_SIZE];
+		let stored = store
+			.get_bytes(Key::AdminPassword as u8, &mut password)
+			.await;
+		if matches!(stored, Ok(Some(b"password"))) {
+			store.remove(Key::AdminPassword as u8).await?;
+		}
+	}
+
+	store.set(Key::SchemaVersion as u8, &SCHEMA_VERSION).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::store::ram_flash::{PAGES, RamFlash};
+	use embassy_futures::block_on;
+
+	fn open(flash: &mut RamFlash) -> KvStore<&mut RamFlash> {
+		block_on(KvStore::open(flash, 0, PAGES)).unwrap()
+	}
+
+	fn load(flash: &mut RamFlash) -> Settings {
+		block_on(Settings::load(&mut open(flash))).unwrap()
+	}
+
+	#[test]
+	fn blank_flash_loads_defaults() {
+		let mut flash = RamFlash::new();
+		let settings = load(&mut flash);
+		let defaults = Settings::default();
+		assert!(settings.radio == defaults.radio);
+		assert_eq!(settings.name(), defaults.name());
+		assert_eq!(settings.role, defaults.role);
+		assert!(!settings.repeater_enabled);
+		assert_eq!(settings.admin_password(), b"");
+		assert_eq!(settings.guest_password(), b"");
+		assert!(settings.lat_long.is_none());
+		assert_eq!(settings.features, [None; 2]);
+	}
+
+	#[test]
+	fn saved_settings_load_back() {
+		let mut flash = RamFlash::new();
+		let mut settings = Settings::default();
+		settings.radio.frequency = 915_000_000;
+		settings.radio.tx_power = 14;
+		settings.set_name(b"Hilltop").unwrap();
+		settings.repeater_enabled = true;
+		settings.role = AdvType::Room;
+		settings.set_admin_password(b"secret").unwrap();
+		settings.set_guest_password(b"hello").unwrap();
+		settings.advert_interval_mins = 30;
+		settings.flood_advert_interval_hours = 0;
+		settings.features = [Some(3700), None];
+		block_on(settings.save(&mut open(&mut flash))).unwrap();
+
+		let loaded = load(&mut flash);
+		assert!(loaded.radio == settings.radio);
+		assert_eq!(loaded.name(), b"Hilltop");
+		assert!(loaded.repeater_enabled);
+		assert_eq!(loaded.role, AdvType::Room);
+		assert_eq!(loaded.admin_password(), b"secret");
+		assert_eq!(loaded.guest_password(), b"hello");
+		assert_eq!(loaded.advert_interval_mins, 30);
+		assert_eq!(loaded.flood_advert_interval_hours, 0);
+		assert_eq!(loaded.features, [Some(3700), None]);
+
+		// Clearing a password removes it rather than storing an empty value
+		settings.set_guest_password(b"").unwrap();
+		block_on(settings.save(&mut open(&mut flash))).unwrap();
+		assert_eq!(load(&mut flash).guest_password(), b"");
+	}
+
+	#[test]
+	fn invalid_radio_settings_fall_back() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		block_on(store.set(Key::SpreadingFactor as u8, &42u8)).unwrap();
+		block_on(store.set(Key::Frequency as u8, &868_000_000u32)).unwrap();
+
+		// The radio settings are only valid together, so none of the stored ones are kept
+		assert!(load(&mut flash).radio == RadioSettings::default());
+	}
+
+	#[test]
+	fn invalid_role_and_name_fall_back() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		block_on(store.set(Key::Role as u8, &200u8)).unwrap();
+		block_on(store.set_bytes(Key::NodeName as u8, &[b'a'; MAX_NAME_SIZE + 1])).unwrap();
+
+		let settings = load(&mut flash);
+		let defaults = Settings::default();
+		assert_eq!(settings.role, defaults.role);
+		assert_eq!(settings.name(), defaults.name());
+	}
+
+	#[test]
+	fn migration_drops_the_old_default_admin_password() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		block_on(store.set(Key::SchemaVersion as u8, &1u16)).unwrap();
+		block_on(store.set(Key::AdminPassword as u8, b"password")).unwrap();
+		assert_eq!(load(&mut flash).admin_password(), b"");
+		let mut store = open(&mut flash);
+		assert_eq!(
+			block_on(store.get(Key::SchemaVersion as u8)).unwrap(),
+			Some(SCHEMA_VERSION)
+		);
+
+		// Only the old default is dropped, a password chosen under version 1 is kept
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		block_on(store.set(Key::SchemaVersion as u8, &1u16)).unwrap();
+		block_on(store.set(Key::AdminPassword as u8, b"hunter2")).unwrap();
+		assert_eq!(load(&mut flash).admin_password(), b"hunter2");
+	}
+}
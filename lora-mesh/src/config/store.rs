@@ -0,0 +1,611 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::U32,
+};
+use embedded_storage_async::nor_flash::NorFlash;
+use sha2::{Digest, Sha256};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+
+pub const MAX_KEYS: usize = 32;
+pub const MAX_VALUE_SIZE: usize = 64;
+
+const PAGE_MAGIC: u32 = 0x4746_4e43;
+const ALIGN: usize = 4;
+const ERASED: u8 = 0xff;
+const PAGE_HEADER_SIZE: u32 = size_of::<PageHeader>() as u32;
+const RECORD_HEADER_SIZE: usize = size_of::<RecordHeader>();
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct PageHeader {
+	magic: U32,
+	sequence: U32,
+}
+
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+struct RecordHeader {
+	key: u8,
+	len: u8,
+	checksum: [u8; 2],
+}
+
+impl RecordHeader {
+	fn is_erased(&self) -> bool { self.as_bytes().iter().all(|&b| b == ERASED) }
+}
+
+fn checksum(key: u8, value: &[u8]) -> [u8; 2] {
+	let sha = Sha256::new()
+		.chain_update([key, value.len() as u8])
+		.chain_update(value)
+		.finalize();
+	[sha[0], sha[1]]
+}
+
+/// Size of a record in flash, padded so every record starts on a write boundary
+fn record_size(len: usize) -> u32 { (RECORD_HEADER_SIZE + len).next_multiple_of(ALIGN) as u32 }
+
+/// Log structured key/value store spread over `page_count` erase pages.
+///
+/// Writes are appended to the active page, so a value only costs flash wear when it changes.
+/// When the active page fills up the latest value of every key is copied to the next page in
+/// the ring, which becomes active once its header is written, and the old page is erased.
+/// A torn or corrupt record ends the log: everything before it is kept and the page is
+/// compacted on the next write.
+pub struct KvStore<F> {
+	flash: F,
+	base: u32,
+	page_count: u32,
+	active_page: u32,
+	sequence: u32,
+	write_offset: u32,
+	/// Offset within the active page of the latest record for each key
+	index: [Option<u32>; MAX_KEYS],
+}
+
+impl<F: NorFlash> KvStore<F> {
+	/// Opens the store in the flash pages starting at `base`, formatting it if no page is valid
+	pub async fn open(flash: F, base: u32, page_count: u32) -> Result<Self> {
+		if page_count < 2
+			|| !ALIGN.is_multiple_of(F::WRITE_SIZE)
+			|| !ALIGN.is_multiple_of(F::READ_SIZE)
+		{
+			return Err(Error::Flash);
+		}
+
+		let mut store = Self {
+			flash,
+			base,
+			page_count,
+			active_page: 0,
+			sequence: 0,
+			write_offset: PAGE_HEADER_SIZE,
+			index: [None; MAX_KEYS],
+		};
+
+		let mut active = None;
+		for page in 0..page_count {
+			let mut header = PageHeader::new_zeroed();
+			store
+				.read(store.page_address(page), header.as_mut_bytes())
+				.await?;
+			if header.magic.0.get() != PAGE_MAGIC {
+				continue;
+			}
+			let sequence = header.sequence.0.get();
+			if active.is_none_or(|(_, active_sequence)| sequence > active_sequence) {
+				active = Some((page, sequence));
+			}
+		}
+
+		match active {
+			Some((page, sequence)) => {
+				store.active_page = page;
+				store.sequence = sequence;
+				store.load_index().await?;
+			}
+			None => {
+				info!("No config store found, formatting");
+				store.erase_page(0).await?;
+				store.write_page_header(0, 0).await?;
+			}
+		}
+
+		Ok(store)
+	}
+
+	/// Reads the raw value of `key` into `buffer`
+	pub async fn get_bytes<'b>(
+		&mut self,
+		key: u8,
+		buffer: &'b mut [u8],
+	) -> Result<Option<&'b [u8]>> {
+		let Some(offset) = self.index.get(key as usize).copied().flatten()
+		else {
+			return Ok(None);
+		};
+
+		let mut record = [0; RECORD_HEADER_SIZE + MAX_VALUE_SIZE];
+		let len = self.read_record(offset, &mut record).await?;
+		let value = &record[RECORD_HEADER_SIZE..][..len];
+
+		let buffer = buffer.get_mut(..len).ok_or(Error::InvalidConfig)?;
+		buffer.copy_from_slice(value);
+		Ok(Some(buffer))
+	}
+
+	/// Reads the value of `key`, which must have been stored with the same size as `T`
+	pub async fn get<T: FromBytes>(&mut self, key: u8) -> Result<Option<T>> {
+		let mut buffer = [0; MAX_VALUE_SIZE];
+		match self.get_bytes(key, &mut buffer).await? {
+			Some(bytes) => T::read_from_bytes(bytes)
+				.map(Some)
+				.map_err(|_| Error::InvalidConfig),
+			None => Ok(None),
+		}
+	}
+
+	pub async fn set<T: IntoBytes + Immutable + ?Sized>(
+		&mut self,
+		key: u8,
+		value: &T,
+	) -> Result<()> {
+		self.set_bytes(key, value.as_bytes()).await
+	}
+
+	pub async fn set_bytes(&mut self, key: u8, value: &[u8]) -> Result<()> {
+		if key as usize >= MAX_KEYS || value.is_empty() || value.len() > MAX_VALUE_SIZE {
+			return Err(Error::InvalidConfig);
+		}
+
+		// Rewriting an unchanged value would only wear the flash
+		let mut current = [0; MAX_VALUE_SIZE];
+		if self.get_bytes(key, &mut current).await? == Some(value) {
+			return Ok(());
+		}
+
+		self.append(key, value).await
+	}
+
+	pub async fn remove(&mut self, key: u8) -> Result<()> {
+		if self.index.get(key as usize).copied().flatten().is_none() {
+			return Ok(());
+		}
+		// A zero length record marks the key as removed
+		self.append(key, &[]).await
+	}
+
+	async fn append(&mut self, key: u8, value: &[u8]) -> Result<()> {
+		let size = record_size(value.len());
+		if self.write_offset + size > self.page_size() {
+			self.compact().await?;
+			if self.write_offset + size > self.page_size() {
+				return Err(Error::Flash);
+			}
+		}
+
+		let mut record = [ERASED; RECORD_HEADER_SIZE + MAX_VALUE_SIZE];
+		let (header, data) =
+			RecordHeader::mut_from_prefix(&mut record).map_err(|_| Error::ZeroCopy)?;
+		*header = RecordHeader {
+			key,
+			len: value.len() as u8,
+			checksum: checksum(key, value),
+		};
+		data[..value.len()].copy_from_slice(value);
+
+		let offset = self.write_offset;
+		self.write(
+			self.page_address(self.active_page) + offset,
+			&record[..size as usize],
+		)
+		.await?;
+		self.write_offset += size;
+		self.index[key as usize] = (!value.is_empty()).then_some(offset);
+
+		Ok(())
+	}
+
+	/// Moves the latest value of every key to the next page in the ring
+	async fn compact(&mut self) -> Result<()> {
+		let old_page = self.active_page;
+		let new_page = (old_page + 1) % self.page_count;
+		info!("Compacting config store into page {}", new_page);
+
+		self.erase_page(new_page).await?;
+
+		let mut write_offset = PAGE_HEADER_SIZE;
+		let mut index = [None; MAX_KEYS];
+		for key in 0..MAX_KEYS {
+			let Some(offset) = self.index[key]
+			else {
+				continue;
+			};
+
+			let mut record = [0; RECORD_HEADER_SIZE + MAX_VALUE_SIZE];
+			let len = self.read_record(offset, &mut record).await?;
+			let size = record_size(len);
+			self.write(
+				self.page_address(new_page) + write_offset,
+				&record[..size as usize],
+			)
+			.await?;
+			index[key] = Some(write_offset);
+			write_offset += size;
+		}
+
+		// The new page only becomes valid once its header is written, so losing power before
+		// this point leaves the old page active
+		self.write_page_header(new_page, self.sequence.wrapping_add(1))
+			.await?;
+		self.erase_page(old_page).await?;
+
+		self.active_page = new_page;
+		self.sequence = self.sequence.wrapping_add(1);
+		self.write_offset = write_offset;
+		self.index = index;
+
+		Ok(())
+	}
+
+	/// Replays the active page's log to find the latest record for each key
+	async fn load_index(&mut self) -> Result<()> {
+		self.index = [None; MAX_KEYS];
+		let mut offset = PAGE_HEADER_SIZE;
+
+		while offset + RECORD_HEADER_SIZE as u32 <= self.page_size() {
+			let mut header = RecordHeader::new_zeroed();
+			self.read(
+				self.page_address(self.active_page) + offset,
+				header.as_mut_bytes(),
+			)
+			.await?;
+			if header.is_erased() {
+				break;
+			}
+
+			let mut record = [0; RECORD_HEADER_SIZE + MAX_VALUE_SIZE];
+			let len = match self.read_record(offset, &mut record).await {
+				Ok(len) => len,
+				Err(Error::InvalidConfig) => {
+					warn!(
+						"Corrupt config record at offset {}, ignoring the rest of the page",
+						offset
+					);
+					// Refuse to append after the corruption so the next write compacts
+					offset = self.page_size();
+					break;
+				}
+				Err(e) => return Err(e),
+			};
+
+			self.index[header.key as usize] = (len != 0).then_some(offset);
+			offset += record_size(len);
+		}
+
+		self.write_offset = offset;
+		Ok(())
+	}
+
+	/// Reads and validates the record at `offset` in the active page, returning its value length
+	async fn read_record(
+		&mut self,
+		offset: u32,
+		record: &mut [u8; RECORD_HEADER_SIZE + MAX_VALUE_SIZE],
+	) -> Result<usize> {
+		let address = self.page_address(self.active_page) + offset;
+		let mut header = RecordHeader::new_zeroed();
+		self.read(address, header.as_mut_bytes()).await?;
+		let len = header.len as usize;
+
+		if header.key as usize >= MAX_KEYS
+			|| len > MAX_VALUE_SIZE
+			|| offset + record_size(len) > self.page_size()
+		{
+			return Err(Error::InvalidConfig);
+		}
+
+		let size = record_size(len) as usize;
+		record[..RECORD_HEADER_SIZE].copy_from_slice(header.as_bytes());
+		self.read(
+			address + RECORD_HEADER_SIZE as u32,
+			&mut record[RECORD_HEADER_SIZE..size],
+		)
+		.await?;
+		if checksum(header.key, &record[RECORD_HEADER_SIZE..][..len]) != header.checksum {
+			return Err(Error::InvalidConfig);
+		}
+
+		Ok(len)
+	}
+
+	async fn write_page_header(&mut self, page: u32, sequence: u32) -> Result<()> {
+		let header = PageHeader {
+			magic: U32::from(PAGE_MAGIC),
+			sequence: U32::from(sequence),
+		};
+		self.write(self.page_address(page), header.as_bytes()).await
+	}
+
+	async fn erase_page(&mut self, page: u32) -> Result<()> {
+		let address = self.page_address(page);
+		self.flash
+			.erase(address, address + self.page_size())
+			.await
+			.map_err(|_| Error::Flash)
+	}
+
+	async fn read(&mut self, address: u32, bytes: &mut [u8]) -> Result<()> {
+		self.flash
+			.read(address, bytes)
+			.await
+			.map_err(|_| Error::Flash)
+	}
+
+	async fn write(&mut self, address: u32, bytes: &[u8]) -> Result<()> {
+		self.flash
+			.write(address, bytes)
+			.await
+			.map_err(|_| Error::Flash)
+	}
+
+	fn page_size(&self) -> u32 { F::ERASE_SIZE as u32 }
+
+	fn page_address(&self, page: u32) -> u32 { self.base + page * self.page_size() }
+}
+
+/// Flash in RAM for testing the store and what's kept in it
+#[cfg(test)]
+pub(crate) mod ram_flash {
+	use super::ERASED;
+	use embedded_storage_async::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+	pub(crate) const PAGE_SIZE: usize = 256;
+	pub(crate) const PAGES: u32 = 2;
+
+	/// NOR flash in RAM, which like the real thing can only clear bits between erases.
+	/// Setting `power_budget` cuts the power after that many more bytes are written.
+	pub(crate) struct RamFlash {
+		pub(crate) bytes: Vec<u8>,
+		pub(crate) power_budget: Option<usize>,
+	}
+
+	impl RamFlash {
+		pub(crate) fn new() -> Self {
+			Self {
+				bytes: vec![ERASED; PAGE_SIZE * PAGES as usize],
+				power_budget: None,
+			}
+		}
+	}
+
+	impl ErrorType for RamFlash {
+		type Error = NorFlashErrorKind;
+	}
+
+	impl ReadNorFlash for RamFlash {
+		const READ_SIZE: usize = 1;
+
+		async fn read(
+			&mut self,
+			offset: u32,
+			bytes: &mut [u8],
+		) -> core::result::Result<(), Self::Error> {
+			let offset = offset as usize;
+			let source = self
+				.bytes
+				.get(offset..offset + bytes.len())
+				.ok_or(NorFlashErrorKind::OutOfBounds)?;
+			bytes.copy_from_slice(source);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize { self.bytes.len() }
+	}
+
+	impl NorFlash for RamFlash {
+		const WRITE_SIZE: usize = 4;
+		const ERASE_SIZE: usize = PAGE_SIZE;
+
+		async fn erase(&mut self, from: u32, to: u32) -> core::result::Result<(), Self::Error> {
+			assert!(
+				(from as usize).is_multiple_of(PAGE_SIZE)
+					&& (to as usize).is_multiple_of(PAGE_SIZE)
+			);
+			self.bytes[from as usize..to as usize].fill(ERASED);
+			Ok(())
+		}
+
+		async fn write(
+			&mut self,
+			offset: u32,
+			bytes: &[u8],
+		) -> core::result::Result<(), Self::Error> {
+			let offset = offset as usize;
+			assert!(
+				offset.is_multiple_of(Self::WRITE_SIZE)
+					&& bytes.len().is_multiple_of(Self::WRITE_SIZE)
+			);
+			let len = match &mut self.power_budget {
+				Some(budget) => {
+					let len = bytes.len().min(*budget);
+					*budget -= len;
+					len
+				}
+				None => bytes.len(),
+			};
+			for (cell, &byte) in self.bytes[offset..offset + len].iter_mut().zip(bytes) {
+				*cell &= byte;
+			}
+			if len < bytes.len() {
+				return Err(NorFlashErrorKind::Other);
+			}
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::store::ram_flash::{PAGE_SIZE, PAGES, RamFlash};
+	use embassy_futures::block_on;
+
+	fn open(flash: &mut RamFlash) -> KvStore<&mut RamFlash> {
+		block_on(KvStore::open(flash, 0, PAGES)).unwrap()
+	}
+
+	fn get(store: &mut KvStore<&mut RamFlash>, key: u8) -> Option<u32> {
+		block_on(store.get(key)).unwrap()
+	}
+
+	fn set(store: &mut KvStore<&mut RamFlash>, key: u8, value: u32) -> Result<()> {
+		block_on(store.set(key, &value))
+	}
+
+	#[test]
+	fn formats_blank_flash() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), None);
+		assert_eq!(store.flash.bytes[..4], PAGE_MAGIC.to_le_bytes());
+
+		// Reopening finds the formatted page rather than formatting again
+		let mut store = open(&mut flash);
+		set(&mut store, 1, 42).unwrap();
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), Some(42));
+	}
+
+	#[test]
+	fn overwrites_keep_the_latest_value() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		set(&mut store, 1, 1).unwrap();
+		set(&mut store, 2, 2).unwrap();
+		set(&mut store, 1, 3).unwrap();
+		assert_eq!(get(&mut store, 1), Some(3));
+
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), Some(3));
+		assert_eq!(get(&mut store, 2), Some(2));
+	}
+
+	#[test]
+	fn unchanged_values_are_not_rewritten() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		set(&mut store, 1, 7).unwrap();
+		let write_offset = store.write_offset;
+		set(&mut store, 1, 7).unwrap();
+		assert_eq!(store.write_offset, write_offset);
+	}
+
+	#[test]
+	fn compacts_across_pages() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		block_on(store.set_bytes(3, b"kept through every compaction")).unwrap();
+		// Each record takes 8 bytes, so this wraps around the ring of pages many times
+		for value in 0..1000 {
+			set(&mut store, 1, value).unwrap();
+			set(&mut store, 2, value * 2).unwrap();
+		}
+		assert!(store.sequence > PAGES);
+
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), Some(999));
+		assert_eq!(get(&mut store, 2), Some(1998));
+		let mut buffer = [0; MAX_VALUE_SIZE];
+		assert_eq!(
+			block_on(store.get_bytes(3, &mut buffer)).unwrap(),
+			Some(&b"kept through every compaction"[..])
+		);
+	}
+
+	#[test]
+	fn torn_record_keeps_the_previous_value() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		set(&mut store, 1, 1).unwrap();
+		set(&mut store, 2, 2).unwrap();
+		store.flash.power_budget = Some(4);
+		assert!(set(&mut store, 1, 5).is_err());
+		flash.power_budget = None;
+
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), Some(1));
+		assert_eq!(get(&mut store, 2), Some(2));
+
+		// Nothing is appended after the torn record, the next write compacts instead
+		let sequence = store.sequence;
+		set(&mut store, 1, 6).unwrap();
+		assert_eq!(store.sequence, sequence + 1);
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), Some(6));
+		assert_eq!(get(&mut store, 2), Some(2));
+	}
+
+	#[test]
+	fn power_loss_during_compaction_keeps_the_old_page() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		set(&mut store, 3, 3).unwrap();
+		let mut value = 0;
+		// Fill the page up to just before it needs compacting
+		while store.write_offset + record_size(size_of::<u32>()) <= PAGE_SIZE as u32 {
+			value += 1;
+			set(&mut store, 1, value).unwrap();
+		}
+
+		// Power goes out after copying the first record to the new page
+		store.flash.power_budget = Some(record_size(size_of::<u32>()) as usize);
+		assert!(set(&mut store, 2, 2).is_err());
+		flash.power_budget = None;
+
+		let mut store = open(&mut flash);
+		assert_eq!(store.active_page, 0);
+		assert_eq!(get(&mut store, 1), Some(value));
+		assert_eq!(get(&mut store, 2), None);
+		assert_eq!(get(&mut store, 3), Some(3));
+
+		// Compacting again starts over from a fresh erase
+		set(&mut store, 2, 2).unwrap();
+		let mut store = open(&mut flash);
+		assert_eq!(store.active_page, 1);
+		assert_eq!(get(&mut store, 1), Some(value));
+		assert_eq!(get(&mut store, 2), Some(2));
+		assert_eq!(get(&mut store, 3), Some(3));
+	}
+
+	#[test]
+	fn removed_keys_stay_removed() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		set(&mut store, 1, 1).unwrap();
+		set(&mut store, 2, 2).unwrap();
+		block_on(store.remove(1)).unwrap();
+		assert_eq!(get(&mut store, 1), None);
+
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), None);
+		assert_eq!(get(&mut store, 2), Some(2));
+
+		// Compacting drops the removed key rather than resurrecting an older value
+		for value in 0..100 {
+			set(&mut store, 2, value).unwrap();
+		}
+		let mut store = open(&mut flash);
+		assert_eq!(get(&mut store, 1), None);
+		assert_eq!(get(&mut store, 2), Some(99));
+	}
+
+	#[test]
+	fn rejects_invalid_keys_and_values() {
+		let mut flash = RamFlash::new();
+		let mut store = open(&mut flash);
+		assert!(block_on(store.set_bytes(MAX_KEYS as u8, b"x")).is_err());
+		assert!(block_on(store.set_bytes(1, &[])).is_err());
+		assert!(block_on(store.set_bytes(1, &[0; MAX_VALUE_SIZE + 1])).is_err());
+	}
+}
@@ -10,6 +10,8 @@ pub enum Error {
 	ZeroCopy,
 	#[error("Packet parsing error")]
 	PacketParse,
+	#[error("Packet building error")]
+	PacketBuild,
 	#[error("Protobuf decode error: {0:?}")]
 	ProtobufDecode(femtopb::error::DecodeError),
 	#[error("Protobuf enccode error: {0:?}")]
@@ -18,4 +20,26 @@ pub enum Error {
 	InvalidMAC,
 	#[error("Cryptographic error")]
 	CryptoError,
+	#[error("Stale advert")]
+	StaleAdvert,
+	#[error("Message too long")]
+	MessageTooLong,
+	#[error("Unknown contact")]
+	UnknownContact,
+	#[error("Invalid channel")]
+	InvalidChannel,
+	#[error("Unknown channel")]
+	UnknownChannel,
+	#[error("Permission denied")]
+	PermissionDenied,
+	#[error("Queue full")]
+	QueueFull,
+	#[error("Flash error")]
+	Flash,
+	#[error("Invalid config value")]
+	InvalidConfig,
+	#[error("Invalid capture")]
+	InvalidCapture,
+	#[error("Time can't go backwards")]
+	TimeBackwards,
 }
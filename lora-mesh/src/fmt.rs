@@ -0,0 +1,47 @@
+//! Logging macros that forward to defmt when the `defmt` feature is enabled and otherwise
+//! compile away, so the protocol code also builds on hosts without a defmt logger.
+#![macro_use]
+#![allow(unused_macros)]
+
+macro_rules! debug {
+	($s:literal $(, $x:expr)* $(,)?) => {{
+		#[cfg(feature = "defmt")]
+		::defmt::debug!($s $(, $x)*);
+		#[cfg(not(feature = "defmt"))]
+		let _ = ($(&$x),*);
+	}};
+}
+
+macro_rules! info {
+	($s:literal $(, $x:expr)* $(,)?) => {{
+		#[cfg(feature = "defmt")]
+		::defmt::info!($s $(, $x)*);
+		#[cfg(not(feature = "defmt"))]
+		let _ = ($(&$x),*);
+	}};
+}
+
+macro_rules! warn {
+	($s:literal $(, $x:expr)* $(,)?) => {{
+		#[cfg(feature = "defmt")]
+		::defmt::warn!($s $(, $x)*);
+		#[cfg(not(feature = "defmt"))]
+		let _ = ($(&$x),*);
+	}};
+}
+
+macro_rules! error {
+	($s:literal $(, $x:expr)* $(,)?) => {{
+		#[cfg(feature = "defmt")]
+		::defmt::error!($s $(, $x)*);
+		#[cfg(not(feature = "defmt"))]
+		let _ = ($(&$x),*);
+	}};
+}
+
+#[cfg(feature = "defmt")]
+pub use defmt::Display2Format;
+
+/// Stand-in for defmt's wrapper so log arguments still type check without it
+#[cfg(not(feature = "defmt"))]
+pub struct Display2Format<'a, T: ?Sized>(pub &'a T);
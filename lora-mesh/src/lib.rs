@@ -0,0 +1,14 @@
+#![feature(slice_as_array)]
+#![cfg_attr(not(test), no_std)]
+
+// Must come first so the logging macros are visible to the other modules
+mod fmt;
+
+pub mod airtime;
+pub mod capture;
+pub mod config;
+pub mod error;
+pub mod meshcore;
+pub mod meshtastic;
+pub mod protobuf;
+pub mod radio;
@@ -0,0 +1,97 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::client::Text,
+};
+use embassy_time::{Duration, Instant};
+
+pub const MAX_PENDING_ACKS: usize = 8;
+/// The attempt counter only has two bits in `MessageFlags`
+pub const MAX_ATTEMPTS: u8 = 4;
+
+#[derive(Clone)]
+pub struct RetryConfig {
+	pub max_attempts: u8,
+	pub base_timeout: Duration,
+}
+
+impl Default for RetryConfig {
+	fn default() -> Self {
+		Self {
+			max_attempts: 3,
+			base_timeout: Duration::from_secs(4),
+		}
+	}
+}
+
+/// A sent text still waiting for its ACK
+#[derive(Clone)]
+pub struct PendingMessage {
+	pub id: u32,
+	pub dest: [u8; 32],
+	pub text: Text,
+	pub timestamp: u32,
+	pub attempt: u8,
+	pub ack_hash: [u8; 4],
+	pub deadline: Instant,
+}
+
+pub struct PendingAcks {
+	config: RetryConfig,
+	pending: [Option<PendingMessage>; MAX_PENDING_ACKS],
+}
+
+impl PendingAcks {
+	pub fn new(config: RetryConfig) -> Self {
+		Self {
+			config,
+			pending: [const { None }; MAX_PENDING_ACKS],
+		}
+	}
+
+	pub fn config(&self) -> &RetryConfig { &self.config }
+
+	/// Whether another attempt is allowed after `attempt`
+	pub fn can_retry(&self, attempt: u8) -> bool {
+		attempt + 1 < self.config.max_attempts.min(MAX_ATTEMPTS)
+	}
+
+	/// How long to wait for an ACK to `attempt`, doubling with each retry
+	pub fn timeout(&self, attempt: u8) -> Duration { self.config.base_timeout * (1 << attempt) }
+
+	pub fn insert(&mut self, message: PendingMessage) -> Result<()> {
+		let slot = self
+			.pending
+			.iter_mut()
+			.find(|slot| slot.is_none())
+			.ok_or(Error::QueueFull)?;
+		*slot = Some(message);
+		Ok(())
+	}
+
+	/// Removes and returns the message matching a received ACK
+	pub fn acknowledge(&mut self, ack_hash: &[u8; 4]) -> Option<PendingMessage> {
+		self.pending
+			.iter_mut()
+			.find(|slot| {
+				slot.as_ref()
+					.is_some_and(|message| &message.ack_hash == ack_hash)
+			})
+			.and_then(Option::take)
+	}
+
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.pending
+			.iter()
+			.flatten()
+			.map(|message| message.deadline)
+			.min()
+	}
+
+	/// Removes and returns a message whose ACK has timed out
+	pub fn take_expired(&mut self, now: Instant) -> Option<PendingMessage> {
+		self.pending
+			.iter_mut()
+			.find(|slot| slot.as_ref().is_some_and(|message| message.deadline <= now))
+			.and_then(Option::take)
+	}
+}
@@ -0,0 +1,68 @@
+use crate::config::Settings;
+use embassy_time::{Duration, Instant};
+use rand_core::RngCore;
+
+/// Longest random delay added to each scheduled advert, so neighbours that booted together
+/// don't keep colliding
+pub const MAX_ADVERT_JITTER: Duration = Duration::from_secs(30);
+
+/// When to send our next adverts, zero-hop ones to our neighbours and flooded ones to the mesh,
+/// at the intervals in `Settings`
+pub struct AdvertSchedule {
+	next_zero_hop: Option<Instant>,
+	next_flood: Option<Instant>,
+}
+
+impl AdvertSchedule {
+	/// Starts with a zero-hop advert shortly, so neighbours learn about us or our new settings
+	pub fn new(settings: &Settings, now: Instant, rng: &mut impl RngCore) -> Self {
+		Self {
+			next_zero_hop: Some(now + jitter(rng)),
+			next_flood: flood_interval(settings).map(|interval| now + interval + jitter(rng)),
+		}
+	}
+
+	pub fn next_due(&self) -> Option<Instant> {
+		[self.next_zero_hop, self.next_flood]
+			.into_iter()
+			.flatten()
+			.min()
+	}
+
+	/// Whether the advert due by `now` is flooded, if one is due
+	pub fn due(&self, now: Instant) -> Option<bool> {
+		if self.next_flood.is_some_and(|next| next <= now) {
+			Some(true)
+		}
+		else if self.next_zero_hop.is_some_and(|next| next <= now) {
+			Some(false)
+		}
+		else {
+			None
+		}
+	}
+
+	/// Schedules the following adverts once one was sent, whether it was due or asked for.
+	/// A flooded advert reaches our neighbours too, so it also stands in for a zero-hop one.
+	pub fn sent(&mut self, settings: &Settings, flood: bool, now: Instant, rng: &mut impl RngCore) {
+		if flood {
+			self.next_flood = flood_interval(settings).map(|interval| now + interval + jitter(rng));
+		}
+		self.next_zero_hop =
+			zero_hop_interval(settings).map(|interval| now + interval + jitter(rng));
+	}
+}
+
+fn zero_hop_interval(settings: &Settings) -> Option<Duration> {
+	let minutes = settings.advert_interval_mins as u64;
+	(minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+fn flood_interval(settings: &Settings) -> Option<Duration> {
+	let hours = settings.flood_advert_interval_hours as u64;
+	(hours > 0).then(|| Duration::from_secs(hours * 60 * 60))
+}
+
+fn jitter(rng: &mut impl RngCore) -> Duration {
+	Duration::from_millis(rng.next_u64() % MAX_ADVERT_JITTER.as_millis())
+}
@@ -0,0 +1,153 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		crypto::{
+			PUBLIC_GROUP_PSK, calculate_channel_hash, decrypt_message, encrypt_message, msg_mac_16,
+		},
+		packet::group_packets::{GroupHeader, GroupPayload},
+	},
+};
+use sha2::{Digest, Sha256};
+
+pub const MAX_CHANNELS: usize = 8;
+pub const MAX_CHANNEL_NAME_SIZE: usize = 32;
+pub const PUBLIC_CHANNEL_NAME: &str = "Public";
+
+/// A group channel, whose members share a 16 byte secret
+#[derive(Clone)]
+pub struct Channel {
+	name: [u8; MAX_CHANNEL_NAME_SIZE],
+	name_len: u8,
+	secret: [u8; 16],
+	hash: u8,
+}
+
+impl Channel {
+	pub fn new(name: &str, secret: [u8; 16]) -> Result<Self> {
+		let len = name.len();
+		if len > MAX_CHANNEL_NAME_SIZE {
+			return Err(Error::InvalidChannel);
+		}
+		let mut bytes = [0; MAX_CHANNEL_NAME_SIZE];
+		bytes[..len].copy_from_slice(name.as_bytes());
+		Ok(Self {
+			name: bytes,
+			name_len: len as _,
+			secret,
+			hash: calculate_channel_hash(&secret),
+		})
+	}
+
+	/// A hashtag channel, open to anyone who knows its name.
+	/// The secret is the start of the SHA-256 of the name, including the leading `#`.
+	pub fn hashtag(name: &str) -> Result<Self> {
+		let Some(tag) = name.strip_prefix('#')
+		else {
+			return Err(Error::InvalidChannel);
+		};
+		if tag.is_empty() {
+			return Err(Error::InvalidChannel);
+		}
+		let sha = <[u8; 32]>::from(Sha256::digest(name.as_bytes()));
+		Self::new(name, <[u8; 16]>::try_from(&sha[..16]).unwrap())
+	}
+
+	/// The channel every node starts with, using the well known public secret
+	pub fn public() -> Self { Self::new(PUBLIC_CHANNEL_NAME, PUBLIC_GROUP_PSK).unwrap() }
+
+	pub fn name(&self) -> &[u8] { &self.name[..self.name_len as usize] }
+
+	pub fn secret(&self) -> &[u8; 16] { &self.secret }
+
+	pub fn hash(&self) -> u8 { self.hash }
+
+	fn mac_matches(&self, payload: &GroupPayload) -> bool {
+		msg_mac_16(payload.ciphertext, &self.secret).is_ok_and(|mac| mac[..2] == payload.header.mac)
+	}
+
+	/// Encrypts the first `len` bytes of `buffer` in place for this channel
+	pub fn encrypt<'a>(
+		&self,
+		buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+		len: usize,
+	) -> Result<GroupPayload<'a>> {
+		let ciphertext = encrypt_message(&self.secret, buffer, len);
+		let mac = msg_mac_16(ciphertext, &self.secret)?;
+		Ok(GroupPayload {
+			header: GroupHeader {
+				channel_hash: self.hash,
+				mac: [mac[0], mac[1]],
+			},
+			ciphertext,
+		})
+	}
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Channel {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(
+			fmt,
+			"Channel {{ name: {}, hash: {:02x} }}",
+			str::from_utf8(self.name()).unwrap_or("<invalid>"),
+			self.hash
+		);
+	}
+}
+
+/// Fixed table of the group channels we are on, addressed by slot like the companion apps do.
+/// Slot 0 starts out as the public channel.
+pub struct Channels {
+	channels: [Option<Channel>; MAX_CHANNELS],
+}
+
+impl Channels {
+	pub fn new() -> Self {
+		let mut channels = [const { None }; MAX_CHANNELS];
+		channels[0] = Some(Channel::public());
+		Self { channels }
+	}
+
+	pub fn get(&self, index: usize) -> Option<&Channel> { self.channels.get(index)?.as_ref() }
+
+	/// Puts `channel` in slot `index`, or clears the slot
+	pub fn set(&mut self, index: usize, channel: Option<Channel>) -> Result<()> {
+		let slot = self.channels.get_mut(index).ok_or(Error::InvalidChannel)?;
+		*slot = channel;
+		Ok(())
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (usize, &Channel)> {
+		self.channels
+			.iter()
+			.enumerate()
+			.filter_map(|(index, slot)| Some((index, slot.as_ref()?)))
+	}
+
+	/// All channels whose secret hashes to `hash`, as more than one may collide
+	pub fn matching_hash(&self, hash: u8) -> impl Iterator<Item = (usize, &Channel)> {
+		self.iter().filter(move |(_, channel)| channel.hash == hash)
+	}
+
+	/// Tries each channel matching the payload's hash until a MAC matches, then decrypts with it.
+	/// Returns the slot of the channel and the plaintext.
+	pub fn decrypt<'a>(
+		&self,
+		payload: &GroupPayload,
+		buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+	) -> Result<(usize, &'a [u8])> {
+		let (index, channel) = self
+			.matching_hash(payload.header.channel_hash)
+			.find(|(_, channel)| channel.mac_matches(payload))
+			.ok_or(Error::InvalidMAC)?;
+
+		let len = payload.ciphertext.len();
+		buffer[..len].copy_from_slice(payload.ciphertext);
+		Ok((index, decrypt_message(&channel.secret, buffer, len)))
+	}
+}
+
+impl Default for Channels {
+	fn default() -> Self { Self::new() }
+}
@@ -0,0 +1,209 @@
+use crate::{
+	config::Settings,
+	meshcore::{
+		client::Text,
+		neighbours::Neighbours,
+		packet::{U32, advert::LatLong},
+		stats::NodeStats,
+	},
+};
+use core::fmt::{self, Write};
+use embassy_time::Instant;
+use zerocopy::FromZeros;
+
+/// Bytes of each neighbour's public key listed by `neighbors`
+const NEIGHBOUR_PREFIX_SIZE: usize = 4;
+
+/// What the MeshCore task does after a command, besides replying with its output
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CliAction {
+	None,
+	/// The settings changed and need saving
+	SaveSettings,
+	SendAdvert,
+	Reboot,
+}
+
+/// The state commands read and change
+pub struct CliContext<'a> {
+	pub settings: &'a mut Settings,
+	pub stats: &'a NodeStats,
+	pub neighbours: &'a Neighbours,
+	pub now: Instant,
+}
+
+/// Runs a command from an admin, writing its output into `reply`.
+/// Commands follow the reference firmware's CLI, such as `set freq 910.525` or `neighbors`.
+pub fn execute(command: &str, context: CliContext, reply: &mut Text) -> CliAction {
+	// Writing to a `Text` never fails
+	run(command, context, reply).unwrap_or(CliAction::None)
+}
+
+fn run(command: &str, context: CliContext, reply: &mut Text) -> Result<CliAction, fmt::Error> {
+	let command = command.trim();
+	let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+	let args = args.trim();
+	match name {
+		"advert" => {
+			write!(reply, "OK - Advert sent")?;
+			Ok(CliAction::SendAdvert)
+		}
+		"reboot" => {
+			write!(reply, "OK - rebooting")?;
+			Ok(CliAction::Reboot)
+		}
+		"neighbors" => {
+			write_neighbours(context.neighbours, context.now, reply)?;
+			Ok(CliAction::None)
+		}
+		"stats" => {
+			write_stats(context.stats, context.now, reply)?;
+			Ok(CliAction::None)
+		}
+		"password" => match context.settings.set_admin_password(args.as_bytes()) {
+			Ok(()) => {
+				write!(reply, "OK")?;
+				Ok(CliAction::SaveSettings)
+			}
+			Err(_) => {
+				write!(reply, "Error: password must be 1 to 15 bytes")?;
+				Ok(CliAction::None)
+			}
+		},
+		"set" => set(args, context.settings, reply),
+		_ => {
+			write!(reply, "Unknown command")?;
+			Ok(CliAction::None)
+		}
+	}
+}
+
+fn set(args: &str, settings: &mut Settings, reply: &mut Text) -> Result<CliAction, fmt::Error> {
+	let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+	let value = value.trim();
+	let mut radio = settings.radio;
+	let parsed = match name {
+		"name" => settings.set_name(value.as_bytes()).ok(),
+		// An empty password disables guest logins
+		"guest.password" => settings.set_guest_password(value.as_bytes()).ok(),
+		"freq" => parse_scaled(value, 6).map(|hz| radio.frequency = hz),
+		"bw" => parse_scaled(value, 3).map(|hz| radio.bandwidth_hz = hz),
+		"sf" => value.parse().ok().map(|sf| radio.spreading_factor = sf),
+		"cr" => value.parse().ok().map(|cr| radio.coding_rate = cr),
+		"tx" => value.parse().ok().map(|dbm| radio.tx_power = dbm),
+		"advert.interval" => value
+			.parse()
+			.ok()
+			.map(|minutes| settings.advert_interval_mins = minutes),
+		"flood.advert.interval" => value
+			.parse()
+			.ok()
+			.map(|hours| settings.flood_advert_interval_hours = hours),
+		"feature1" => parse_feature(value).map(|feature| settings.features[0] = feature),
+		"feature2" => parse_feature(value).map(|feature| settings.features[1] = feature),
+		"lat" => parse_degrees(value, 90).map(|lat| {
+			settings
+				.lat_long
+				.get_or_insert_with(LatLong::new_zeroed)
+				.lat = U32::from(lat as u32)
+		}),
+		"lon" => parse_degrees(value, 180).map(|long| {
+			settings
+				.lat_long
+				.get_or_insert_with(LatLong::new_zeroed)
+				.long = U32::from(long as u32)
+		}),
+		_ => {
+			write!(reply, "Unknown setting")?;
+			return Ok(CliAction::None);
+		}
+	};
+	if parsed.is_none() || radio.validate().is_err() {
+		write!(reply, "Error: invalid value")?;
+		return Ok(CliAction::None);
+	}
+	// Retuning straight away would cut us off from the admin
+	if radio != settings.radio {
+		settings.radio = radio;
+		write!(reply, "OK - reboot to apply")?;
+	}
+	else {
+		write!(reply, "OK")?;
+	}
+	Ok(CliAction::SaveSettings)
+}
+
+/// Parses a decimal such as `910.525` scaled up by 10^`decimals`, without going through floats as
+/// they can't hold frequencies in Hz exactly
+fn parse_scaled(value: &str, decimals: usize) -> Option<u32> {
+	let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+	if fraction.len() > decimals || !fraction.bytes().all(|digit| digit.is_ascii_digit()) {
+		return None;
+	}
+	let mut scaled = whole.parse::<u32>().ok()?;
+	for index in 0..decimals {
+		let digit = fraction
+			.as_bytes()
+			.get(index)
+			.map_or(0, |digit| digit - b'0');
+		scaled = scaled.checked_mul(10)?.checked_add(digit as u32)?;
+	}
+	Some(scaled)
+}
+
+/// Parses a value for one of the advert's optional fields, `off` leaving it out
+fn parse_feature(value: &str) -> Option<Option<u16>> {
+	match value {
+		"off" => Some(None),
+		_ => value.parse().ok().map(Some),
+	}
+}
+
+/// Parses signed degrees up to `limit` into the millionths adverts carry
+fn parse_degrees(value: &str, limit: i32) -> Option<i32> {
+	let (sign, magnitude) = match value.strip_prefix('-') {
+		Some(magnitude) => (-1, magnitude),
+		None => (1, value),
+	};
+	let millionths = i32::try_from(parse_scaled(magnitude, 6)?).ok()?;
+	(millionths <= limit * 1_000_000).then_some(sign * millionths)
+}
+
+/// Lists neighbours one per line as `prefix:seconds since heard:SNR`, as many as fit
+fn write_neighbours(neighbours: &Neighbours, now: Instant, reply: &mut Text) -> fmt::Result {
+	if neighbours.is_empty() {
+		return write!(reply, "-none-");
+	}
+	for (index, neighbour) in neighbours.iter().enumerate() {
+		if index > 0 {
+			writeln!(reply)?;
+		}
+		for byte in &neighbour.pub_key[..NEIGHBOUR_PREFIX_SIZE] {
+			write!(reply, "{byte:02x}")?;
+		}
+		let heard_secs = (now - neighbour.heard).as_secs();
+		write!(reply, ":{}:{}", heard_secs, neighbour.snr / 4)?;
+	}
+	Ok(())
+}
+
+fn write_stats(stats: &NodeStats, now: Instant, reply: &mut Text) -> fmt::Result {
+	write!(
+		reply,
+		"uptime {}s, rx {} ({} flood, {} direct), tx {} ({} flood, {} direct), dups {}, \
+		 last rssi {}, last snr {}, airtime tx {}s rx {}s",
+		now.as_secs(),
+		stats.packets_received,
+		stats.received_flood,
+		stats.received_direct,
+		stats.packets_sent,
+		stats.sent_flood,
+		stats.sent_direct,
+		stats.flood_dups + stats.direct_dups,
+		stats.last_rssi,
+		stats.last_snr,
+		stats.tx_airtime.as_secs(),
+		stats.rx_airtime.as_secs(),
+	)
+}
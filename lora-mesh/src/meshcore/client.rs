@@ -0,0 +1,366 @@
+use crate::{
+	config::{Password, Settings},
+	error::{Error, Result},
+	meshcore::{
+		MAX_GROUP_DATA_SIZE, MAX_PACKET_PAYLOAD, MAX_TEXT_SIZE,
+		channels::{self, MAX_CHANNELS},
+		contacts::Permissions,
+		packet::{
+			group_data::DataType,
+			plain_message::{SIGNER_PREFIX_SIZE, TextType},
+			request::RequestType,
+		},
+	},
+};
+use core::{
+	fmt,
+	sync::atomic::{AtomicU32, Ordering},
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+
+pub const COMMAND_QUEUE_SIZE: usize = 4;
+pub const EVENT_QUEUE_SIZE: usize = 8;
+
+#[derive(Clone)]
+pub struct Text {
+	bytes: [u8; MAX_TEXT_SIZE],
+	len: u8,
+}
+
+impl Text {
+	pub fn new(text: &str) -> Result<Self> {
+		let len = text.len();
+		if len > MAX_TEXT_SIZE {
+			return Err(Error::MessageTooLong);
+		}
+		let mut bytes = [0; MAX_TEXT_SIZE];
+		bytes[..len].copy_from_slice(text.as_bytes());
+		Ok(Self {
+			bytes,
+			len: len as _,
+		})
+	}
+
+	/// Replaces invalid UTF-8 with U+FFFD, cutting off whatever doesn't fit
+	pub fn from_lossy(bytes: &[u8]) -> Self {
+		let mut text = Self::default();
+		for chunk in bytes.utf8_chunks() {
+			text.push(chunk.valid());
+			if !chunk.invalid().is_empty() {
+				text.push(char::REPLACEMENT_CHARACTER.encode_utf8(&mut [0; 4]));
+			}
+		}
+		text
+	}
+
+	/// Appends as much of `text` as fits, up to a character boundary
+	fn push(&mut self, text: &str) {
+		let start = self.len as usize;
+		let mut len = text.len().min(MAX_TEXT_SIZE - start);
+		while !text.is_char_boundary(len) {
+			len -= 1;
+		}
+		self.bytes[start..start + len].copy_from_slice(&text.as_bytes()[..len]);
+		self.len += len as u8;
+	}
+
+	pub fn as_bytes(&self) -> &[u8] { &self.bytes[..self.len as usize] }
+
+	pub fn as_str(&self) -> &str { str::from_utf8(self.as_bytes()).unwrap_or_default() }
+}
+
+impl Default for Text {
+	fn default() -> Self {
+		Self {
+			bytes: [0; MAX_TEXT_SIZE],
+			len: 0,
+		}
+	}
+}
+
+/// Writing past the end cuts the text off rather than failing
+impl fmt::Write for Text {
+	fn write_str(&mut self, text: &str) -> fmt::Result {
+		self.push(text);
+		Ok(())
+	}
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Text {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{=str}", self.as_str());
+	}
+}
+
+/// Binary data sent or received in a single packet
+#[derive(Clone)]
+pub struct Data {
+	bytes: [u8; MAX_PACKET_PAYLOAD],
+	len: u8,
+}
+
+impl Data {
+	pub fn new(data: &[u8]) -> Result<Self> {
+		let len = data.len();
+		if len > MAX_PACKET_PAYLOAD {
+			return Err(Error::MessageTooLong);
+		}
+		let mut bytes = [0; MAX_PACKET_PAYLOAD];
+		bytes[..len].copy_from_slice(data);
+		Ok(Self {
+			bytes,
+			len: len as _,
+		})
+	}
+
+	pub fn as_bytes(&self) -> &[u8] { &self.bytes[..self.len as usize] }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Data {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:02x}", self.as_bytes());
+	}
+}
+
+pub enum Command {
+	SendDirectText {
+		id: u32,
+		dest: [u8; 32],
+		text: Text,
+	},
+	/// Announces us to other nodes, either just to neighbours or flooded across the mesh
+	SendAdvert {
+		flood: bool,
+	},
+	SendGroupText {
+		channel: u8,
+		text: Text,
+	},
+	SendGroupData {
+		channel: u8,
+		data_type: DataType,
+		data: Data,
+	},
+	SendRequest {
+		id: u32,
+		dest: [u8; 32],
+		request_type: RequestType,
+		data: Data,
+	},
+	/// Runs a command on a repeater or room server we're logged in to as an admin
+	SendCliCommand {
+		dest: [u8; 32],
+		command: Text,
+	},
+	/// Logs in to a repeater or room server
+	Login {
+		id: u32,
+		dest: [u8; 32],
+		password: Password,
+	},
+	SetChannel {
+		index: u8,
+		channel: Option<channels::Channel>,
+	},
+	/// Sets our clock, in seconds since the Unix epoch
+	SetTime {
+		timestamp: u32,
+	},
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+	MessageDelivered {
+		id: u32,
+	},
+	MessageFailed {
+		id: u32,
+	},
+	/// Direct text from the contact with public key `sender`
+	TextMessage {
+		sender: [u8; 32],
+		timestamp: u32,
+		text_type: TextType,
+		signer: Option<[u8; SIGNER_PREFIX_SIZE]>,
+		text: Text,
+	},
+	/// Text received on the channel in slot `channel`, starting with the sender's name
+	GroupText {
+		channel: u8,
+		timestamp: u32,
+		text: Text,
+	},
+	/// Answer to a request, with any zero padding left on `data`
+	Response {
+		id: u32,
+		data: Data,
+	},
+	/// A login was accepted, granting `permissions`
+	LoggedIn {
+		id: u32,
+		permissions: Permissions,
+	},
+	/// No response to a request or login arrived in time
+	RequestFailed {
+		id: u32,
+	},
+	/// Binary data received on the channel in slot `channel`
+	GroupData {
+		channel: u8,
+		data_type: DataType,
+		data: Data,
+	},
+	/// An admin changed our settings remotely, to be saved so they survive a reboot
+	SettingsChanged {
+		settings: Settings,
+	},
+	/// An admin asked us to reboot, so changed radio settings take effect
+	RebootRequested,
+}
+
+/// Queues between clients (BLE, serial) and a MeshCore task
+pub struct Client {
+	/// Requests for the MeshCore task to act on
+	pub commands: Channel<CriticalSectionRawMutex, Command, COMMAND_QUEUE_SIZE>,
+	/// Notifications from the MeshCore task back to clients
+	pub events: Channel<CriticalSectionRawMutex, Event, EVENT_QUEUE_SIZE>,
+	next_message_id: AtomicU32,
+}
+
+impl Client {
+	pub const fn new() -> Self {
+		Self {
+			commands: Channel::new(),
+			events: Channel::new(),
+			next_message_id: AtomicU32::new(0),
+		}
+	}
+
+	/// Queues a text message to the contact with public key `contact`.
+	/// Returns the id that the matching delivered or failed event will carry.
+	pub async fn send_direct_text(&self, contact: &[u8; 32], text: &str) -> Result<u32> {
+		let text = Text::new(text)?;
+		let id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+		self.commands
+			.send(Command::SendDirectText {
+				id,
+				dest: *contact,
+				text,
+			})
+			.await;
+		Ok(id)
+	}
+
+	pub async fn send_advert(&self, flood: bool) {
+		self.commands.send(Command::SendAdvert { flood }).await;
+	}
+
+	/// Floods a text to everyone on the channel in slot `channel`. Group texts aren't ACKed.
+	pub async fn send_group_text(&self, channel: u8, text: &str) -> Result<()> {
+		if channel as usize >= MAX_CHANNELS {
+			return Err(Error::InvalidChannel);
+		}
+		let text = Text::new(text)?;
+		self.commands
+			.send(Command::SendGroupText { channel, text })
+			.await;
+		Ok(())
+	}
+
+	/// Floods binary data to everyone on the channel in slot `channel`, tagged with `data_type`
+	pub async fn send_group_data(
+		&self,
+		channel: u8,
+		data_type: DataType,
+		data: &[u8],
+	) -> Result<()> {
+		if channel as usize >= MAX_CHANNELS {
+			return Err(Error::InvalidChannel);
+		}
+		if data.len() > MAX_GROUP_DATA_SIZE {
+			return Err(Error::MessageTooLong);
+		}
+		let data = Data::new(data)?;
+		self.commands
+			.send(Command::SendGroupData {
+				channel,
+				data_type,
+				data,
+			})
+			.await;
+		Ok(())
+	}
+
+	/// Queues a request to the contact with public key `contact`, such as a repeater.
+	/// Returns the id that the matching response or failed event will carry.
+	pub async fn send_request(
+		&self,
+		contact: &[u8; 32],
+		request_type: RequestType,
+		data: &[u8],
+	) -> Result<u32> {
+		let data = Data::new(data)?;
+		let id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+		self.commands
+			.send(Command::SendRequest {
+				id,
+				dest: *contact,
+				request_type,
+				data,
+			})
+			.await;
+		Ok(id)
+	}
+
+	/// Queues a CLI command to the contact with public key `contact`. The output comes back as a
+	/// `TextMessage` of type `TextType::CliData`, or not at all if we aren't logged in as an admin.
+	pub async fn send_cli_command(&self, contact: &[u8; 32], command: &str) -> Result<()> {
+		let command = Text::new(command)?;
+		self.commands
+			.send(Command::SendCliCommand {
+				dest: *contact,
+				command,
+			})
+			.await;
+		Ok(())
+	}
+
+	/// Queues a login to the contact with public key `contact`. A wrong password gets no answer,
+	/// so shows up as a failed request.
+	pub async fn send_login(&self, contact: &[u8; 32], password: &[u8]) -> Result<u32> {
+		let password = Password::new(password).map_err(|_| Error::MessageTooLong)?;
+		let id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+		self.commands
+			.send(Command::Login {
+				id,
+				dest: *contact,
+				password,
+			})
+			.await;
+		Ok(id)
+	}
+
+	/// Sets our clock to `timestamp` in seconds since the Unix epoch, as our messages and adverts
+	/// carry it. The clock can't go backwards, so earlier times are ignored.
+	pub async fn set_time(&self, timestamp: u32) {
+		self.commands.send(Command::SetTime { timestamp }).await;
+	}
+
+	/// Puts `channel` in slot `index` of the channel table, or clears the slot
+	pub async fn set_channel(&self, index: u8, channel: Option<channels::Channel>) -> Result<()> {
+		if index as usize >= MAX_CHANNELS {
+			return Err(Error::InvalidChannel);
+		}
+		self.commands
+			.send(Command::SetChannel { index, channel })
+			.await;
+		Ok(())
+	}
+}
+
+impl Default for Client {
+	fn default() -> Self { Self::new() }
+}
@@ -0,0 +1,29 @@
+use crate::error::{Error, Result};
+use embassy_time::Instant;
+
+/// Wall clock in seconds since the Unix epoch, as MeshCore timestamps are.
+/// Counts up from zero at boot until a client, such as a phone, tells us the time.
+pub struct Clock {
+	/// The time at `Instant` zero
+	boot_time: u32,
+}
+
+impl Clock {
+	pub const fn new() -> Self { Self { boot_time: 0 } }
+
+	pub fn now(&self) -> u32 { self.boot_time + Instant::now().as_secs() as u32 }
+
+	/// Sets the current time, which can't go backwards as other nodes drop timestamps older than
+	/// ones they've already seen from us
+	pub fn set(&mut self, timestamp: u32) -> Result<()> {
+		if timestamp < self.now() {
+			return Err(Error::TimeBackwards);
+		}
+		self.boot_time = timestamp - Instant::now().as_secs() as u32;
+		Ok(())
+	}
+}
+
+impl Default for Clock {
+	fn default() -> Self { Self::new() }
+}
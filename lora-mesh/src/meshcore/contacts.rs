@@ -0,0 +1,255 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::{
+		MAX_PATH_SIZE,
+		crypto::{SharedSecret, SigningKeys},
+		packet::advert::{AdvType, Advert, LatLong},
+	},
+};
+use core::cell::{Cell, OnceCell};
+use ed25519_dalek::VerifyingKey;
+
+pub const MAX_CONTACTS: usize = 32;
+pub const MAX_NAME_SIZE: usize = 32;
+
+/// What a contact that logged in to us may do, as the role bits of the reference firmware's ACL
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Permissions {
+	Guest = 0,
+	ReadOnly = 1,
+	ReadWrite = 2,
+	Admin = 3,
+}
+
+impl From<u8> for Permissions {
+	fn from(value: u8) -> Self {
+		match value & 0x03 {
+			0 => Self::Guest,
+			1 => Self::ReadOnly,
+			2 => Self::ReadWrite,
+			_ => Self::Admin,
+		}
+	}
+}
+
+pub struct Contact {
+	verifying_key: VerifyingKey,
+	shared_secret: OnceCell<SharedSecret>,
+	name: [u8; MAX_NAME_SIZE],
+	name_len: u8,
+	pub adv_type: AdvType,
+	pub last_advert: u32,
+	pub lat_long: Option<LatLong>,
+	pub last_rssi: i16,
+	pub last_snr: i16,
+	out_path: [u8; MAX_PATH_SIZE],
+	out_path_len: Option<u8>,
+	/// Set once the contact has logged in with a password
+	pub permissions: Option<Permissions>,
+	/// Timestamp of the last login, older ones are replays
+	pub last_login: u32,
+	/// Timestamp of the last CLI command from the contact, older ones are replays
+	pub last_command: u32,
+	/// Timestamp of the newest room post the contact has seen
+	pub sync_since: u32,
+	/// Room posts pushed to the contact in a row without an ACK. A cell, as a member checking in
+	/// resets it while the contact list is borrowed to answer it.
+	pub push_failures: Cell<u8>,
+}
+
+impl Contact {
+	fn new(verifying_key: VerifyingKey) -> Self {
+		Self {
+			verifying_key,
+			shared_secret: OnceCell::new(),
+			name: [0; MAX_NAME_SIZE],
+			name_len: 0,
+			adv_type: AdvType::None,
+			last_advert: 0,
+			lat_long: None,
+			last_rssi: 0,
+			last_snr: 0,
+			out_path: [0; MAX_PATH_SIZE],
+			out_path_len: None,
+			permissions: None,
+			last_login: 0,
+			last_command: 0,
+			sync_since: 0,
+			push_failures: Cell::new(0),
+		}
+	}
+
+	pub fn pub_key(&self) -> &[u8; 32] { self.verifying_key.as_bytes() }
+
+	pub fn verifying_key(&self) -> &VerifyingKey { &self.verifying_key }
+
+	pub fn hash(&self) -> u8 { self.pub_key()[0] }
+
+	/// The ECDH secret shared with this contact, derived on first use and cached afterwards
+	pub fn shared_secret(&self, identity: &SigningKeys) -> &SharedSecret {
+		self.shared_secret
+			.get_or_init(|| identity.calc_shared_secret(&self.verifying_key))
+	}
+
+	pub fn name(&self) -> &[u8] { &self.name[..self.name_len as usize] }
+
+	/// The route to this contact, if one has been learned
+	pub fn out_path(&self) -> Option<&[u8]> {
+		self.out_path_len.map(|len| &self.out_path[..len as usize])
+	}
+
+	pub fn set_out_path(&mut self, path: &[u8]) -> Result<()> {
+		let path_len = path.len();
+		if path_len > MAX_PATH_SIZE {
+			return Err(Error::PacketBuild);
+		}
+		self.out_path[..path_len].copy_from_slice(path);
+		self.out_path_len = Some(path_len as _);
+		Ok(())
+	}
+
+	pub fn reset_out_path(&mut self) { self.out_path_len = None; }
+
+	fn update_from_advert(&mut self, advert: &Advert, rssi: i16, snr: i16) {
+		let name = advert.name.unwrap_or(&[]);
+		let name_len = name.len().min(MAX_NAME_SIZE);
+		self.name[..name_len].copy_from_slice(&name[..name_len]);
+		self.name_len = name_len as _;
+		self.adv_type = advert.header.flags.adv_type();
+		self.last_advert = advert.header.timestamp.0.get();
+		self.lat_long = advert.lat_long.clone();
+		self.last_rssi = rssi;
+		self.last_snr = snr;
+	}
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Contact {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(
+			fmt,
+			"Contact {{ pub_key: {}.., name: {}, adv_type: {}, last_advert: {}, rssi: {}, snr: {}, out_path: {}, permissions: {} }}",
+			self.pub_key()[..4],
+			str::from_utf8(self.name()).unwrap_or("<invalid>"),
+			self.adv_type,
+			self.last_advert,
+			self.last_rssi,
+			self.last_snr,
+			self.out_path(),
+			self.permissions
+		);
+	}
+}
+
+/// Fixed capacity table of the nodes we have heard adverts from
+pub struct Contacts {
+	contacts: [Option<Contact>; MAX_CONTACTS],
+}
+
+impl Contacts {
+	pub const fn new() -> Self {
+		Self {
+			contacts: [const { None }; MAX_CONTACTS],
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &Contact> { self.contacts.iter().flatten() }
+
+	pub fn get(&self, pub_key: &[u8; 32]) -> Option<&Contact> {
+		self.iter().find(|contact| contact.pub_key() == pub_key)
+	}
+
+	pub fn get_mut(&mut self, pub_key: &[u8; 32]) -> Option<&mut Contact> {
+		self.contacts
+			.iter_mut()
+			.flatten()
+			.find(|contact| contact.pub_key() == pub_key)
+	}
+
+	/// All contacts whose public key starts with `hash`, as more than one may collide
+	pub fn matching_hash(&self, hash: u8) -> impl Iterator<Item = &Contact> {
+		self.iter().filter(move |contact| contact.hash() == hash)
+	}
+
+	pub fn matching_hash_mut(&mut self, hash: u8) -> impl Iterator<Item = &mut Contact> {
+		self.contacts
+			.iter_mut()
+			.flatten()
+			.filter(move |contact| contact.hash() == hash)
+	}
+
+	pub fn remove(&mut self, pub_key: &[u8; 32]) -> Option<Contact> {
+		self.position(pub_key)
+			.and_then(|index| self.contacts[index].take())
+	}
+
+	/// Adds or refreshes a contact from an advert whose signature has already been verified.
+	/// Adverts older than the one we already hold are ignored.
+	pub fn update_from_advert(
+		&mut self,
+		advert: &Advert,
+		rssi: i16,
+		snr: i16,
+	) -> Result<&mut Contact> {
+		let pub_key = &advert.header.pub_key;
+		let timestamp = advert.header.timestamp.0.get();
+
+		if self
+			.get(pub_key)
+			.is_some_and(|contact| timestamp <= contact.last_advert)
+		{
+			return Err(Error::StaleAdvert);
+		}
+
+		let contact = self.get_or_insert(pub_key)?;
+		contact.update_from_advert(advert, rssi, snr);
+		Ok(contact)
+	}
+
+	/// Returns the contact with `pub_key`, adding it if we haven't heard its advert
+	pub fn get_or_insert(&mut self, pub_key: &[u8; 32]) -> Result<&mut Contact> {
+		let index = match self.position(pub_key) {
+			Some(index) => index,
+			None => {
+				let verifying_key =
+					VerifyingKey::from_bytes(pub_key).map_err(|_| Error::CryptoError)?;
+				let index = self.free_slot();
+				self.contacts[index] = Some(Contact::new(verifying_key));
+				index
+			}
+		};
+		Ok(self.contacts[index].as_mut().unwrap())
+	}
+
+	fn position(&self, pub_key: &[u8; 32]) -> Option<usize> {
+		self.contacts.iter().position(|slot| {
+			slot.as_ref()
+				.is_some_and(|contact| contact.pub_key() == pub_key)
+		})
+	}
+
+	/// Returns an empty slot, evicting the contact with the oldest advert if the table is full.
+	/// Contacts that logged in are only evicted once nothing else is left.
+	/// Evicted contacts are dropped, which wipes their cached shared secret.
+	fn free_slot(&mut self) -> usize {
+		if let Some(index) = self.contacts.iter().position(Option::is_none) {
+			return index;
+		}
+		let (index, _) = self
+			.contacts
+			.iter()
+			.enumerate()
+			.min_by_key(|(_, slot)| {
+				slot.as_ref()
+					.map(|contact| (contact.permissions.is_some(), contact.last_advert))
+			})
+			.unwrap();
+		index
+	}
+}
+
+impl Default for Contacts {
+	fn default() -> Self { Self::new() }
+}
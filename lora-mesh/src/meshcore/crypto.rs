@@ -0,0 +1,200 @@
+use crate::{
+	error::Result,
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		packet::plain_message::{PlainMessageHeader, SIGNER_PREFIX_SIZE},
+	},
+};
+use aes::{
+	Aes128Dec, Aes128Enc,
+	cipher::{BlockDecryptMut, BlockEncryptMut, KeyInit},
+};
+use ed25519_dalek::{SigningKey, VerifyingKey, ed25519::signature::Signer};
+use hmac::Mac;
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const PUBLIC_GROUP_PSK: [u8; 16] = [
+	0x8b, 0x33, 0x87, 0xe9, 0xc5, 0xcd, 0xea, 0x6a, 0xc9, 0xe5, 0xed, 0xba, 0xa1, 0x15, 0xcd, 0x72,
+];
+
+type HmacSha256 = hmac::Hmac<Sha256>;
+
+pub struct SigningKeys {
+	keys: SigningKey,
+}
+
+impl SigningKeys {
+	pub fn from_bytes(private_key: &[u8; 32]) -> Self {
+		let keys = SigningKey::from_bytes(private_key);
+		Self { keys }
+	}
+
+	pub fn private_key(&self) -> [u8; 32] { self.keys.to_bytes() }
+
+	pub fn public_key(&self) -> [u8; 32] { self.keys.verifying_key().to_bytes() }
+
+	pub fn sign_message(&self, msg: &[u8]) -> [u8; 64] { self.keys.sign(msg).to_bytes() }
+
+	pub fn calc_shared_secret(&self, other: &VerifyingKey) -> SharedSecret {
+		SharedSecret((self.keys.to_scalar() * other.to_montgomery()).to_bytes())
+	}
+}
+
+/// ECDH secret shared with a peer, wiped from memory when dropped
+pub struct SharedSecret([u8; 32]);
+
+impl SharedSecret {
+	pub fn as_bytes(&self) -> &[u8; 32] { &self.0 }
+
+	/// AES-128 key used for direct messages, the first half of the secret. The copy is wiped
+	/// when dropped, like the secret itself.
+	pub fn aes_key(&self) -> Zeroizing<[u8; 16]> {
+		Zeroizing::new(<[u8; 16]>::try_from(&self.0[..16]).unwrap())
+	}
+}
+
+impl Drop for SharedSecret {
+	fn drop(&mut self) { self.0.zeroize(); }
+}
+
+/// Hash a recipient ACKs a text with. A signed text is hashed with its signer prefix, and with
+/// the recipient's public key where other texts use the sender's.
+pub fn msg_ack_hash(
+	header: &PlainMessageHeader,
+	signer: Option<&[u8; SIGNER_PREFIX_SIZE]>,
+	message: &[u8],
+	pubkey: &[u8; 32],
+) -> [u8; 4] {
+	let trunc_message = message.split(|x| *x == 0).next().unwrap();
+
+	let sha = Sha256::new()
+		.chain_update(header.timestamp.0.to_bytes())
+		.chain_update([header.flags.as_raw()])
+		.chain_update(signer.map_or(&[][..], |signer| &signer[..]))
+		.chain_update(trunc_message)
+		.chain_update(pubkey)
+		.finalize();
+	let out = <[u8; 32]>::from(sha);
+
+	out[..4].try_into().unwrap()
+}
+
+pub fn msg_mac_16(payload: &[u8], shared: &[u8; 16]) -> Result<[u8; 32]> {
+	let mut mac = <HmacSha256 as Mac>::new_from_slice(shared).unwrap();
+	mac.update(payload);
+	let finished = mac.finalize().into_bytes();
+	let mac = <[u8; 32]>::from(finished);
+	Ok(mac)
+}
+
+pub fn msg_mac_32(payload: &[u8], shared: &[u8; 32]) -> Result<[u8; 32]> {
+	let mut mac = <HmacSha256 as Mac>::new_from_slice(shared).unwrap();
+	mac.update(payload);
+	let finished = mac.finalize().into_bytes();
+	let mac = <[u8; 32]>::from(finished);
+	Ok(mac)
+}
+
+pub fn calculate_channel_hash(secret: &[u8; 16]) -> u8 {
+	let sha = Sha256::new().chain_update(secret).finalize();
+	let out = <[u8; 32]>::from(sha);
+	out[0]
+}
+
+pub fn decrypt_message<'a>(
+	key: &[u8; 16],
+	message: &'a mut [u8; PACKET_BUFFER_SIZE],
+	len: usize,
+) -> &'a [u8] {
+	let mut aes = Aes128Dec::new(key.into());
+	for block in message.as_chunks_mut::<16>().0 {
+		aes.decrypt_block_mut(block.into());
+	}
+	&message[..len]
+}
+
+/// Zero-pads the first `len` bytes of `message` to a whole number of blocks and encrypts them in place
+pub fn encrypt_message<'a>(
+	key: &[u8; 16],
+	message: &'a mut [u8; PACKET_BUFFER_SIZE],
+	len: usize,
+) -> &'a [u8] {
+	let padded_len = len.next_multiple_of(16);
+	message[len..padded_len].fill(0);
+	let mut aes = Aes128Enc::new(key.into());
+	for block in message[..padded_len].as_chunks_mut::<16>().0 {
+		aes.encrypt_block_mut(block.into());
+	}
+	&message[..padded_len]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Encrypts `plaintext` as a sender would, checks its MAC, then decrypts it as the recipient
+	fn round_trip(plaintext: &[u8]) {
+		let alice = SigningKeys::from_bytes(&[1; 32]);
+		let bob = SigningKeys::from_bytes(&[2; 32]);
+		let secret =
+			alice.calc_shared_secret(&VerifyingKey::from_bytes(&bob.public_key()).unwrap());
+
+		let mut buffer = [0xee; PACKET_BUFFER_SIZE];
+		buffer[..plaintext.len()].copy_from_slice(plaintext);
+		let ciphertext = encrypt_message(&secret.aes_key(), &mut buffer, plaintext.len());
+		assert_eq!(ciphertext.len(), plaintext.len().next_multiple_of(16));
+		let mac = msg_mac_32(ciphertext, secret.as_bytes()).unwrap();
+
+		let secret =
+			bob.calc_shared_secret(&VerifyingKey::from_bytes(&alice.public_key()).unwrap());
+		assert_eq!(msg_mac_32(ciphertext, secret.as_bytes()).unwrap(), mac);
+		let mut received = [0; PACKET_BUFFER_SIZE];
+		received[..ciphertext.len()].copy_from_slice(ciphertext);
+		let decrypted = decrypt_message(&secret.aes_key(), &mut received, ciphertext.len());
+		assert_eq!(&decrypted[..plaintext.len()], plaintext);
+		assert!(decrypted[plaintext.len()..].iter().all(|&byte| byte == 0));
+	}
+
+	#[test]
+	fn messages_round_trip_across_block_boundaries() {
+		let plaintext = [0x41; 64];
+		for len in [1, 15, 16, 17, 31, 32, 33, 64] {
+			round_trip(&plaintext[..len]);
+		}
+	}
+
+	#[test]
+	fn whole_blocks_get_no_padding_block() {
+		let key = [7; 16];
+		let mut buffer = [0; PACKET_BUFFER_SIZE];
+		assert_eq!(encrypt_message(&key, &mut buffer, 16).len(), 16);
+		assert_eq!(encrypt_message(&key, &mut buffer, 0).len(), 0);
+	}
+
+	#[test]
+	fn padding_overwrites_stale_bytes() {
+		let key = [7; 16];
+		let mut buffer = [0xff; PACKET_BUFFER_SIZE];
+		buffer[..3].copy_from_slice(b"abc");
+		let mut ciphertext = [0; PACKET_BUFFER_SIZE];
+		let len = encrypt_message(&key, &mut buffer, 3).len();
+		ciphertext[..len].copy_from_slice(&buffer[..len]);
+		assert_eq!(
+			decrypt_message(&key, &mut ciphertext, len),
+			b"abc\0\0\0\0\0\0\0\0\0\0\0\0\0"
+		);
+	}
+
+	#[test]
+	fn mac_covers_every_ciphertext_byte() {
+		let secret = [9; 32];
+		let ciphertext = [0x33; 32];
+		let mac = msg_mac_32(&ciphertext, &secret).unwrap();
+		for index in 0..ciphertext.len() {
+			let mut tampered = ciphertext;
+			tampered[index] ^= 1;
+			assert_ne!(msg_mac_32(&tampered, &secret).unwrap()[..2], mac[..2]);
+		}
+	}
+}
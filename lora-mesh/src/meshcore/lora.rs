@@ -0,0 +1,476 @@
+mod build;
+mod cli;
+mod commands;
+mod groups;
+mod login;
+mod requests;
+mod room;
+mod texts;
+mod timers;
+
+use self::build::build_advert;
+use crate::{
+	airtime::AirtimeParams,
+	config::{RadioSettings, Settings},
+	error::{Error, Result},
+	fmt::Display2Format,
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		acks::{PendingAcks, RetryConfig},
+		adverts::AdvertSchedule,
+		channels::Channels,
+		client::{Client, Event},
+		clock::Clock,
+		contacts::{Contact, Contacts},
+		crypto::{SigningKeys, decrypt_message, msg_mac_32},
+		neighbours::Neighbours,
+		outbound::OutboundQueue,
+		packet::{
+			Packet, PayloadType, RouteType,
+			ack::Ack,
+			advert::Advert,
+			direct_packets::{AnonReqPayload, DirectHeader, DirectPayload},
+			path::ReturnedPath,
+		},
+		repeater::{
+			RecentPackets, RepeaterConfig, forward_direct_packet, forward_packet, retransmit_delay,
+			should_forward, should_forward_direct,
+		},
+		requests::PendingRequests,
+		room::Room,
+		stats::StatsRadio,
+	},
+	radio::MeshRadio,
+};
+use core::borrow::Borrow;
+use ed25519_dalek::VerifyingKey;
+use embassy_futures::select::{Either3, select3};
+use embassy_time::{Instant, Timer};
+use lora_phy::mod_params::PacketStatus;
+use rand_core::RngCore;
+use zerocopy::FromBytes;
+
+/// Everything the MeshCore task keeps between packets, client commands and timers
+struct Node<'a, M, R> {
+	radio: StatsRadio<M>,
+	rng: R,
+	client: &'a Client,
+	identity: SigningKeys,
+	our_hash: u8,
+	settings: Settings,
+	repeater: RepeaterConfig,
+	airtime: AirtimeParams,
+	contacts: Contacts,
+	channels: Channels,
+	pending_acks: PendingAcks,
+	pending_requests: PendingRequests,
+	neighbours: Neighbours,
+	room: Room,
+	recent_packets: RecentPackets,
+	outbound: OutboundQueue,
+	clock: Clock,
+	adverts: AdvertSchedule,
+	/// Servers ignore commands no newer than the last, so ones sent within a second need bumping
+	last_command_timestamp: u32,
+	crypto_buffer: [u8; PACKET_BUFFER_SIZE],
+	resp_buffer: [u8; PACKET_BUFFER_SIZE],
+	forward_buffer: [u8; PACKET_BUFFER_SIZE],
+	reply_buffer: [u8; PACKET_BUFFER_SIZE],
+}
+
+async fn rx_packet<'a, M: MeshRadio>(
+	radio: &mut M,
+	buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<(&'a [u8], PacketStatus)> {
+	let (received_len, packet_status) = radio.rx(buffer).await?;
+	Ok((&buffer[..received_len], packet_status))
+}
+
+/// Sends a frame, remembering it so we don't repeat our own floods when neighbours relay them
+async fn transmit<M: MeshRadio>(
+	radio: &mut M,
+	recent_packets: &mut RecentPackets,
+	frame: &[u8],
+) -> Result<()> {
+	if let Ok(packet) = Packet::from_bytes(frame) {
+		recent_packets.check_and_insert(&packet);
+	}
+	radio.tx(frame).await
+}
+
+/// Decrypts a direct message with whichever of `candidates`, the contacts matching its source
+/// hash, it carries a valid MAC for
+fn decrypt_direct_message<'a, C: Borrow<Contact>>(
+	identity: &SigningKeys,
+	candidates: impl IntoIterator<Item = C>,
+	header: &DirectHeader,
+	payload: &[u8],
+	decryption_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<(&'a [u8], C)> {
+	// Several contacts may share the same hash, so try each until a MAC matches
+	for candidate in candidates {
+		let shared_secret = candidate.borrow().shared_secret(identity);
+		let mac = msg_mac_32(payload, shared_secret.as_bytes())?;
+		if mac[..2] != header.mac {
+			continue;
+		}
+
+		let payload_len = payload.len();
+		decryption_buffer[..payload_len].copy_from_slice(payload);
+
+		let decrypted = decrypt_message(&shared_secret.aes_key(), decryption_buffer, payload_len);
+
+		return Ok((decrypted, candidate));
+	}
+
+	warn!("No contact with matching MAC");
+	Err(Error::InvalidMAC)
+}
+
+/// Decrypts an anonymous request to us, keyed with the public key it carries as we may not
+/// have heard the sender's advert
+fn decrypt_anon_request<'a>(
+	identity: &SigningKeys,
+	request: &AnonReqPayload,
+	decryption_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let sender =
+		VerifyingKey::from_bytes(&request.header.pub_key).map_err(|_| Error::CryptoError)?;
+	let shared_secret = identity.calc_shared_secret(&sender);
+
+	let mac = msg_mac_32(request.ciphertext, shared_secret.as_bytes())?;
+	if mac[..2] != request.header.mac {
+		return Err(Error::InvalidMAC);
+	}
+
+	let payload_len = request.ciphertext.len();
+	decryption_buffer[..payload_len].copy_from_slice(request.ciphertext);
+
+	Ok(decrypt_message(
+		&shared_secret.aes_key(),
+		decryption_buffer,
+		payload_len,
+	))
+}
+
+fn handle_ack(
+	client: &Client,
+	pending_acks: &mut PendingAcks,
+	room: &mut Room,
+	contacts: &mut Contacts,
+	ack: &Ack,
+) {
+	if let Some(message) = pending_acks.acknowledge(&ack.hash) {
+		info!("Message {} delivered", message.id);
+		emit_event(client, Event::MessageDelivered { id: message.id });
+	}
+	// The member has now seen every post up to this one
+	if let Some(push) = room.acknowledge(&ack.hash, Instant::now())
+		&& let Some(member) = contacts.get_mut(&push.member)
+	{
+		member.sync_since = push.timestamp;
+		member.push_failures.set(0);
+	}
+}
+
+fn emit_event(client: &Client, event: Event) {
+	if client.events.try_send(event).is_err() {
+		warn!("Event queue full");
+	}
+}
+
+/// Waits for `deadline`, or forever without one.
+/// A timer at `Instant::MAX` would drop this task from the executor's timer queue while
+/// leaving it marked as queued, so its later timers would never fire.
+async fn wait_until(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => Timer::at(deadline).await,
+		None => core::future::pending().await,
+	}
+}
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Forwards a received packet if we repeat it, then handles its payload if it's for us
+	async fn handle_packet(&mut self, packet: &[u8], packet_status: PacketStatus) {
+		// info!("Got data: {:02x}", packet);
+
+		let Ok(packet) = Packet::from_bytes(packet)
+		else {
+			warn!("Parsing packet failed");
+			return;
+		};
+
+		info!("Packet Header: {:02x}", packet.header);
+
+		let route_type = packet.header.flags.route_type();
+		if self.recent_packets.check_and_insert(&packet) {
+			info!("Dropping duplicate packet");
+			match route_type {
+				RouteType::Flood => self.radio.stats.flood_dups += 1,
+				_ => self.radio.stats.direct_dups += 1,
+			}
+			return;
+		}
+
+		if route_type == RouteType::Direct && !packet.path.is_empty() {
+			// Still in transit, only handled by the next hop along the path
+			if should_forward_direct(&self.repeater, &packet, self.our_hash) {
+				match forward_direct_packet(&packet, &mut self.forward_buffer) {
+					Ok(forwarded) => {
+						if self.outbound.push(Instant::now(), forwarded).is_err() {
+							warn!("Outbound queue full, not forwarding");
+						}
+					}
+					Err(_) => warn!("Failed to build forwarded packet"),
+				}
+			}
+			return;
+		}
+
+		if should_forward(&self.repeater, &packet, self.our_hash) {
+			match forward_packet(&packet, self.our_hash, &mut self.forward_buffer) {
+				Ok(forwarded) => {
+					let delay = retransmit_delay(
+						packet_status.snr,
+						self.airtime.time_on_air(forwarded.len()),
+						&mut self.rng,
+					);
+					if self
+						.outbound
+						.push(Instant::now() + delay, forwarded)
+						.is_err()
+					{
+						warn!("Outbound queue full, not forwarding");
+					}
+				}
+				Err(_) => warn!("Failed to build forwarded packet"),
+			}
+		}
+
+		let Ok(payload_type) = packet.header.flags.payload_type()
+		else {
+			info!("Invalid payload type");
+			return;
+		};
+		info!("==> Payload type <{}>", payload_type);
+		match payload_type {
+			PayloadType::Req => self.handle_request(&packet).await,
+			PayloadType::Resp => self.handle_response(&packet),
+			PayloadType::Txt => self.handle_text(&packet).await,
+			PayloadType::Ack => {
+				let Ok((ack, _)) = Ack::ref_from_prefix(packet.payload)
+				else {
+					warn!("Invalid ACK");
+					return;
+				};
+				handle_ack(
+					self.client,
+					&mut self.pending_acks,
+					&mut self.room,
+					&mut self.contacts,
+					ack,
+				);
+			}
+			PayloadType::Advert => self.handle_advert(&packet, packet_status),
+			PayloadType::GrpText => self.handle_group_text(&packet),
+			PayloadType::GrpData => self.handle_group_data(&packet),
+			PayloadType::AnonReq => self.handle_login(&packet).await,
+			PayloadType::Path => self.handle_path(&packet),
+			// PayloadType::RawCustom => {}
+			_ => {
+				info!("Unable to process payload type");
+			}
+		}
+	}
+
+	/// Parses the payload of a direct packet, if it's addressed to us
+	fn direct_to_us<'p>(&self, payload: &'p [u8]) -> Option<DirectPayload<'p>> {
+		let Ok(direct) = DirectPayload::from_bytes(payload)
+		else {
+			warn!("Invalid direct packet");
+			return None;
+		};
+		(direct.header.dest_hash == self.our_hash).then_some(direct)
+	}
+
+	fn handle_advert(&mut self, packet: &Packet, packet_status: PacketStatus) {
+		let Ok((advert, _)) = Advert::from_bytes(packet.payload)
+		else {
+			warn!("Invalid advert");
+			return;
+		};
+		info!("{:02x}", &advert);
+
+		info!("pub key: {:#02x}", &advert.header.pub_key);
+
+		if advert.verify_signature().is_err() {
+			warn!("Signature doesn't match");
+			return;
+		}
+
+		// Heard straight from the advertiser, rather than relayed
+		if packet.path.is_empty() {
+			self.neighbours
+				.heard(&advert.header.pub_key, packet_status.snr, Instant::now());
+		}
+
+		match self
+			.contacts
+			.update_from_advert(&advert, packet_status.rssi, packet_status.snr)
+		{
+			Ok(contact) => info!("Updated {}", contact),
+			Err(e) => warn!("Contact not updated: {}", Display2Format(&e)),
+		}
+	}
+
+	/// Sends a signed advert, flooded to the whole mesh or only to our neighbours, and schedules
+	/// the next one
+	async fn send_advert(&mut self, flood: bool) {
+		match build_advert(
+			&self.identity,
+			&self.settings,
+			self.clock.now(),
+			flood,
+			&mut self.resp_buffer,
+		) {
+			Ok(advert_packet) => {
+				if transmit(&mut self.radio, &mut self.recent_packets, advert_packet)
+					.await
+					.is_err()
+				{
+					warn!("Failed to send advert");
+				}
+			}
+			Err(_) => warn!("Failed to build advert"),
+		}
+		self.adverts
+			.sent(&self.settings, flood, Instant::now(), &mut self.rng);
+	}
+
+	/// Learns the route back to a contact that answered our flood, along with the ACK or response
+	/// it piggybacked
+	fn handle_path(&mut self, packet: &Packet) {
+		let Some(direct) = self.direct_to_us(packet.payload)
+		else {
+			return;
+		};
+		info!("Returned path to this device");
+		info!("{:02x}", &direct.header);
+		let Ok((decrypted, sender)) = decrypt_direct_message(
+			&self.identity,
+			self.contacts.matching_hash_mut(direct.header.src_hash),
+			&direct.header,
+			direct.ciphertext,
+			&mut self.crypto_buffer,
+		)
+		else {
+			warn!("Failed to decrypt message");
+			return;
+		};
+
+		let Ok(returned_path) = ReturnedPath::from_bytes(decrypted)
+		else {
+			warn!("Invalid returned path");
+			return;
+		};
+		info!("{:02x}", &returned_path);
+
+		if sender.set_out_path(returned_path.path).is_err() {
+			warn!("Returned path too long");
+		}
+
+		match returned_path.extra_payload_type() {
+			Ok(PayloadType::Ack) => {
+				if let Ok((ack, _)) = Ack::ref_from_prefix(returned_path.extra) {
+					handle_ack(
+						self.client,
+						&mut self.pending_acks,
+						&mut self.room,
+						&mut self.contacts,
+						ack,
+					);
+				}
+			}
+			Ok(PayloadType::Resp) => requests::hand_over_response(
+				self.client,
+				&mut self.pending_requests,
+				sender.pub_key(),
+				returned_path.extra,
+			),
+			_ => (),
+		}
+	}
+}
+
+pub async fn lora_loop<M: MeshRadio, R: RngCore>(
+	radio: M,
+	mut rng: R,
+	client: &Client,
+	identity: SigningKeys,
+	mut settings: Settings,
+	repeater: RepeaterConfig,
+) -> ! {
+	let mut radio = StatsRadio::new(radio);
+	let mut modulation = settings.radio.modulation();
+	if let Err(e) = radio.set_modulation(&modulation).await {
+		warn!(
+			"Failed to set modulation {}, using defaults: {}",
+			modulation,
+			Display2Format(&e)
+		);
+		settings.radio = RadioSettings::default();
+		modulation = settings.radio.modulation();
+		if let Err(e) = radio.set_modulation(&modulation).await {
+			error!("Failed to set default modulation: {}", Display2Format(&e));
+		}
+	}
+
+	info!("=> My public key: {:02x}", identity.public_key());
+
+	let adverts = AdvertSchedule::new(&settings, Instant::now(), &mut rng);
+	let mut node = Node {
+		radio,
+		rng,
+		client,
+		our_hash: identity.public_key()[0],
+		identity,
+		settings,
+		repeater,
+		airtime: modulation.airtime(),
+		contacts: Contacts::new(),
+		channels: Channels::new(),
+		pending_acks: PendingAcks::new(RetryConfig::default()),
+		pending_requests: PendingRequests::new(),
+		neighbours: Neighbours::new(),
+		room: Room::new(),
+		recent_packets: RecentPackets::new(),
+		outbound: OutboundQueue::new(),
+		clock: Clock::new(),
+		adverts,
+		last_command_timestamp: 0,
+		crypto_buffer: [0; PACKET_BUFFER_SIZE],
+		resp_buffer: [0; PACKET_BUFFER_SIZE],
+		forward_buffer: [0; PACKET_BUFFER_SIZE],
+		reply_buffer: [0; PACKET_BUFFER_SIZE],
+	};
+	let mut packet_buffer: [u8; PACKET_BUFFER_SIZE] = [0; PACKET_BUFFER_SIZE];
+
+	loop {
+		let next_deadline = node.next_deadline();
+		let event = select3(
+			rx_packet(&mut node.radio, &mut packet_buffer),
+			node.client.commands.receive(),
+			wait_until(next_deadline),
+		)
+		.await;
+
+		match event {
+			Either3::First(Ok((packet, packet_status))) => {
+				node.handle_packet(packet, packet_status).await
+			}
+			Either3::First(Err(_)) => info!("Invalid message"),
+			Either3::Second(command) => node.handle_command(command).await,
+			Either3::Third(()) => node.handle_deadlines().await,
+		}
+	}
+}
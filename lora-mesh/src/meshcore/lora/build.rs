@@ -0,0 +1,257 @@
+use crate::{
+	config::Settings,
+	error::{Error, Result},
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		channels::Channel,
+		contacts::Contact,
+		crypto::{SigningKeys, encrypt_message, msg_ack_hash, msg_mac_32},
+		packet::{
+			PacketBuilder, Payload, PayloadType, RouteType, U16, U32,
+			advert::{AdvType, Advert, Battery, Temperature},
+			direct_packets::{AnonReqHeader, AnonReqPayload, DirectHeader, DirectPayload},
+			group_data::{DataType, GroupData, GroupDataHeader},
+			login::LoginRequest,
+			path::ReturnedPath,
+			plain_message::{MessageFlags, PlainMessage, PlainMessageHeader, TextType},
+			request::{Request, RequestType},
+			write_bytes,
+		},
+	},
+};
+use zerocopy::FromBytes;
+
+fn encrypt_direct_message<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	encryption_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+	len: usize,
+) -> DirectPayload<'a> {
+	let shared_secret = contact.shared_secret(identity);
+
+	let ciphertext = encrypt_message(&shared_secret.aes_key(), encryption_buffer, len);
+	let mac = msg_mac_32(ciphertext, shared_secret.as_bytes()).unwrap();
+
+	DirectPayload {
+		header: DirectHeader {
+			dest_hash: contact.hash(),
+			src_hash: identity.public_key()[0],
+			mac: [mac[0], mac[1]],
+		},
+		ciphertext,
+	}
+}
+
+/// Builds an encrypted text packet, flooded unless we know a path to the contact.
+/// Also returns the hash the recipient will ACK with.
+pub(super) fn build_direct_text<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	message: &PlainMessage,
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<(&'a [u8], [u8; 4])> {
+	// Signed texts are hashed with the recipient's key, others with the sender's
+	let ack_key = match message.text_type {
+		TextType::SignedPlain => *contact.pub_key(),
+		_ => identity.public_key(),
+	};
+	let ack_hash = msg_ack_hash(&message.header, message.signer, message.text, &ack_key);
+	let plaintext_len = message.write_bytes(crypto_buffer)?;
+
+	let payload = encrypt_direct_message(identity, contact, crypto_buffer, plaintext_len);
+
+	let packet = route_to(contact).build(&Payload::Txt(payload), packet_buffer)?;
+
+	Ok((packet, ack_hash))
+}
+
+/// Routes directly along the contact's learned path, or floods if we don't have one
+pub(super) fn route_to(contact: &Contact) -> PacketBuilder<'_> {
+	match contact.out_path() {
+		Some(path) => PacketBuilder::new(RouteType::Direct).path(path),
+		None => PacketBuilder::new(RouteType::Flood),
+	}
+}
+
+/// Builds a flooded PATH packet telling `contact` the route its flood packet took to reach us,
+/// with `extra` piggybacked on it
+pub(super) fn build_path_return<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	path: &[u8],
+	extra_type: PayloadType,
+	extra: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let returned_path = ReturnedPath {
+		path,
+		extra_type: extra_type as u8,
+		extra,
+	};
+	let plaintext_len = returned_path.write_bytes(crypto_buffer)?;
+
+	let payload = encrypt_direct_message(identity, contact, crypto_buffer, plaintext_len);
+
+	PacketBuilder::new(RouteType::Flood).build(&Payload::Path(payload), packet_buffer)
+}
+
+/// Builds a flooded text to everyone on `channel`, prefixed with our name as group texts are
+pub(super) fn build_group_text<'a>(
+	settings: &Settings,
+	channel: &Channel,
+	timestamp: u32,
+	text: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let (plain_header, body) =
+		PlainMessageHeader::mut_from_prefix(crypto_buffer).map_err(|_| Error::ZeroCopy)?;
+	plain_header.timestamp = U32::from(timestamp);
+	plain_header.flags = MessageFlags::new(TextType::Plain, 0);
+	let mut len = write_bytes(body, settings.name())?;
+	len += write_bytes(&mut body[len..], b": ")?;
+	len += write_bytes(&mut body[len..], text)?;
+	let plaintext_len = size_of::<PlainMessageHeader>() + len;
+
+	let payload = channel.encrypt(crypto_buffer, plaintext_len)?;
+
+	PacketBuilder::new(RouteType::Flood).build(&Payload::GrpText(payload), packet_buffer)
+}
+
+/// Builds flooded binary data to everyone on `channel`
+pub(super) fn build_group_data<'a>(
+	channel: &Channel,
+	timestamp: u32,
+	data_type: DataType,
+	data: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let group_data = GroupData {
+		header: GroupDataHeader {
+			timestamp: U32::from(timestamp),
+			data_type,
+			len: data.len() as _,
+		},
+		data,
+	};
+	let plaintext_len = group_data.write_bytes(crypto_buffer)?;
+
+	let payload = channel.encrypt(crypto_buffer, plaintext_len)?;
+
+	PacketBuilder::new(RouteType::Flood).build(&Payload::GrpData(payload), packet_buffer)
+}
+
+/// Builds an encrypted request, flooded unless we know a path to the contact
+pub(super) fn build_request<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	tag: u32,
+	request_type: RequestType,
+	data: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let request = Request {
+		tag: U32::from(tag),
+		request_type: request_type as u8,
+		data,
+	};
+	let plaintext_len = request.write_bytes(crypto_buffer)?;
+
+	let payload = encrypt_direct_message(identity, contact, crypto_buffer, plaintext_len);
+
+	route_to(contact).build(&Payload::Req(payload), packet_buffer)
+}
+
+/// Builds an anonymous login, carrying our public key for servers that haven't heard our advert
+pub(super) fn build_login<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	timestamp: u32,
+	password: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let login = LoginRequest {
+		timestamp: U32::from(timestamp),
+		// Ask a room server for all the posts it still holds
+		sync_since: (contact.adv_type == AdvType::Room).then(|| U32::from(0)),
+		password,
+	};
+	let plaintext_len = login.write_bytes(crypto_buffer)?;
+
+	let shared_secret = contact.shared_secret(identity);
+	let ciphertext = encrypt_message(&shared_secret.aes_key(), crypto_buffer, plaintext_len);
+	let mac = msg_mac_32(ciphertext, shared_secret.as_bytes())?;
+	let payload = AnonReqPayload {
+		header: AnonReqHeader {
+			dest_hash: contact.hash(),
+			pub_key: identity.public_key(),
+			mac: [mac[0], mac[1]],
+		},
+		ciphertext,
+	};
+
+	route_to(contact).build(&Payload::AnonReq(payload), packet_buffer)
+}
+
+/// Builds the answer to a request that reached us with `route_type` along `path`.
+/// A flooded request is answered by returning its path, so the requester learns a route to us.
+pub(super) fn build_response<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	route_type: RouteType,
+	path: &[u8],
+	response: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	if route_type == RouteType::Flood {
+		return build_path_return(
+			identity,
+			contact,
+			path,
+			PayloadType::Resp,
+			response,
+			crypto_buffer,
+			packet_buffer,
+		);
+	}
+	let plaintext_len = write_bytes(crypto_buffer, response)?;
+
+	let payload = encrypt_direct_message(identity, contact, crypto_buffer, plaintext_len);
+
+	route_to(contact).build(&Payload::Resp(payload), packet_buffer)
+}
+
+/// Builds a signed advert, flooded to the whole mesh or only sent to our neighbours
+pub(super) fn build_advert<'a>(
+	identity: &SigningKeys,
+	settings: &Settings,
+	timestamp: u32,
+	flood: bool,
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let mut advert = Advert::new(settings.role, timestamp).with_name(settings.name());
+	if let Some(lat_long) = &settings.lat_long {
+		advert = advert.with_lat_long(lat_long.clone());
+	}
+	if let Some(feature) = settings.features[0] {
+		advert = advert.with_battery(Battery(U16::from(feature)));
+	}
+	if let Some(feature) = settings.features[1] {
+		advert = advert.with_temperature(Temperature(U16::from(feature)));
+	}
+	advert.sign(identity)?;
+
+	let route_type = if flood {
+		RouteType::Flood
+	}
+	else {
+		RouteType::Direct
+	};
+	PacketBuilder::new(route_type).build(&Payload::Advert(advert), packet_buffer)
+}
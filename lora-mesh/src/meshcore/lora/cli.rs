@@ -0,0 +1,78 @@
+use crate::{
+	error::Result,
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		adverts::AdvertSchedule,
+		cli::CliAction,
+		client::{Event, Text},
+		contacts::{Contact, Permissions},
+		crypto::SigningKeys,
+		lora::{Node, build::build_direct_text, emit_event},
+		packet::{
+			U32,
+			plain_message::{MessageFlags, PlainMessage, PlainMessageHeader, TextType},
+		},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+
+/// Builds a CLI command or its output. Neither is ACKed, so there is nothing to wait for.
+pub(super) fn build_cli_text<'a>(
+	identity: &SigningKeys,
+	contact: &Contact,
+	timestamp: u32,
+	text: &[u8],
+	crypto_buffer: &mut [u8; PACKET_BUFFER_SIZE],
+	packet_buffer: &'a mut [u8; PACKET_BUFFER_SIZE],
+) -> Result<&'a [u8]> {
+	let message = PlainMessage {
+		header: PlainMessageHeader {
+			timestamp: U32::from(timestamp),
+			flags: MessageFlags::new(TextType::CliData, 0),
+		},
+		text_type: TextType::CliData,
+		signer: None,
+		text,
+	};
+	let (text_packet, _) =
+		build_direct_text(identity, contact, &message, crypto_buffer, packet_buffer)?;
+	Ok(text_packet)
+}
+
+/// The command in a CLI text, if `sender` is an admin and hasn't sent it before
+pub(super) fn accept_command(sender: &mut Contact, message: &PlainMessage) -> Option<Text> {
+	if sender.permissions != Some(Permissions::Admin) {
+		info!("Not running CLI command from a contact that isn't an admin");
+		return None;
+	}
+	let command_timestamp = message.header.timestamp.0.get();
+	if command_timestamp <= sender.last_command {
+		warn!("Replayed CLI command");
+		return None;
+	}
+	sender.last_command = command_timestamp;
+	Some(Text::from_lossy(message.text))
+}
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Carries out what a CLI command asked for beyond changing the settings
+	pub(super) async fn apply_cli_action(&mut self, action: CliAction) {
+		match action {
+			CliAction::None => (),
+			CliAction::SaveSettings => {
+				// Advertises any new name or location, on the new intervals
+				self.adverts = AdvertSchedule::new(&self.settings, Instant::now(), &mut self.rng);
+				emit_event(
+					self.client,
+					Event::SettingsChanged {
+						settings: self.settings.clone(),
+					},
+				);
+			}
+			CliAction::SendAdvert => self.send_advert(true).await,
+			CliAction::Reboot => emit_event(self.client, Event::RebootRequested),
+		}
+	}
+}
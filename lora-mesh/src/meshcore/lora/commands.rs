@@ -0,0 +1,257 @@
+use crate::{
+	error::{Error, Result},
+	fmt::Display2Format,
+	meshcore::{
+		acks::PendingMessage,
+		client::{Command, Event, Text},
+		lora::{
+			Node,
+			build::{
+				build_direct_text, build_group_data, build_group_text, build_login, build_request,
+			},
+			cli::build_cli_text,
+			emit_event, transmit,
+		},
+		packet::{
+			U32,
+			plain_message::{MessageFlags, PlainMessage, PlainMessageHeader, TextType},
+		},
+		requests::{PendingRequest, REQUEST_TIMEOUT},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Carries out a command from the client
+	pub(super) async fn handle_command(&mut self, command: Command) {
+		match command {
+			Command::SendDirectText { id, dest, text } => {
+				self.send_direct_text(id, dest, text).await
+			}
+			Command::SendGroupText { channel, text } => {
+				let Some(channel) = self.channels.get(channel as usize)
+				else {
+					warn!("No channel {}", channel);
+					return;
+				};
+				match build_group_text(
+					&self.settings,
+					channel,
+					self.clock.now(),
+					text.as_bytes(),
+					&mut self.crypto_buffer,
+					&mut self.resp_buffer,
+				) {
+					Ok(group_packet) => {
+						if transmit(&mut self.radio, &mut self.recent_packets, group_packet)
+							.await
+							.is_err()
+						{
+							warn!("Failed to send group text");
+						}
+					}
+					Err(e) => warn!("Failed to build group text: {}", Display2Format(&e)),
+				}
+			}
+			Command::SendGroupData {
+				channel,
+				data_type,
+				data,
+			} => {
+				let Some(channel) = self.channels.get(channel as usize)
+				else {
+					warn!("No channel {}", channel);
+					return;
+				};
+				match build_group_data(
+					channel,
+					self.clock.now(),
+					data_type,
+					data.as_bytes(),
+					&mut self.crypto_buffer,
+					&mut self.resp_buffer,
+				) {
+					Ok(group_packet) => {
+						if transmit(&mut self.radio, &mut self.recent_packets, group_packet)
+							.await
+							.is_err()
+						{
+							warn!("Failed to send group data");
+						}
+					}
+					Err(e) => warn!("Failed to build group data: {}", Display2Format(&e)),
+				}
+			}
+			Command::SendRequest {
+				id,
+				dest,
+				request_type,
+				data,
+			} => {
+				let Some(contact) = self.contacts.get(&dest)
+				else {
+					warn!("Request to unknown contact");
+					emit_event(self.client, Event::RequestFailed { id });
+					return;
+				};
+				let tag = self.pending_requests.next_tag(self.clock.now());
+				let sent = match build_request(
+					&self.identity,
+					contact,
+					tag,
+					request_type,
+					data.as_bytes(),
+					&mut self.crypto_buffer,
+					&mut self.resp_buffer,
+				) {
+					Ok(request_packet) => {
+						transmit(&mut self.radio, &mut self.recent_packets, request_packet).await
+					}
+					Err(e) => Err(e),
+				};
+				let request = PendingRequest {
+					id,
+					dest,
+					tag,
+					deadline: Instant::now() + REQUEST_TIMEOUT,
+					login: false,
+				};
+				self.await_response(request, sent);
+			}
+			Command::SendCliCommand { dest, command } => {
+				let Some(contact) = self.contacts.get(&dest)
+				else {
+					warn!("CLI command to unknown contact");
+					return;
+				};
+				self.last_command_timestamp = self.clock.now().max(self.last_command_timestamp + 1);
+				let sent = match build_cli_text(
+					&self.identity,
+					contact,
+					self.last_command_timestamp,
+					command.as_bytes(),
+					&mut self.crypto_buffer,
+					&mut self.resp_buffer,
+				) {
+					Ok(text_packet) => {
+						transmit(&mut self.radio, &mut self.recent_packets, text_packet).await
+					}
+					Err(e) => Err(e),
+				};
+				if let Err(e) = sent {
+					warn!("Failed to send CLI command: {}", Display2Format(&e));
+				}
+			}
+			Command::Login { id, dest, password } => {
+				let Some(contact) = self.contacts.get(&dest)
+				else {
+					warn!("Login to unknown contact");
+					emit_event(self.client, Event::RequestFailed { id });
+					return;
+				};
+				let tag = self.pending_requests.next_tag(self.clock.now());
+				let sent = match build_login(
+					&self.identity,
+					contact,
+					tag,
+					password.as_bytes(),
+					&mut self.crypto_buffer,
+					&mut self.resp_buffer,
+				) {
+					Ok(login_packet) => {
+						transmit(&mut self.radio, &mut self.recent_packets, login_packet).await
+					}
+					Err(e) => Err(e),
+				};
+				let request = PendingRequest {
+					id,
+					dest,
+					tag,
+					deadline: Instant::now() + REQUEST_TIMEOUT,
+					login: true,
+				};
+				self.await_response(request, sent);
+			}
+			Command::SetChannel { index, channel } => {
+				if self.channels.set(index as usize, channel).is_err() {
+					warn!("No channel slot {}", index);
+				}
+			}
+			Command::SetTime { timestamp } => match self.clock.set(timestamp) {
+				Ok(()) => info!("Clock set to {}", timestamp),
+				Err(e) => warn!("Clock not set: {}", Display2Format(&e)),
+			},
+			Command::SendAdvert { flood } => self.send_advert(flood).await,
+		}
+	}
+
+	async fn send_direct_text(&mut self, id: u32, dest: [u8; 32], text: Text) {
+		let mut message = PendingMessage {
+			id,
+			dest,
+			text,
+			timestamp: self.clock.now(),
+			attempt: 0,
+			ack_hash: [0; 4],
+			deadline: Instant::now(),
+		};
+		if let Err(e) = self.send_pending_message(&mut message).await {
+			warn!("Failed to send message: {}", Display2Format(&e));
+			emit_event(self.client, Event::MessageFailed { id });
+			return;
+		}
+		message.deadline = Instant::now() + self.pending_acks.timeout(message.attempt);
+		if self.pending_acks.insert(message).is_err() {
+			warn!("Too many messages awaiting ACKs");
+			emit_event(self.client, Event::MessageFailed { id });
+		}
+	}
+
+	/// Transmits the current attempt of `message`, recording the ACK hash to wait for
+	pub(super) async fn send_pending_message(
+		&mut self,
+		message: &mut PendingMessage,
+	) -> Result<()> {
+		let contact = self
+			.contacts
+			.get(&message.dest)
+			.ok_or(Error::UnknownContact)?;
+		let plain_message = PlainMessage {
+			header: PlainMessageHeader {
+				timestamp: U32::from(message.timestamp),
+				flags: MessageFlags::new(TextType::Plain, message.attempt),
+			},
+			text_type: TextType::Plain,
+			signer: None,
+			text: message.text.as_bytes(),
+		};
+		let (text_packet, ack_hash) = build_direct_text(
+			&self.identity,
+			contact,
+			&plain_message,
+			&mut self.crypto_buffer,
+			&mut self.resp_buffer,
+		)?;
+
+		transmit(&mut self.radio, &mut self.recent_packets, text_packet).await?;
+		message.ack_hash = ack_hash;
+
+		Ok(())
+	}
+
+	/// Waits for the response to a request or login that was `sent`, failing it if it wasn't
+	fn await_response(&mut self, request: PendingRequest, sent: Result<()>) {
+		let (id, login) = (request.id, request.login);
+		if let Err(e) = sent.and_then(|()| self.pending_requests.insert(request)) {
+			if login {
+				warn!("Failed to send login: {}", Display2Format(&e));
+			}
+			else {
+				warn!("Failed to send request: {}", Display2Format(&e));
+			}
+			emit_event(self.client, Event::RequestFailed { id });
+		}
+	}
+}
@@ -0,0 +1,83 @@
+use crate::{
+	meshcore::{
+		client::{Data, Event, Text},
+		lora::{Node, emit_event},
+		packet::{
+			Packet, group_data::GroupData, group_packets::GroupPayload, plain_message::PlainMessage,
+		},
+	},
+	radio::MeshRadio,
+};
+use rand_core::RngCore;
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	pub(super) fn handle_group_text(&mut self, packet: &Packet<'_>) {
+		let Ok(group) = GroupPayload::from_bytes(packet.payload)
+		else {
+			warn!("Invalid group packet");
+			return;
+		};
+
+		info!("{:02x}", &group.header);
+
+		// Several channels may share the hash, and most group texts aren't for us
+		let Ok((index, decrypted)) = self.channels.decrypt(&group, &mut self.crypto_buffer)
+		else {
+			info!("Not on any of our channels");
+			return;
+		};
+
+		let Ok(message) = PlainMessage::from_bytes(decrypted)
+		else {
+			warn!("Invalid group text");
+			return;
+		};
+
+		info!("Channel {}, {}", index, message);
+		emit_event(
+			self.client,
+			Event::GroupText {
+				channel: index as _,
+				timestamp: message.header.timestamp.0.get(),
+				text: Text::from_lossy(message.text),
+			},
+		);
+	}
+
+	pub(super) fn handle_group_data(&mut self, packet: &Packet<'_>) {
+		let Ok(group) = GroupPayload::from_bytes(packet.payload)
+		else {
+			warn!("Invalid group packet");
+			return;
+		};
+
+		info!("{:02x}", &group.header);
+
+		let Ok((index, decrypted)) = self.channels.decrypt(&group, &mut self.crypto_buffer)
+		else {
+			info!("Not on any of our channels");
+			return;
+		};
+
+		let Ok(group_data) = GroupData::from_bytes(decrypted)
+		else {
+			warn!("Invalid group data");
+			return;
+		};
+		info!("Channel {}, Data: {}", index, group_data);
+
+		let Ok(data) = Data::new(group_data.data)
+		else {
+			warn!("Group data too long");
+			return;
+		};
+		emit_event(
+			self.client,
+			Event::GroupData {
+				channel: index as _,
+				data_type: group_data.header.data_type,
+				data,
+			},
+		);
+	}
+}
@@ -0,0 +1,99 @@
+use crate::{
+	fmt::Display2Format,
+	meshcore::{
+		lora::{Node, build::build_response, decrypt_anon_request, room::catch_up, transmit},
+		packet::{Packet, advert::AdvType, direct_packets::AnonReqPayload, login::LoginRequest},
+		requests::{check_password, login_response},
+	},
+	radio::MeshRadio,
+};
+use rand_core::RngCore;
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Logs in a client that knows one of our passwords, if we're a server.
+	/// Anonymous requests are only ever logins, as with the reference firmware.
+	pub(super) async fn handle_login(&mut self, packet: &Packet<'_>) {
+		let Ok(anon) = AnonReqPayload::from_bytes(packet.payload)
+		else {
+			warn!("Invalid anonymous request");
+			return;
+		};
+		// Only servers take logins
+		if anon.header.dest_hash != self.our_hash
+			|| !matches!(self.settings.role, AdvType::Repeater | AdvType::Room)
+		{
+			return;
+		}
+		info!("Anonymous request to this device");
+		let Ok(decrypted) = decrypt_anon_request(&self.identity, &anon, &mut self.crypto_buffer)
+		else {
+			warn!("Failed to decrypt anonymous request");
+			return;
+		};
+
+		let login = match self.settings.role {
+			AdvType::Room => LoginRequest::from_room_bytes(decrypted),
+			_ => LoginRequest::from_bytes(decrypted),
+		};
+		let Ok(login) = login
+		else {
+			warn!("Invalid login");
+			return;
+		};
+		// A wrong password gets no answer, as with the reference firmware
+		let Some(permissions) = check_password(&self.settings, login.password)
+		else {
+			warn!("Login with wrong password");
+			return;
+		};
+
+		let Ok(sender) = self.contacts.get_or_insert(&anon.header.pub_key)
+		else {
+			warn!("Invalid public key");
+			return;
+		};
+		let login_timestamp = login.timestamp.0.get();
+		if login_timestamp <= sender.last_login {
+			warn!("Replayed login");
+			return;
+		}
+		sender.last_login = login_timestamp;
+		sender.permissions = Some(permissions);
+		if let Some(sync_since) = &login.sync_since {
+			sender.sync_since = sync_since.0.get();
+			catch_up(&mut self.room, sender);
+		}
+		// The client may have moved since it last logged in, so learn its path afresh
+		sender.reset_out_path();
+		info!("Logged in as {}", permissions);
+
+		let Ok(response_len) = login_response(
+			permissions,
+			self.clock.now(),
+			self.rng.next_u32(),
+			&mut self.reply_buffer,
+		)
+		else {
+			return;
+		};
+		match build_response(
+			&self.identity,
+			sender,
+			packet.header.flags.route_type(),
+			packet.path,
+			&self.reply_buffer[..response_len],
+			&mut self.crypto_buffer,
+			&mut self.resp_buffer,
+		) {
+			Ok(response_packet) => {
+				if transmit(&mut self.radio, &mut self.recent_packets, response_packet)
+					.await
+					.is_err()
+				{
+					warn!("Failed to send login response");
+				}
+			}
+			Err(e) => warn!("Failed to build login response: {}", Display2Format(&e)),
+		}
+	}
+}
@@ -0,0 +1,171 @@
+use crate::{
+	fmt::Display2Format,
+	meshcore::{
+		client::{Client, Data, Event},
+		contacts::Permissions,
+		lora::{
+			Node, build::build_response, decrypt_direct_message, emit_event, room::catch_up,
+			transmit,
+		},
+		packet::{
+			Packet,
+			advert::AdvType,
+			login::LoginResponse,
+			request::{Request, RequestType, Response},
+		},
+		requests::{PendingRequests, RequestContext, respond},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+use zerocopy::FromBytes;
+
+/// Hands a response from `sender` to whoever sent the matching request
+pub(super) fn hand_over_response(
+	client: &Client,
+	pending_requests: &mut PendingRequests,
+	sender: &[u8; 32],
+	plaintext: &[u8],
+) {
+	let Ok(response) = Response::from_bytes(plaintext)
+	else {
+		warn!("Invalid response");
+		return;
+	};
+	let Some(request) = pending_requests.take_response(sender, response.tag.0.get())
+	else {
+		info!("No request waiting for response {}", response.tag);
+		return;
+	};
+	info!("Response to request {}", request.id);
+	if request.login {
+		match LoginResponse::ref_from_prefix(response.data) {
+			Ok((login, _)) => emit_event(
+				client,
+				Event::LoggedIn {
+					id: request.id,
+					permissions: Permissions::from(login.permissions),
+				},
+			),
+			Err(_) => emit_event(client, Event::RequestFailed { id: request.id }),
+		}
+		return;
+	}
+	let Ok(data) = Data::new(response.data)
+	else {
+		emit_event(client, Event::RequestFailed { id: request.id });
+		return;
+	};
+	emit_event(
+		client,
+		Event::Response {
+			id: request.id,
+			data,
+		},
+	);
+}
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Answers a request from a contact that logged in to us
+	pub(super) async fn handle_request(&mut self, packet: &Packet<'_>) {
+		let Some(direct) = self.direct_to_us(packet.payload)
+		else {
+			return;
+		};
+		info!("Request to this device");
+		info!("{:02x}", &direct.header);
+		// Only borrowed, as the whole contact list may be needed for the answer
+		let Ok((decrypted, sender)) = decrypt_direct_message(
+			&self.identity,
+			self.contacts.matching_hash(direct.header.src_hash),
+			&direct.header,
+			direct.ciphertext,
+			&mut self.crypto_buffer,
+		)
+		else {
+			warn!("Failed to decrypt message");
+			return;
+		};
+
+		// Only contacts that logged in may make requests, as with the reference firmware
+		let Some(permissions) = sender.permissions
+		else {
+			info!("Request from a contact that hasn't logged in");
+			return;
+		};
+
+		let Ok(request) = Request::from_bytes(decrypted)
+		else {
+			warn!("Invalid request");
+			return;
+		};
+		info!("{}", request);
+		let keep_alive = request.request_type == RequestType::KeepAlive as u8;
+
+		let context = RequestContext {
+			permissions,
+			contacts: &self.contacts,
+			stats: &self.radio.stats,
+			neighbours: &mut self.neighbours,
+			tx_queue_len: self.outbound.len(),
+			now: Instant::now(),
+		};
+		let Ok(response_len) = respond(&request, context, &mut self.reply_buffer)
+		else {
+			info!("Not answering request type {}", request.request_type);
+			return;
+		};
+
+		if keep_alive && self.settings.role == AdvType::Room {
+			catch_up(&mut self.room, sender);
+		}
+		match build_response(
+			&self.identity,
+			sender,
+			packet.header.flags.route_type(),
+			packet.path,
+			&self.reply_buffer[..response_len],
+			&mut self.crypto_buffer,
+			&mut self.resp_buffer,
+		) {
+			Ok(response_packet) => {
+				if transmit(&mut self.radio, &mut self.recent_packets, response_packet)
+					.await
+					.is_err()
+				{
+					warn!("Failed to send response");
+				}
+			}
+			Err(e) => warn!("Failed to build response: {}", Display2Format(&e)),
+		}
+	}
+
+	/// Hands a response to one of our requests, sent directly, to the client
+	pub(super) fn handle_response(&mut self, packet: &Packet<'_>) {
+		let Some(direct) = self.direct_to_us(packet.payload)
+		else {
+			return;
+		};
+		info!("Response to this device");
+		info!("{:02x}", &direct.header);
+		let Ok((decrypted, sender)) = decrypt_direct_message(
+			&self.identity,
+			self.contacts.matching_hash(direct.header.src_hash),
+			&direct.header,
+			direct.ciphertext,
+			&mut self.crypto_buffer,
+		)
+		else {
+			warn!("Failed to decrypt message");
+			return;
+		};
+
+		hand_over_response(
+			self.client,
+			&mut self.pending_requests,
+			sender.pub_key(),
+			decrypted,
+		);
+	}
+}
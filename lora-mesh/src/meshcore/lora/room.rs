@@ -0,0 +1,98 @@
+use crate::{
+	fmt::Display2Format,
+	meshcore::{
+		client::Text,
+		contacts::{Contact, Permissions},
+		lora::{Node, build::build_direct_text, transmit},
+		packet::{
+			U32,
+			plain_message::{
+				MessageFlags, PlainMessage, PlainMessageHeader, SIGNER_PREFIX_SIZE, TextType,
+			},
+		},
+		room::{PUSH_ACK_TIMEOUT, Push, Room},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+
+/// A member checking in wants its room posts again, even after missed ACKs
+pub(super) fn catch_up(room: &mut Room, member: &Contact) {
+	member.push_failures.set(0);
+	room.wake(Instant::now());
+}
+
+/// Stores a text from a member as a post, if it may write to the room
+pub(super) fn add_post(room: &mut Room, sender: &Contact, message: &PlainMessage, now: u32) {
+	if !sender
+		.permissions
+		.is_some_and(|permissions| permissions >= Permissions::ReadWrite)
+	{
+		info!("Not posting text from a contact without write access");
+		return;
+	}
+	if let Some(post_timestamp) = room.add_post(
+		sender.pub_key(),
+		message.header.timestamp.0.get(),
+		now,
+		Text::from_lossy(message.text),
+		Instant::now(),
+	) {
+		info!("Stored post {}", post_timestamp);
+	}
+}
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Sends the next room post a member hasn't seen, if any
+	pub(super) async fn push_post(&mut self) {
+		let Some((member, post)) = self.room.select_push(&self.contacts)
+		else {
+			return;
+		};
+		let Some(contact) = self.contacts.get(&member)
+		else {
+			return;
+		};
+		// Signed with the author's key prefix, so members can tell who wrote it
+		let plain_message = PlainMessage {
+			header: PlainMessageHeader {
+				timestamp: U32::from(post.timestamp),
+				flags: MessageFlags::new(TextType::SignedPlain, 0),
+			},
+			text_type: TextType::SignedPlain,
+			signer: Some(post.author[..SIGNER_PREFIX_SIZE].try_into().unwrap()),
+			text: post.text.as_bytes(),
+		};
+		let sent = match build_direct_text(
+			&self.identity,
+			contact,
+			&plain_message,
+			&mut self.crypto_buffer,
+			&mut self.resp_buffer,
+		) {
+			Ok((text_packet, ack_hash)) => {
+				transmit(&mut self.radio, &mut self.recent_packets, text_packet)
+					.await
+					.map(|()| ack_hash)
+			}
+			Err(e) => Err(e),
+		};
+		let ack_hash = match sent {
+			Ok(ack_hash) => ack_hash,
+			Err(e) => {
+				warn!("Failed to push post: {}", Display2Format(&e));
+				// Counted as a missed ACK, so a member we can't reach is given up on
+				contact.push_failures.set(contact.push_failures.get() + 1);
+				self.room.wake(Instant::now());
+				return;
+			}
+		};
+		self.room.start_push(Push {
+			member,
+			timestamp: post.timestamp,
+			ack_hash,
+			deadline: Instant::now() + PUSH_ACK_TIMEOUT,
+		});
+	}
+}
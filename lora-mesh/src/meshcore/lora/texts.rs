@@ -0,0 +1,154 @@
+use crate::{
+	fmt::Display2Format,
+	meshcore::{
+		cli::{self, CliContext},
+		client::{Event, Text},
+		crypto::msg_ack_hash,
+		lora::{
+			Node,
+			build::{build_path_return, route_to},
+			cli::{accept_command, build_cli_text},
+			decrypt_direct_message, emit_event,
+			room::add_post,
+			transmit,
+		},
+		packet::{
+			Packet, Payload, PayloadType, RouteType,
+			ack::Ack,
+			advert::AdvType,
+			plain_message::{PlainMessage, TextType},
+		},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// Handles a direct text to us: a CLI command from an admin of a server, or a text for the
+	/// client, also posted to the room if we host one, and ACKed
+	pub(super) async fn handle_text(&mut self, packet: &Packet<'_>) {
+		let Some(direct) = self.direct_to_us(packet.payload)
+		else {
+			return;
+		};
+		info!("Direct text to this device");
+		info!("{:02x}", &direct.header);
+		let Ok((decrypted, sender)) = decrypt_direct_message(
+			&self.identity,
+			self.contacts.matching_hash_mut(direct.header.src_hash),
+			&direct.header,
+			direct.ciphertext,
+			&mut self.crypto_buffer,
+		)
+		else {
+			warn!("Failed to decrypt message");
+			return;
+		};
+
+		let Ok(message) = PlainMessage::from_bytes(decrypted)
+		else {
+			warn!("Invalid text message");
+			return;
+		};
+
+		info!("{}", message);
+
+		// Admins manage repeaters and room servers remotely with CLI commands
+		if message.text_type == TextType::CliData
+			&& matches!(self.settings.role, AdvType::Repeater | AdvType::Room)
+		{
+			let Some(command) = accept_command(sender, &message)
+			else {
+				return;
+			};
+			info!("Running CLI command: {}", command.as_str());
+
+			let mut output = Text::default();
+			let context = CliContext {
+				settings: &mut self.settings,
+				stats: &self.radio.stats,
+				neighbours: &self.neighbours,
+				now: Instant::now(),
+			};
+			let action = cli::execute(command.as_str(), context, &mut output);
+
+			let sent = match build_cli_text(
+				&self.identity,
+				sender,
+				self.clock.now(),
+				output.as_bytes(),
+				&mut self.crypto_buffer,
+				&mut self.resp_buffer,
+			) {
+				Ok(text_packet) => {
+					transmit(&mut self.radio, &mut self.recent_packets, text_packet).await
+				}
+				Err(e) => Err(e),
+			};
+			if let Err(e) = sent {
+				warn!("Failed to send CLI output: {}", Display2Format(&e));
+			}
+
+			self.apply_cli_action(action).await;
+			return;
+		}
+
+		emit_event(
+			self.client,
+			Event::TextMessage {
+				sender: *sender.pub_key(),
+				timestamp: message.header.timestamp.0.get(),
+				text_type: message.text_type,
+				signer: message.signer.copied(),
+				text: Text::from_lossy(message.text),
+			},
+		);
+
+		// Room members with write access post by texting the room
+		if self.settings.role == AdvType::Room && message.text_type == TextType::Plain {
+			add_post(&mut self.room, sender, &message, self.clock.now());
+		}
+
+		// Replies to CLI commands aren't ACKed
+		let ack = match message.text_type {
+			TextType::Plain => msg_ack_hash(&message.header, None, message.text, sender.pub_key()),
+			TextType::CliData => return,
+			TextType::SignedPlain => msg_ack_hash(
+				&message.header,
+				message.signer,
+				message.text,
+				&self.identity.public_key(),
+			),
+		};
+
+		// A flooded message means the sender has no path to us, so return the one it took with
+		// the ACK attached
+		let ack_packet = if packet.header.flags.route_type() == RouteType::Flood {
+			build_path_return(
+				&self.identity,
+				sender,
+				packet.path,
+				PayloadType::Ack,
+				&ack,
+				&mut self.crypto_buffer,
+				&mut self.resp_buffer,
+			)
+		}
+		else {
+			route_to(sender).build(&Payload::Ack(Ack { hash: ack }), &mut self.resp_buffer)
+		};
+		let Ok(ack_packet) = ack_packet
+		else {
+			warn!("Failed to build ACK");
+			return;
+		};
+
+		if transmit(&mut self.radio, &mut self.recent_packets, ack_packet)
+			.await
+			.is_err()
+		{
+			warn!("Failed to send ACK");
+		}
+	}
+}
@@ -0,0 +1,82 @@
+use crate::{
+	meshcore::{
+		client::Event,
+		lora::{Node, emit_event, transmit},
+	},
+	radio::MeshRadio,
+};
+use embassy_time::Instant;
+use rand_core::RngCore;
+
+impl<M: MeshRadio, R: RngCore> Node<'_, M, R> {
+	/// When the next retry, timeout or scheduled send is due, if anything is waiting
+	pub(super) fn next_deadline(&self) -> Option<Instant> {
+		[
+			self.pending_acks.next_deadline(),
+			self.pending_requests.next_deadline(),
+			self.room.next_deadline(),
+			self.adverts.next_due(),
+			self.outbound.next_due(),
+		]
+		.into_iter()
+		.flatten()
+		.min()
+	}
+
+	/// Retries or gives up on whatever timed out, and sends whatever is due
+	pub(super) async fn handle_deadlines(&mut self) {
+		while let Some(mut message) = self.pending_acks.take_expired(Instant::now()) {
+			let id = message.id;
+			if !self.pending_acks.can_retry(message.attempt) {
+				warn!("No ACK for message {}", id);
+				emit_event(self.client, Event::MessageFailed { id });
+				continue;
+			}
+
+			message.attempt += 1;
+			info!("Retrying message {}, attempt {}", id, message.attempt);
+			// The learned path may be stale, so the last attempt floods
+			if !self.pending_acks.can_retry(message.attempt)
+				&& let Some(contact) = self.contacts.get_mut(&message.dest)
+			{
+				contact.reset_out_path();
+			}
+			if self.send_pending_message(&mut message).await.is_err() {
+				emit_event(self.client, Event::MessageFailed { id });
+				continue;
+			}
+			message.deadline = Instant::now() + self.pending_acks.timeout(message.attempt);
+			if self.pending_acks.insert(message).is_err() {
+				emit_event(self.client, Event::MessageFailed { id });
+			}
+		}
+
+		while let Some(request) = self.pending_requests.take_expired(Instant::now()) {
+			warn!("No response to request {}", request.id);
+			emit_event(self.client, Event::RequestFailed { id: request.id });
+		}
+
+		if let Some(push) = self.room.take_expired(Instant::now()) {
+			warn!("No ACK for post {}", push.timestamp);
+			if let Some(member) = self.contacts.get(&push.member) {
+				member.push_failures.set(member.push_failures.get() + 1);
+			}
+		}
+		if self.room.push_due(Instant::now()) {
+			self.push_post().await;
+		}
+
+		if let Some(flood) = self.adverts.due(Instant::now()) {
+			self.send_advert(flood).await;
+		}
+
+		while let Some(queued) = self.outbound.take_due(Instant::now()) {
+			if transmit(&mut self.radio, &mut self.recent_packets, queued.as_bytes())
+				.await
+				.is_err()
+			{
+				warn!("Failed to send queued packet");
+			}
+		}
+	}
+}
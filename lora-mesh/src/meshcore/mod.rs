@@ -0,0 +1,25 @@
+pub mod acks;
+pub mod adverts;
+pub mod channels;
+pub mod cli;
+pub mod client;
+pub mod clock;
+pub mod contacts;
+pub mod crypto;
+pub mod lora;
+pub mod neighbours;
+pub mod outbound;
+pub mod packet;
+pub mod repeater;
+pub mod requests;
+pub mod room;
+pub mod stats;
+
+pub const PACKET_BUFFER_SIZE: usize = 256;
+pub const MESHCORE_SYNCWORD: u8 = 0x12;
+
+pub const MAX_PACKET_PAYLOAD: usize = 184;
+pub const MAX_PATH_SIZE: usize = 64;
+pub const SIGNATURE_SIZE: usize = 64;
+pub const MAX_TEXT_SIZE: usize = 160;
+pub const MAX_GROUP_DATA_SIZE: usize = 160;
@@ -0,0 +1,74 @@
+use crate::meshcore::packet::request::NeighbourOrder;
+use embassy_time::Instant;
+
+pub const MAX_NEIGHBOURS: usize = 16;
+
+/// A node whose advert we heard straight from it, rather than relayed
+#[derive(Clone)]
+pub struct Neighbour {
+	pub pub_key: [u8; 32],
+	pub heard: Instant,
+	/// In quarter dB, as neighbours lists are sent
+	pub snr: i8,
+}
+
+/// Fixed capacity table of the nodes in radio range, evicting the least recently heard
+pub struct Neighbours {
+	neighbours: [Option<Neighbour>; MAX_NEIGHBOURS],
+}
+
+impl Neighbours {
+	pub const fn new() -> Self {
+		Self {
+			neighbours: [const { None }; MAX_NEIGHBOURS],
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &Neighbour> { self.neighbours.iter().flatten() }
+
+	pub fn len(&self) -> usize { self.iter().count() }
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	/// Records hearing `pub_key` directly, with `snr` in dB
+	pub fn heard(&mut self, pub_key: &[u8; 32], snr: i16, now: Instant) {
+		let neighbour = Neighbour {
+			pub_key: *pub_key,
+			heard: now,
+			snr: snr.saturating_mul(4).clamp(i8::MIN as i16, i8::MAX as i16) as i8,
+		};
+		let index = self
+			.neighbours
+			.iter()
+			.position(|slot| slot.as_ref().is_some_and(|n| &n.pub_key == pub_key))
+			.or_else(|| self.neighbours.iter().position(Option::is_none))
+			.unwrap_or_else(|| {
+				let (index, _) = self
+					.neighbours
+					.iter()
+					.enumerate()
+					.min_by_key(|(_, slot)| slot.as_ref().map(|n| n.heard))
+					.unwrap();
+				index
+			});
+		self.neighbours[index] = Some(neighbour);
+	}
+
+	/// Reorders the table so `iter` yields neighbours by `order`
+	pub fn sort(&mut self, order: NeighbourOrder) {
+		self.neighbours.sort_unstable_by(|a, b| match (a, b) {
+			(Some(a), Some(b)) => match order {
+				NeighbourOrder::NewestFirst => b.heard.cmp(&a.heard),
+				NeighbourOrder::OldestFirst => a.heard.cmp(&b.heard),
+				NeighbourOrder::StrongestFirst => b.snr.cmp(&a.snr),
+				NeighbourOrder::WeakestFirst => a.snr.cmp(&b.snr),
+			},
+			// Empty slots go last
+			_ => b.is_some().cmp(&a.is_some()),
+		});
+	}
+}
+
+impl Default for Neighbours {
+	fn default() -> Self { Self::new() }
+}
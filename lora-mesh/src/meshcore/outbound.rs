@@ -0,0 +1,68 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::PACKET_BUFFER_SIZE,
+};
+use embassy_time::Instant;
+
+pub const OUTBOUND_QUEUE_SIZE: usize = 4;
+
+pub struct QueuedPacket {
+	due: Instant,
+	len: usize,
+	bytes: [u8; PACKET_BUFFER_SIZE],
+}
+
+impl QueuedPacket {
+	pub fn as_bytes(&self) -> &[u8] { &self.bytes[..self.len] }
+}
+
+/// Packets waiting for a delayed transmission, such as flood retransmits
+pub struct OutboundQueue {
+	packets: [Option<QueuedPacket>; OUTBOUND_QUEUE_SIZE],
+}
+
+impl OutboundQueue {
+	pub const fn new() -> Self {
+		Self {
+			packets: [const { None }; OUTBOUND_QUEUE_SIZE],
+		}
+	}
+
+	pub fn push(&mut self, due: Instant, packet: &[u8]) -> Result<()> {
+		let slot = self
+			.packets
+			.iter_mut()
+			.find(|slot| slot.is_none())
+			.ok_or(Error::QueueFull)?;
+
+		let len = packet.len();
+		let mut bytes = [0; PACKET_BUFFER_SIZE];
+		bytes
+			.get_mut(..len)
+			.ok_or(Error::PacketBuild)?
+			.copy_from_slice(packet);
+		*slot = Some(QueuedPacket { due, len, bytes });
+		Ok(())
+	}
+
+	pub fn len(&self) -> usize { self.packets.iter().flatten().count() }
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	pub fn next_due(&self) -> Option<Instant> {
+		self.packets.iter().flatten().map(|packet| packet.due).min()
+	}
+
+	/// Removes and returns the earliest packet that is due by `now`
+	pub fn take_due(&mut self, now: Instant) -> Option<QueuedPacket> {
+		self.packets
+			.iter_mut()
+			.filter(|slot| slot.as_ref().is_some_and(|packet| packet.due <= now))
+			.min_by_key(|slot| slot.as_ref().map(|packet| packet.due))
+			.and_then(Option::take)
+	}
+}
+
+impl Default for OutboundQueue {
+	fn default() -> Self { Self::new() }
+}
@@ -0,0 +1,530 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::{
+		MAX_PACKET_PAYLOAD, MAX_PATH_SIZE,
+		packet::{
+			ack::Ack,
+			advert::Advert,
+			direct_packets::{AnonReqPayload, DirectPayload},
+			group_packets::GroupPayload,
+		},
+	},
+};
+use sha2::{Digest, Sha256};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+pub mod ack;
+pub mod advert;
+pub mod direct_packets;
+pub mod group_data;
+pub mod group_packets;
+pub mod login;
+pub mod path;
+pub mod plain_message;
+pub mod request;
+
+pub const PACKET_HASH_SIZE: usize = 8;
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct U16(pub zerocopy::little_endian::U16);
+
+impl From<u16> for U16 {
+	fn from(value: u16) -> Self { Self(value.into()) }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for U16 {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:04x}", self.0.get());
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct I16(pub zerocopy::little_endian::I16);
+
+impl From<i16> for I16 {
+	fn from(value: i16) -> Self { Self(value.into()) }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for I16 {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{}", self.0.get());
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct U32(pub zerocopy::little_endian::U32);
+
+impl From<u32> for U32 {
+	fn from(value: u32) -> Self { Self(value.into()) }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for U32 {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:08x}", self.0.get());
+	}
+}
+
+pub fn try_split_at<T>(slice: &[T], index: usize) -> Option<(&[T], &[T])> {
+	(slice.len() >= index).then(|| slice.split_at(index))
+}
+
+pub fn try_split_at_mut<T>(slice: &mut [T], index: usize) -> Option<(&mut [T], &mut [T])> {
+	(slice.len() >= index).then(|| slice.split_at_mut(index))
+}
+
+/// Copies `bytes` to the start of `buffer`, returning the number of bytes written
+pub fn write_bytes(buffer: &mut [u8], bytes: &[u8]) -> Result<usize> {
+	buffer
+		.get_mut(..bytes.len())
+		.ok_or(Error::PacketBuild)?
+		.copy_from_slice(bytes);
+	Ok(bytes.len())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum RouteType {
+	Reserved1 = 0b00,
+	Flood = 0b01,
+	Direct = 0b10,
+	Reserved2 = 0b11,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum PayloadType {
+	Req = 0x0,
+	Resp = 0x1,
+	Txt = 0x2,
+	Ack = 0x3,
+	Advert = 0x4,
+	GrpText = 0x5,
+	GrpData = 0x6,
+	AnonReq = 0x7,
+	Path = 0x8,
+	RawCustom = 0xf,
+}
+
+impl TryFrom<u8> for PayloadType {
+	type Error = Error;
+
+	fn try_from(value: u8) -> Result<Self> {
+		let payload_type = match value {
+			0x0 => Self::Req,
+			0x1 => Self::Resp,
+			0x2 => Self::Txt,
+			0x3 => Self::Ack,
+			0x4 => Self::Advert,
+			0x5 => Self::GrpText,
+			0x6 => Self::GrpData,
+			0x7 => Self::AnonReq,
+			0x8 => Self::Path,
+			0xf => Self::RawCustom,
+			_ => return Err(Error::PacketParse),
+		};
+		Ok(payload_type)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum PayloadVersion {
+	Ver1 = 0b00,
+	Ver2 = 0b01,
+	Ver3 = 0b10,
+	Ver4 = 0b11,
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PacketFlags(pub u8);
+
+impl PacketFlags {
+	pub fn new(
+		route_type: RouteType,
+		payload_type: PayloadType,
+		payload_version: PayloadVersion,
+	) -> Self {
+		let header =
+			((payload_version as u8) << 6) | ((payload_type as u8) << 2) | (route_type as u8);
+		Self(header)
+	}
+
+	pub fn route_type(&self) -> RouteType {
+		match self.0 & 0b11 {
+			0b00 => RouteType::Reserved1,
+			0b01 => RouteType::Flood,
+			0b10 => RouteType::Direct,
+			0b11 => RouteType::Reserved2,
+			_ => unreachable!(),
+		}
+	}
+
+	pub fn payload_type(&self) -> Result<PayloadType> { PayloadType::try_from((self.0 >> 2) & 0xf) }
+
+	pub fn payload_version(&self) -> PayloadVersion {
+		match (self.0 >> 6) & 0b11 {
+			0b00 => PayloadVersion::Ver1,
+			0b01 => PayloadVersion::Ver2,
+			0b10 => PayloadVersion::Ver3,
+			0b11 => PayloadVersion::Ver4,
+			_ => unreachable!(),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PacketHeader {
+	pub flags: PacketFlags,
+	pub path_len: u8,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Packet<'a> {
+	pub header: PacketHeader,
+	pub path: &'a [u8],
+	pub payload: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, tail) =
+			PacketHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		let (path, payload) = try_split_at(tail, header.path_len as _).ok_or(Error::PacketParse)?;
+
+		// info!("header: {:02x}", header);
+		// info!("path: {:02x}", path);
+		// info!("payload: {:02x}", payload);
+
+		let packet = Self {
+			header: header.clone(),
+			path,
+			payload,
+		};
+
+		Ok(packet)
+	}
+
+	/// Identifies the packet independently of the path it took, for duplicate detection
+	pub fn hash(&self) -> [u8; PACKET_HASH_SIZE] {
+		let sha = Sha256::new()
+			.chain_update([(self.header.flags.0 >> 2) & 0xf])
+			.chain_update(self.payload)
+			.finalize();
+		let out = <[u8; 32]>::from(sha);
+		out[..PACKET_HASH_SIZE].try_into().unwrap()
+	}
+
+	pub fn parse_payload(&self) -> Result<Payload<'a>> {
+		Payload::from_bytes(self.header.flags.payload_type()?, self.payload)
+	}
+
+	pub fn to_bytes<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b [u8]> {
+		if self.header.path_len as usize != self.path.len() {
+			return Err(Error::PacketBuild);
+		}
+
+		let mut len = write_bytes(buffer, self.header.as_bytes())?;
+		len += write_bytes(&mut buffer[len..], self.path)?;
+		len += write_bytes(&mut buffer[len..], self.payload)?;
+
+		Ok(&buffer[..len])
+	}
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Payload<'a> {
+	Req(DirectPayload<'a>),
+	Resp(DirectPayload<'a>),
+	Txt(DirectPayload<'a>),
+	Ack(Ack),
+	Advert(Advert<'a>),
+	GrpText(GroupPayload<'a>),
+	GrpData(GroupPayload<'a>),
+	AnonReq(AnonReqPayload<'a>),
+	Path(DirectPayload<'a>),
+	RawCustom(&'a [u8]),
+}
+
+impl<'a> Payload<'a> {
+	pub fn from_bytes(payload_type: PayloadType, bytes: &'a [u8]) -> Result<Self> {
+		let payload = match payload_type {
+			PayloadType::Req => Self::Req(DirectPayload::from_bytes(bytes)?),
+			PayloadType::Resp => Self::Resp(DirectPayload::from_bytes(bytes)?),
+			PayloadType::Txt => Self::Txt(DirectPayload::from_bytes(bytes)?),
+			PayloadType::Ack => {
+				Self::Ack(Ack::read_from_bytes(bytes).map_err(|_| Error::PacketParse)?)
+			}
+			PayloadType::Advert => Self::Advert(Advert::from_bytes(bytes)?.0),
+			PayloadType::GrpText => Self::GrpText(GroupPayload::from_bytes(bytes)?),
+			PayloadType::GrpData => Self::GrpData(GroupPayload::from_bytes(bytes)?),
+			PayloadType::AnonReq => Self::AnonReq(AnonReqPayload::from_bytes(bytes)?),
+			PayloadType::Path => Self::Path(DirectPayload::from_bytes(bytes)?),
+			PayloadType::RawCustom => Self::RawCustom(bytes),
+		};
+		Ok(payload)
+	}
+
+	pub fn payload_type(&self) -> PayloadType {
+		match self {
+			Self::Req(_) => PayloadType::Req,
+			Self::Resp(_) => PayloadType::Resp,
+			Self::Txt(_) => PayloadType::Txt,
+			Self::Ack(_) => PayloadType::Ack,
+			Self::Advert(_) => PayloadType::Advert,
+			Self::GrpText(_) => PayloadType::GrpText,
+			Self::GrpData(_) => PayloadType::GrpData,
+			Self::AnonReq(_) => PayloadType::AnonReq,
+			Self::Path(_) => PayloadType::Path,
+			Self::RawCustom(_) => PayloadType::RawCustom,
+		}
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		match self {
+			Self::Req(x) | Self::Resp(x) | Self::Txt(x) | Self::Path(x) => x.write_bytes(buffer),
+			Self::Ack(x) => write_bytes(buffer, x.as_bytes()),
+			Self::Advert(x) => x.write_bytes(buffer),
+			Self::GrpText(x) | Self::GrpData(x) => x.write_bytes(buffer),
+			Self::AnonReq(x) => x.write_bytes(buffer),
+			Self::RawCustom(x) => write_bytes(buffer, x),
+		}
+	}
+}
+
+/// Assembles the wire representation of a packet from its typed parts
+#[derive(Clone)]
+pub struct PacketBuilder<'a> {
+	route_type: RouteType,
+	payload_version: PayloadVersion,
+	path: &'a [u8],
+}
+
+impl<'a> PacketBuilder<'a> {
+	pub fn new(route_type: RouteType) -> Self {
+		Self {
+			route_type,
+			payload_version: PayloadVersion::Ver1,
+			path: &[],
+		}
+	}
+
+	pub fn payload_version(mut self, payload_version: PayloadVersion) -> Self {
+		self.payload_version = payload_version;
+		self
+	}
+
+	pub fn path(mut self, path: &'a [u8]) -> Self {
+		self.path = path;
+		self
+	}
+
+	pub fn build<'b>(self, payload: &Payload, buffer: &'b mut [u8]) -> Result<&'b [u8]> {
+		if self.path.len() > MAX_PATH_SIZE {
+			return Err(Error::PacketBuild);
+		}
+
+		let (header, tail) = PacketHeader::mut_from_prefix(buffer).map_err(|_| Error::ZeroCopy)?;
+		header.flags = PacketFlags::new(
+			self.route_type,
+			payload.payload_type(),
+			self.payload_version,
+		);
+		header.path_len = self.path.len() as _;
+
+		let path_len = write_bytes(tail, self.path)?;
+		let payload_len = payload.write_bytes(&mut tail[path_len..])?;
+		if payload_len > MAX_PACKET_PAYLOAD {
+			return Err(Error::PacketBuild);
+		}
+
+		let packet_length = size_of::<PacketHeader>() + path_len + payload_len;
+
+		Ok(&buffer[..packet_length])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::meshcore::{
+		crypto::SigningKeys,
+		packet::{
+			advert::{AdvType, Battery, LatLong},
+			direct_packets::{AnonReqHeader, DirectHeader},
+			group_packets::GroupHeader,
+		},
+	};
+
+	/// Builds `payload`, parses it back and checks both the typed and wire forms survive
+	fn round_trip(route_type: RouteType, path: &[u8], payload: Payload) {
+		let mut buffer = [0; 256];
+		let bytes = PacketBuilder::new(route_type)
+			.path(path)
+			.build(&payload, &mut buffer)
+			.unwrap();
+
+		let packet = Packet::from_bytes(bytes).unwrap();
+		assert_eq!(packet.header.flags.route_type(), route_type);
+		assert_eq!(
+			packet.header.flags.payload_type().ok(),
+			Some(payload.payload_type())
+		);
+		assert_eq!(packet.header.flags.payload_version(), PayloadVersion::Ver1);
+		assert_eq!(packet.path, path);
+		assert!(packet.parse_payload().unwrap() == payload);
+
+		let mut rebuilt = [0; 256];
+		assert_eq!(packet.to_bytes(&mut rebuilt).unwrap(), bytes);
+	}
+
+	fn direct(ciphertext: &[u8]) -> DirectPayload<'_> {
+		DirectPayload {
+			header: DirectHeader {
+				dest_hash: 0x12,
+				src_hash: 0x34,
+				mac: [0x56, 0x78],
+			},
+			ciphertext,
+		}
+	}
+
+	fn group(ciphertext: &[u8]) -> GroupPayload<'_> {
+		GroupPayload {
+			header: GroupHeader {
+				channel_hash: 0x11,
+				mac: [0xab, 0xcd],
+			},
+			ciphertext,
+		}
+	}
+
+	#[test]
+	fn direct_payloads_round_trip() {
+		let ciphertext = [0x5a; 32];
+		round_trip(RouteType::Flood, &[], Payload::Req(direct(&ciphertext)));
+		round_trip(
+			RouteType::Direct,
+			&[1, 2, 3],
+			Payload::Resp(direct(&ciphertext)),
+		);
+		round_trip(RouteType::Direct, &[4], Payload::Txt(direct(&ciphertext)));
+		round_trip(
+			RouteType::Flood,
+			&[5, 6],
+			Payload::Path(direct(&ciphertext)),
+		);
+	}
+
+	#[test]
+	fn ack_round_trips() {
+		round_trip(
+			RouteType::Direct,
+			&[7, 8],
+			Payload::Ack(Ack { hash: [1, 2, 3, 4] }),
+		);
+	}
+
+	#[test]
+	fn advert_round_trips() {
+		let keys = SigningKeys::from_bytes(&[1; 32]);
+		let mut advert = Advert::new(AdvType::Repeater, 1_700_000_000)
+			.with_lat_long(LatLong {
+				lat: U32::from(51_500_000),
+				long: U32::from(-120_000i32 as u32),
+			})
+			.with_battery(Battery(U16::from(4100)))
+			.with_name(b"Repeater");
+		advert.sign(&keys).unwrap();
+		round_trip(RouteType::Flood, &[], Payload::Advert(advert.clone()));
+
+		let mut buffer = [0; 256];
+		let bytes = PacketBuilder::new(RouteType::Flood)
+			.build(&Payload::Advert(advert), &mut buffer)
+			.unwrap();
+		let Ok(Payload::Advert(parsed)) = Packet::from_bytes(bytes).unwrap().parse_payload()
+		else {
+			panic!("Not an advert");
+		};
+		assert_eq!(parsed.header.pub_key, keys.public_key());
+		assert!(parsed.verify_signature().is_ok());
+	}
+
+	#[test]
+	fn group_payloads_round_trip() {
+		let ciphertext = [0xa5; 48];
+		round_trip(RouteType::Flood, &[9], Payload::GrpText(group(&ciphertext)));
+		round_trip(RouteType::Flood, &[], Payload::GrpData(group(&ciphertext)));
+	}
+
+	#[test]
+	fn anon_req_round_trips() {
+		let ciphertext = [0x3c; 16];
+		round_trip(
+			RouteType::Flood,
+			&[],
+			Payload::AnonReq(AnonReqPayload {
+				header: AnonReqHeader {
+					dest_hash: 0x12,
+					pub_key: [0x42; 32],
+					mac: [0x56, 0x78],
+				},
+				ciphertext: &ciphertext,
+			}),
+		);
+	}
+
+	#[test]
+	fn raw_custom_round_trips() {
+		round_trip(
+			RouteType::Direct,
+			&[1],
+			Payload::RawCustom(b"anything at all"),
+		);
+	}
+
+	#[test]
+	fn truncated_packets_fail_to_parse() {
+		assert!(Packet::from_bytes(&[]).is_err());
+		// Claims a 5 byte path but carries 2
+		assert!(Packet::from_bytes(&[0x09, 5, 1, 2]).is_err());
+
+		let mut buffer = [0; 256];
+		let bytes = PacketBuilder::new(RouteType::Direct)
+			.build(&Payload::Ack(Ack { hash: [1, 2, 3, 4] }), &mut buffer)
+			.unwrap();
+		let packet = Packet::from_bytes(&bytes[..bytes.len() - 1]).unwrap();
+		assert!(packet.parse_payload().is_err());
+	}
+
+	#[test]
+	fn oversized_advert_fails_to_sign() {
+		let keys = SigningKeys::from_bytes(&[1; 32]);
+		let mut header = Advert::new(AdvType::Chat, 1_700_000_000).header;
+		let body = [0; 256];
+		assert!(header.fill_key_and_signature(&body[..219], &keys).is_ok());
+		assert!(header.fill_key_and_signature(&body[..220], &keys).is_err());
+	}
+
+	#[test]
+	fn oversized_path_fails_to_build() {
+		let path = [0; MAX_PATH_SIZE + 1];
+		let mut buffer = [0; 512];
+		let built = PacketBuilder::new(RouteType::Direct)
+			.path(&path)
+			.build(&Payload::RawCustom(&[]), &mut buffer);
+		assert!(built.is_err());
+	}
+}
@@ -0,0 +1,294 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::{
+		PACKET_BUFFER_SIZE, SIGNATURE_SIZE,
+		crypto::SigningKeys,
+		packet::{U16, U32, write_bytes},
+	},
+};
+use core::ops::BitOr;
+use ed25519_dalek::{Signature, VerifyingKey};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum AdvType {
+	None = 0b00,
+	Chat = 0b01,
+	Repeater = 0b10,
+	Room = 0b11,
+}
+
+impl TryFrom<u8> for AdvType {
+	type Error = Error;
+
+	fn try_from(value: u8) -> Result<Self> {
+		let adv_type = match value {
+			0b00 => Self::None,
+			0b01 => Self::Chat,
+			0b10 => Self::Repeater,
+			0b11 => Self::Room,
+			_ => return Err(Error::PacketParse),
+		};
+		Ok(adv_type)
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct AdvertFlags(u8);
+
+impl BitOr for AdvertFlags {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self::Output { Self(self.0 | rhs.0) }
+}
+
+impl AdvertFlags {
+	pub const LATLONG: Self = Self(0x10);
+
+	pub const BATTERY: Self = Self(0x20);
+
+	pub const TEMPERATURE: Self = Self(0x40);
+
+	pub const NAME: Self = Self(0x80);
+
+	pub fn from(byte: u8) -> Self { Self(byte) }
+
+	pub fn from_adv_type(ty: AdvType) -> Self { Self(ty as u8) }
+
+	pub fn contains(&self, flags: AdvertFlags) -> bool { self.0 & flags.0 != 0 }
+
+	pub fn adv_type(&self) -> AdvType {
+		match self.0 & 0xf {
+			0b01 => AdvType::Chat,
+			0b10 => AdvType::Repeater,
+			0b11 => AdvType::Room,
+			_ => AdvType::None,
+		}
+	}
+
+	pub fn as_raw(&self) -> u8 { self.0 }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AdvertFlags {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:x}", self.0);
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct AdvertHeader {
+	pub pub_key: [u8; 32],
+	pub timestamp: U32,
+	pub signature: [u8; SIGNATURE_SIZE],
+	pub flags: AdvertFlags,
+}
+
+impl AdvertHeader {
+	pub fn verify_signature(&self, body: &[u8]) -> Result<()> {
+		if 32 + 4 + 1 + body.len() > PACKET_BUFFER_SIZE {
+			return Err(Error::PacketParse);
+		}
+		let mut message_buffer = [0u8; PACKET_BUFFER_SIZE];
+		message_buffer[..32].copy_from_slice(&self.pub_key);
+		message_buffer[32..36].copy_from_slice(self.timestamp.as_bytes());
+		message_buffer[36] = self.flags.as_raw();
+		message_buffer[37..37 + body.len()].copy_from_slice(body);
+		let pub_key = VerifyingKey::from_bytes(&self.pub_key).map_err(|_| Error::CryptoError)?;
+		let signature = Signature::from_bytes(&self.signature);
+		pub_key
+			.verify_strict(&message_buffer[..32 + 4 + 1 + body.len()], &signature)
+			.map_err(|_| Error::CryptoError)
+	}
+
+	pub fn fill_key_and_signature(&mut self, body: &[u8], identity: &SigningKeys) -> Result<()> {
+		if 32 + 4 + 1 + body.len() > PACKET_BUFFER_SIZE {
+			return Err(Error::PacketBuild);
+		}
+		let mut message_buffer = [0u8; PACKET_BUFFER_SIZE];
+		self.pub_key = identity.public_key();
+		message_buffer[..32].copy_from_slice(&identity.public_key());
+		message_buffer[32..36].copy_from_slice(self.timestamp.as_bytes());
+		message_buffer[36] = self.flags.as_raw();
+		message_buffer[37..37 + body.len()].copy_from_slice(body);
+		self.signature = identity.sign_message(&message_buffer[..32 + 4 + 1 + body.len()]);
+		Ok(())
+	}
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AdvertHeader {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(
+			fmt,
+			"AdvertHeader {{ pub_key: {}.., timestamp: {}, signature: {}..., flags: {} }}",
+			self.pub_key[..4],
+			self.timestamp,
+			self.signature[..4],
+			self.flags
+		);
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct LatLong {
+	pub lat: U32,
+	pub long: U32,
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct Battery(pub U16);
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct Temperature(pub U16);
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Advert<'a> {
+	pub header: AdvertHeader,
+	pub lat_long: Option<LatLong>,
+	pub battery: Option<Battery>,
+	pub temperature: Option<Temperature>,
+	pub name: Option<&'a [u8]>,
+}
+
+impl<'a> Advert<'a> {
+	pub fn new(adv_type: AdvType, timestamp: u32) -> Self {
+		let mut header = AdvertHeader::new_zeroed();
+		header.timestamp = U32::from(timestamp);
+		header.flags = AdvertFlags::from_adv_type(adv_type);
+		Self {
+			header,
+			lat_long: None,
+			battery: None,
+			temperature: None,
+			name: None,
+		}
+	}
+
+	pub fn with_lat_long(mut self, lat_long: LatLong) -> Self {
+		self.header.flags = self.header.flags | AdvertFlags::LATLONG;
+		self.lat_long = Some(lat_long);
+		self
+	}
+
+	pub fn with_battery(mut self, battery: Battery) -> Self {
+		self.header.flags = self.header.flags | AdvertFlags::BATTERY;
+		self.battery = Some(battery);
+		self
+	}
+
+	pub fn with_temperature(mut self, temperature: Temperature) -> Self {
+		self.header.flags = self.header.flags | AdvertFlags::TEMPERATURE;
+		self.temperature = Some(temperature);
+		self
+	}
+
+	pub fn with_name(mut self, name: &'a [u8]) -> Self {
+		self.header.flags = self.header.flags | AdvertFlags::NAME;
+		self.name = Some(name);
+		self
+	}
+
+	/// Fills in our public key and signs the header and body
+	pub fn sign(&mut self, identity: &SigningKeys) -> Result<()> {
+		let mut body_buffer = [0u8; PACKET_BUFFER_SIZE];
+		let body_len = self.write_body(&mut body_buffer)?;
+		self.header
+			.fill_key_and_signature(&body_buffer[..body_len], identity)
+	}
+
+	pub fn verify_signature(&self) -> Result<()> {
+		let mut body_buffer = [0u8; PACKET_BUFFER_SIZE];
+		let body_len = self.write_body(&mut body_buffer)?;
+		self.header.verify_signature(&body_buffer[..body_len])
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.header.as_bytes())?;
+		Ok(len + self.write_body(&mut buffer[len..])?)
+	}
+
+	fn write_body(&self, buffer: &mut [u8]) -> Result<usize> {
+		let mut len = 0;
+		if let Some(lat_long) = &self.lat_long {
+			len += write_bytes(&mut buffer[len..], lat_long.as_bytes())?;
+		}
+		if let Some(battery) = &self.battery {
+			len += write_bytes(&mut buffer[len..], battery.as_bytes())?;
+		}
+		if let Some(temperature) = &self.temperature {
+			len += write_bytes(&mut buffer[len..], temperature.as_bytes())?;
+		}
+		if let Some(name) = self.name {
+			len += write_bytes(&mut buffer[len..], name)?;
+		}
+		Ok(len)
+	}
+
+	pub fn from_bytes(payload: &'a [u8]) -> Result<(Self, &'a [u8])> {
+		let (header, mut body) =
+			AdvertHeader::ref_from_prefix(payload).map_err(|_| Error::PacketParse)?;
+
+		let mut lat_long = None;
+		let mut battery = None;
+		let mut temperature = None;
+		let mut name = None;
+
+		let flags = header.flags.clone();
+		if flags.contains(AdvertFlags::LATLONG) {
+			let (x, tail) = LatLong::ref_from_prefix(body).map_err(|_| Error::PacketParse)?;
+			lat_long = Some(x.clone());
+			body = tail;
+		}
+		if flags.contains(AdvertFlags::BATTERY) {
+			let (x, tail) = Battery::ref_from_prefix(body).map_err(|_| Error::PacketParse)?;
+			battery = Some(x.clone());
+			body = tail;
+		}
+		if flags.contains(AdvertFlags::TEMPERATURE) {
+			let (x, tail) = Temperature::ref_from_prefix(body).map_err(|_| Error::PacketParse)?;
+			temperature = Some(x.clone());
+			body = tail;
+		}
+		if flags.contains(AdvertFlags::NAME) {
+			name = Some(body);
+			body = &[];
+		}
+
+		let advert = Self {
+			header: header.clone(),
+			lat_long,
+			battery,
+			temperature,
+			name,
+		};
+
+		Ok((advert, body))
+	}
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Advert<'_> {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(
+			fmt,
+			"Advert {{ header: {}, lat_long: {}, battery: {}, temperature: {}, name: {} }}",
+			self.header,
+			self.lat_long,
+			self.battery,
+			self.temperature,
+			self.name
+				.map(|bytes| str::from_utf8(bytes).unwrap_or("<invalid>"))
+		);
+	}
+}
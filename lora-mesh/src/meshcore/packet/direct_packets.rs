@@ -0,0 +1,69 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::write_bytes,
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct DirectHeader {
+	pub dest_hash: u8,
+	pub src_hash: u8,
+	pub mac: [u8; 2],
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DirectPayload<'a> {
+	pub header: DirectHeader,
+	pub ciphertext: &'a [u8],
+}
+
+impl<'a> DirectPayload<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, ciphertext) =
+			DirectHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			header: header.clone(),
+			ciphertext,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.header.as_bytes())?;
+		Ok(len + write_bytes(&mut buffer[len..], self.ciphertext)?)
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct AnonReqHeader {
+	pub dest_hash: u8,
+	pub pub_key: [u8; 32],
+	pub mac: [u8; 2],
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnonReqPayload<'a> {
+	pub header: AnonReqHeader,
+	pub ciphertext: &'a [u8],
+}
+
+impl<'a> AnonReqPayload<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, ciphertext) =
+			AnonReqHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			header: header.clone(),
+			ciphertext,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.header.as_bytes())?;
+		Ok(len + write_bytes(&mut buffer[len..], self.ciphertext)?)
+	}
+}
@@ -0,0 +1,60 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::{U32, try_split_at, write_bytes},
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Tells applications sharing a channel apart, the values are up to them
+#[derive(Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct DataType(u8);
+
+impl DataType {
+	pub fn as_raw(&self) -> u8 { self.0 }
+}
+
+impl From<u8> for DataType {
+	fn from(byte: u8) -> Self { Self(byte) }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DataType {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:02x}", self.0);
+	}
+}
+
+/// The length is sent, as unlike text, binary data can end in zeros that look like padding
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct GroupDataHeader {
+	pub timestamp: U32,
+	pub data_type: DataType,
+	pub len: u8,
+}
+
+/// Decrypted contents of a `PayloadType::GrpData` packet
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupData<'a> {
+	pub header: GroupDataHeader,
+	pub data: &'a [u8],
+}
+
+impl<'a> GroupData<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, tail) =
+			GroupDataHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		let (data, _padding) = try_split_at(tail, header.len as _).ok_or(Error::PacketParse)?;
+		Ok(Self {
+			header: header.clone(),
+			data,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.header.as_bytes())?;
+		Ok(len + write_bytes(&mut buffer[len..], self.data)?)
+	}
+}
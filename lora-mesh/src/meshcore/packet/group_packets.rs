@@ -0,0 +1,36 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::write_bytes,
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Clone, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct GroupHeader {
+	pub channel_hash: u8,
+	pub mac: [u8; 2],
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupPayload<'a> {
+	pub header: GroupHeader,
+	pub ciphertext: &'a [u8],
+}
+
+impl<'a> GroupPayload<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, ciphertext) =
+			GroupHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			header: header.clone(),
+			ciphertext,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.header.as_bytes())?;
+		Ok(len + write_bytes(&mut buffer[len..], self.ciphertext)?)
+	}
+}
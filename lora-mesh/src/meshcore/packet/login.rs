@@ -0,0 +1,73 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::{U32, write_bytes},
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// Firmware feature level reported to clients that log in, as the reference firmware does
+pub const FIRMWARE_VERSION_LEVEL: u8 = 1;
+
+/// Decrypted contents of a `PayloadType::AnonReq` login
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LoginRequest<'a> {
+	/// The sender's clock, never going backwards between its logins
+	pub timestamp: U32,
+	/// Only sent to room servers, the timestamp of the newest post the sender has seen
+	pub sync_since: Option<U32>,
+	/// With the zero padding stripped
+	pub password: &'a [u8],
+}
+
+impl<'a> LoginRequest<'a> {
+	/// Parses a login to a repeater
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (timestamp, password) = U32::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			timestamp: timestamp.clone(),
+			sync_since: None,
+			password: strip_padding(password),
+		})
+	}
+
+	/// Parses a login to a room server
+	pub fn from_room_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (timestamp, tail) = U32::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		let (sync_since, password) = U32::ref_from_prefix(tail).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			timestamp: timestamp.clone(),
+			sync_since: Some(sync_since.clone()),
+			password: strip_padding(password),
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let mut len = write_bytes(buffer, self.timestamp.as_bytes())?;
+		if let Some(sync_since) = &self.sync_since {
+			len += write_bytes(&mut buffer[len..], sync_since.as_bytes())?;
+		}
+		Ok(len + write_bytes(&mut buffer[len..], self.password)?)
+	}
+}
+
+fn strip_padding(password: &[u8]) -> &[u8] {
+	password.split(|&x| x == 0).next().unwrap_or_default()
+}
+
+/// `LoginResponse::result` of a successful login. A wrong password gets no response at all.
+pub const LOGIN_OK: u8 = 0;
+
+/// Data of the `Response` to a login, whose tag is the server's timestamp
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct LoginResponse {
+	pub result: u8,
+	/// Formerly the keep-alive interval in units of 16 seconds, now always zero
+	pub keep_alive: u8,
+	pub is_admin: u8,
+	pub permissions: u8,
+	/// Keeps otherwise identical responses from being dropped as duplicates
+	pub random: [u8; 4],
+	pub firmware_version: u8,
+}
@@ -0,0 +1,39 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::{PayloadType, try_split_at, write_bytes},
+};
+
+/// Decrypted contents of a `PayloadType::Path` packet: the route a flood packet took to reach
+/// its destination, returned to the sender along with an optional piggybacked payload
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReturnedPath<'a> {
+	pub path: &'a [u8],
+	pub extra_type: u8,
+	pub extra: &'a [u8],
+}
+
+impl<'a> ReturnedPath<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (&path_len, tail) = bytes.split_first().ok_or(Error::PacketParse)?;
+		let (path, tail) = try_split_at(tail, path_len as _).ok_or(Error::PacketParse)?;
+		let (&extra_type, extra) = tail.split_first().ok_or(Error::PacketParse)?;
+		Ok(Self {
+			path,
+			extra_type,
+			extra,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let mut len = write_bytes(buffer, &[self.path.len() as u8])?;
+		len += write_bytes(&mut buffer[len..], self.path)?;
+		len += write_bytes(&mut buffer[len..], &[self.extra_type])?;
+		len += write_bytes(&mut buffer[len..], self.extra)?;
+		Ok(len)
+	}
+
+	pub fn extra_payload_type(&self) -> Result<PayloadType> {
+		PayloadType::try_from(self.extra_type)
+	}
+}
@@ -0,0 +1,113 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::{U32, try_split_at, write_bytes},
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+pub const SIGNER_PREFIX_SIZE: usize = 4;
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PlainMessageHeader {
+	pub timestamp: U32,
+	pub flags: MessageFlags,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum TextType {
+	Plain = 0x0,
+	/// A command for a repeater or room server's CLI, or its reply. These aren't ACKed.
+	CliData = 0x1,
+	/// Text posted by someone else, such as a room server relaying a post, starting with a
+	/// prefix of the author's public key
+	SignedPlain = 0x2,
+}
+
+impl TryFrom<u8> for TextType {
+	type Error = Error;
+
+	fn try_from(value: u8) -> Result<Self> {
+		let text_type = match value {
+			0x0 => Self::Plain,
+			0x1 => Self::CliData,
+			0x2 => Self::SignedPlain,
+			_ => return Err(Error::PacketParse),
+		};
+		Ok(text_type)
+	}
+}
+
+/// Text type in the upper six bits, and the attempt number in the lower two
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(transparent)]
+pub struct MessageFlags(u8);
+
+impl MessageFlags {
+	pub fn new(text_type: TextType, attempt: u8) -> Self {
+		Self((text_type as u8) << 2).with_attempt(attempt)
+	}
+
+	pub fn text_type(&self) -> Result<TextType> { TextType::try_from(self.0 >> 2) }
+
+	pub fn attempt(&self) -> u8 { self.0 & 0b11 }
+
+	pub fn with_attempt(self, attempt: u8) -> Self { Self((self.0 & !0b11) | (attempt & 0b11)) }
+
+	pub fn as_raw(&self) -> u8 { self.0 }
+}
+
+impl From<u8> for MessageFlags {
+	fn from(byte: u8) -> Self { Self(byte) }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for MessageFlags {
+	fn format(&self, fmt: defmt::Formatter) {
+		defmt::write!(fmt, "{:x}", self.0);
+	}
+}
+
+/// Decrypted contents of a text message, with the zero padding cut off `text`
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PlainMessage<'a> {
+	pub header: PlainMessageHeader,
+	pub text_type: TextType,
+	/// Public key prefix of the author of a `TextType::SignedPlain` text
+	pub signer: Option<&'a [u8; SIGNER_PREFIX_SIZE]>,
+	pub text: &'a [u8],
+}
+
+impl<'a> PlainMessage<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (header, mut text) =
+			PlainMessageHeader::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		let text_type = header.flags.text_type()?;
+		let mut signer = None;
+		if text_type == TextType::SignedPlain {
+			let (prefix, tail) =
+				try_split_at(text, SIGNER_PREFIX_SIZE).ok_or(Error::PacketParse)?;
+			signer = Some(prefix.try_into().unwrap());
+			text = tail;
+		}
+		let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+		Ok(Self {
+			header: header.clone(),
+			text_type,
+			signer,
+			text: &text[..end],
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let mut len = write_bytes(buffer, self.header.as_bytes())?;
+		if let Some(signer) = self.signer {
+			len += write_bytes(&mut buffer[len..], signer)?;
+		}
+		len += write_bytes(&mut buffer[len..], self.text)?;
+		Ok(len)
+	}
+}
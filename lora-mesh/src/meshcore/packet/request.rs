@@ -0,0 +1,178 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::packet::{I16, U16, U32, write_bytes},
+};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum RequestType {
+	GetStatus = 0x01,
+	/// Lets a server know the client is still around, answered with an empty response
+	KeepAlive = 0x02,
+	GetTelemetry = 0x03,
+	GetAccessList = 0x05,
+	GetNeighbours = 0x06,
+}
+
+impl TryFrom<u8> for RequestType {
+	type Error = Error;
+
+	fn try_from(value: u8) -> Result<Self> {
+		let request_type = match value {
+			0x01 => Self::GetStatus,
+			0x02 => Self::KeepAlive,
+			0x03 => Self::GetTelemetry,
+			0x05 => Self::GetAccessList,
+			0x06 => Self::GetNeighbours,
+			_ => return Err(Error::PacketParse),
+		};
+		Ok(request_type)
+	}
+}
+
+/// Decrypted contents of a `PayloadType::Req` packet. The tag is the sender's timestamp, unique
+/// per request, and comes back on the response.
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Request<'a> {
+	pub tag: U32,
+	pub request_type: u8,
+	pub data: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (tag, tail) = U32::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		let (&request_type, data) = tail.split_first().ok_or(Error::PacketParse)?;
+		Ok(Self {
+			tag: tag.clone(),
+			request_type,
+			data,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let mut len = write_bytes(buffer, self.tag.as_bytes())?;
+		len += write_bytes(&mut buffer[len..], &[self.request_type])?;
+		len += write_bytes(&mut buffer[len..], self.data)?;
+		Ok(len)
+	}
+
+	pub fn request_type(&self) -> Result<RequestType> { RequestType::try_from(self.request_type) }
+}
+
+/// Decrypted contents of a `PayloadType::Resp` packet, with the zero padding left on `data`
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Response<'a> {
+	pub tag: U32,
+	pub data: &'a [u8],
+}
+
+impl<'a> Response<'a> {
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+		let (tag, data) = U32::ref_from_prefix(bytes).map_err(|_| Error::PacketParse)?;
+		Ok(Self {
+			tag: tag.clone(),
+			data,
+		})
+	}
+
+	pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<usize> {
+		let len = write_bytes(buffer, self.tag.as_bytes())?;
+		Ok(len + write_bytes(&mut buffer[len..], self.data)?)
+	}
+}
+
+/// Reply to `RequestType::GetStatus`, laid out like the repeater stats of the reference firmware
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct StatusResponse {
+	pub battery_mv: U16,
+	pub tx_queue_len: U16,
+	pub noise_floor: I16,
+	pub last_rssi: I16,
+	pub packets_received: U32,
+	pub packets_sent: U32,
+	pub tx_airtime_secs: U32,
+	pub uptime_secs: U32,
+	pub sent_flood: U32,
+	pub sent_direct: U32,
+	pub received_flood: U32,
+	pub received_direct: U32,
+	pub error_events: U16,
+	/// In quarter dB
+	pub last_snr: I16,
+	pub direct_dups: U16,
+	pub flood_dups: U16,
+	pub rx_airtime_secs: U32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NeighbourOrder {
+	NewestFirst = 0,
+	OldestFirst = 1,
+	StrongestFirst = 2,
+	WeakestFirst = 3,
+}
+
+impl TryFrom<u8> for NeighbourOrder {
+	type Error = Error;
+
+	fn try_from(value: u8) -> Result<Self> {
+		let order = match value {
+			0 => Self::NewestFirst,
+			1 => Self::OldestFirst,
+			2 => Self::StrongestFirst,
+			3 => Self::WeakestFirst,
+			_ => return Err(Error::PacketParse),
+		};
+		Ok(order)
+	}
+}
+
+/// Data of a `RequestType::GetNeighbours` request, asking for a page of the neighbours list
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct NeighboursRequest {
+	pub version: u8,
+	pub count: u8,
+	pub offset: U16,
+	pub order: u8,
+	/// How many bytes of each neighbour's public key to send back
+	pub prefix_len: u8,
+	/// Keeps otherwise identical requests from being dropped as duplicates
+	pub random: U32,
+}
+
+impl NeighboursRequest {
+	pub fn order(&self) -> Result<NeighbourOrder> { NeighbourOrder::try_from(self.order) }
+}
+
+/// Start of the reply to `RequestType::GetNeighbours`, followed by `count` entries of a public
+/// key prefix, the seconds since the neighbour was heard as a `U32` and its SNR in quarter dB
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct NeighboursHeader {
+	pub total: U16,
+	pub count: U16,
+}
+
+/// Public key prefix sent for each entry of the access list
+pub const ACCESS_PREFIX_SIZE: usize = 6;
+
+/// Entry of the reply to `RequestType::GetAccessList`
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct AccessEntry {
+	pub prefix: [u8; ACCESS_PREFIX_SIZE],
+	pub permissions: u8,
+}
@@ -0,0 +1,127 @@
+use crate::{
+	error::{Error, Result},
+	meshcore::{
+		MAX_PATH_SIZE,
+		packet::{PACKET_HASH_SIZE, Packet, RouteType},
+	},
+};
+use embassy_time::Duration;
+use rand_core::RngCore;
+
+pub const RECENT_PACKETS_SIZE: usize = 64;
+
+#[derive(Clone)]
+pub struct RepeaterConfig {
+	pub enabled: bool,
+	pub max_path_len: usize,
+}
+
+impl Default for RepeaterConfig {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			max_path_len: MAX_PATH_SIZE,
+		}
+	}
+}
+
+/// Ring buffer of the hashes of recently received packets, used to drop duplicates
+pub struct RecentPackets {
+	hashes: [[u8; PACKET_HASH_SIZE]; RECENT_PACKETS_SIZE],
+	len: usize,
+	next: usize,
+}
+
+impl RecentPackets {
+	pub const fn new() -> Self {
+		Self {
+			hashes: [[0; PACKET_HASH_SIZE]; RECENT_PACKETS_SIZE],
+			len: 0,
+			next: 0,
+		}
+	}
+
+	pub fn contains(&self, hash: &[u8; PACKET_HASH_SIZE]) -> bool {
+		self.hashes[..self.len].contains(hash)
+	}
+
+	pub fn insert(&mut self, hash: [u8; PACKET_HASH_SIZE]) {
+		self.hashes[self.next] = hash;
+		self.next = (self.next + 1) % RECENT_PACKETS_SIZE;
+		self.len = (self.len + 1).min(RECENT_PACKETS_SIZE);
+	}
+
+	/// Records the packet, returning whether it had already been seen
+	pub fn check_and_insert(&mut self, packet: &Packet) -> bool {
+		let hash = packet.hash();
+		if self.contains(&hash) {
+			return true;
+		}
+		self.insert(hash);
+		false
+	}
+}
+
+impl Default for RecentPackets {
+	fn default() -> Self { Self::new() }
+}
+
+pub fn should_forward(config: &RepeaterConfig, packet: &Packet, our_hash: u8) -> bool {
+	config.enabled
+		&& packet.header.flags.route_type() == RouteType::Flood
+		&& packet.path.len() < config.max_path_len.min(MAX_PATH_SIZE)
+		&& !packet.path.contains(&our_hash)
+}
+
+/// Writes a copy of `packet` with our hash appended to its path
+pub fn forward_packet<'b>(packet: &Packet, our_hash: u8, buffer: &'b mut [u8]) -> Result<&'b [u8]> {
+	let path_len = packet.path.len();
+	if path_len >= MAX_PATH_SIZE {
+		return Err(Error::PacketBuild);
+	}
+
+	let mut path = [0u8; MAX_PATH_SIZE];
+	path[..path_len].copy_from_slice(packet.path);
+	path[path_len] = our_hash;
+
+	let mut header = packet.header.clone();
+	header.path_len += 1;
+
+	let forwarded = Packet {
+		header,
+		path: &path[..path_len + 1],
+		payload: packet.payload,
+	};
+	forwarded.to_bytes(buffer)
+}
+
+/// Random delay before retransmitting a flood packet, in slots of half the packet's airtime.
+/// Nodes that heard the packet well wait longer, so those further away extend the flood first.
+pub fn retransmit_delay(snr: i16, airtime: Duration, rng: &mut impl RngCore) -> Duration {
+	let slot = airtime / 2;
+	let snr_slots = (snr.clamp(-10, 20) + 10) as u32 / 3;
+	let random_slots = rng.next_u32() % 5;
+	slot * (snr_slots + random_slots)
+}
+
+/// Direct packets carry the remaining route, and we forward them only when we are the next hop
+pub fn should_forward_direct(config: &RepeaterConfig, packet: &Packet, our_hash: u8) -> bool {
+	config.enabled
+		&& packet.header.flags.route_type() == RouteType::Direct
+		&& packet.path.first() == Some(&our_hash)
+}
+
+/// Writes a copy of `packet` with our hash removed from the front of its path
+pub fn forward_direct_packet<'b>(packet: &Packet, buffer: &'b mut [u8]) -> Result<&'b [u8]> {
+	let (_, path) = packet.path.split_first().ok_or(Error::PacketBuild)?;
+
+	let mut header = packet.header.clone();
+	header.path_len -= 1;
+
+	let forwarded = Packet {
+		header,
+		path,
+		payload: packet.payload,
+	};
+	forwarded.to_bytes(buffer)
+}
@@ -0,0 +1,257 @@
+use crate::{
+	config::Settings,
+	error::{Error, Result},
+	meshcore::{
+		contacts::{Contacts, Permissions},
+		neighbours::Neighbours,
+		packet::{
+			I16, U16, U32,
+			advert::AdvType,
+			login::{FIRMWARE_VERSION_LEVEL, LOGIN_OK, LoginResponse},
+			request::{
+				ACCESS_PREFIX_SIZE, AccessEntry, NeighboursHeader, NeighboursRequest, Request,
+				RequestType, StatusResponse,
+			},
+			write_bytes,
+		},
+		stats::NodeStats,
+	},
+};
+use embassy_time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use zerocopy::{FromBytes, IntoBytes};
+
+pub const MAX_PENDING_REQUESTS: usize = 4;
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// Small enough to still fit when returned along with the longest path in a PATH packet
+pub const MAX_RESPONSE_SIZE: usize = 110;
+
+/// A sent request still waiting for its response
+#[derive(Clone)]
+pub struct PendingRequest {
+	pub id: u32,
+	pub dest: [u8; 32],
+	pub tag: u32,
+	pub deadline: Instant,
+	/// Login responses are tagged with the server's clock rather than ours, so they are matched
+	/// on the sender alone
+	pub login: bool,
+}
+
+pub struct PendingRequests {
+	pending: [Option<PendingRequest>; MAX_PENDING_REQUESTS],
+	last_tag: u32,
+}
+
+impl PendingRequests {
+	pub const fn new() -> Self {
+		Self {
+			pending: [const { None }; MAX_PENDING_REQUESTS],
+			last_tag: 0,
+		}
+	}
+
+	/// Tags are timestamps, bumped past the last one so requests sent within a second differ
+	pub fn next_tag(&mut self, timestamp: u32) -> u32 {
+		self.last_tag = timestamp.max(self.last_tag.wrapping_add(1));
+		self.last_tag
+	}
+
+	pub fn insert(&mut self, request: PendingRequest) -> Result<()> {
+		let slot = self
+			.pending
+			.iter_mut()
+			.find(|slot| slot.is_none())
+			.ok_or(Error::QueueFull)?;
+		*slot = Some(request);
+		Ok(())
+	}
+
+	/// Removes and returns the request that `sender` answered with `tag`, or the login sent to it
+	pub fn take_response(&mut self, sender: &[u8; 32], tag: u32) -> Option<PendingRequest> {
+		self.pending
+			.iter_mut()
+			.find(|slot| {
+				slot.as_ref().is_some_and(|request| {
+					&request.dest == sender && (request.login || request.tag == tag)
+				})
+			})
+			.and_then(Option::take)
+	}
+
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.pending
+			.iter()
+			.flatten()
+			.map(|request| request.deadline)
+			.min()
+	}
+
+	/// Removes and returns a request whose response has timed out
+	pub fn take_expired(&mut self, now: Instant) -> Option<PendingRequest> {
+		self.pending
+			.iter_mut()
+			.find(|slot| slot.as_ref().is_some_and(|request| request.deadline <= now))
+			.and_then(Option::take)
+	}
+}
+
+impl Default for PendingRequests {
+	fn default() -> Self { Self::new() }
+}
+
+/// The permissions a login with `password` grants, if any
+pub fn check_password(settings: &Settings, password: &[u8]) -> Option<Permissions> {
+	// Compared in constant time, so response timing doesn't leak how much of a guess matched
+	let admin = bool::from(password.ct_eq(settings.admin_password()));
+	let guest = bool::from(password.ct_eq(settings.guest_password()));
+	// An unset password disables that login rather than matching an empty one
+	if password.is_empty() {
+		None
+	}
+	else if admin {
+		Some(Permissions::Admin)
+	}
+	else if guest {
+		// Members of a room server may post as well as read
+		match settings.role {
+			AdvType::Room => Some(Permissions::ReadWrite),
+			_ => Some(Permissions::Guest),
+		}
+	}
+	else {
+		None
+	}
+}
+
+/// Writes the response to a successful login into `buffer`, returning its length
+pub fn login_response(
+	permissions: Permissions,
+	timestamp: u32,
+	random: u32,
+	buffer: &mut [u8],
+) -> Result<usize> {
+	let response = LoginResponse {
+		result: LOGIN_OK,
+		keep_alive: 0,
+		is_admin: (permissions == Permissions::Admin) as u8,
+		permissions: permissions as u8,
+		random: random.to_le_bytes(),
+		firmware_version: FIRMWARE_VERSION_LEVEL,
+	};
+	let len = write_bytes(buffer, U32::from(timestamp).as_bytes())?;
+	Ok(len + write_bytes(&mut buffer[len..], response.as_bytes())?)
+}
+
+/// The state requests are answered from
+pub struct RequestContext<'a> {
+	/// What the requester logged in as
+	pub permissions: Permissions,
+	pub contacts: &'a Contacts,
+	pub stats: &'a NodeStats,
+	pub neighbours: &'a mut Neighbours,
+	pub tx_queue_len: usize,
+	pub now: Instant,
+}
+
+/// Writes the response to `request` into `buffer`, returning its length.
+/// Fails for requests we don't answer or the requester may not make, which get no response at all.
+pub fn respond(request: &Request, context: RequestContext, buffer: &mut [u8]) -> Result<usize> {
+	let buffer = buffer
+		.get_mut(..MAX_RESPONSE_SIZE)
+		.ok_or(Error::PacketBuild)?;
+	let len = write_bytes(buffer, request.tag.as_bytes())?;
+	let body = &mut buffer[len..];
+	let body_len = match request.request_type()? {
+		RequestType::GetStatus => write_bytes(body, status(&context).as_bytes())?,
+		RequestType::KeepAlive => 0,
+		// Cayenne LPP readings, of which we have none yet
+		RequestType::GetTelemetry => 0,
+		RequestType::GetAccessList => {
+			if context.permissions != Permissions::Admin {
+				return Err(Error::PermissionDenied);
+			}
+			write_access_list(context.contacts, body)
+		}
+		RequestType::GetNeighbours => write_neighbours(request.data, context, body)?,
+	};
+	Ok(len + body_len)
+}
+
+fn status(context: &RequestContext) -> StatusResponse {
+	let stats = context.stats;
+	StatusResponse {
+		battery_mv: U16::from(0),
+		tx_queue_len: U16::from(context.tx_queue_len as u16),
+		noise_floor: I16::from(0),
+		last_rssi: I16::from(stats.last_rssi),
+		packets_received: U32::from(stats.packets_received),
+		packets_sent: U32::from(stats.packets_sent),
+		tx_airtime_secs: U32::from(stats.tx_airtime.as_secs() as u32),
+		uptime_secs: U32::from(context.now.as_secs() as u32),
+		sent_flood: U32::from(stats.sent_flood),
+		sent_direct: U32::from(stats.sent_direct),
+		received_flood: U32::from(stats.received_flood),
+		received_direct: U32::from(stats.received_direct),
+		error_events: U16::from(0),
+		last_snr: I16::from(stats.last_snr.saturating_mul(4)),
+		direct_dups: U16::from(stats.direct_dups as u16),
+		flood_dups: U16::from(stats.flood_dups as u16),
+		rx_airtime_secs: U32::from(stats.rx_airtime.as_secs() as u32),
+	}
+}
+
+/// Writes the contacts that logged in with more than guest access, as many as fit in `buffer`
+fn write_access_list(contacts: &Contacts, buffer: &mut [u8]) -> usize {
+	let entries = contacts.iter().filter_map(|contact| {
+		contact
+			.permissions
+			.filter(|&permissions| permissions > Permissions::Guest)
+			.map(|permissions| AccessEntry {
+				prefix: contact.pub_key()[..ACCESS_PREFIX_SIZE].try_into().unwrap(),
+				permissions: permissions as u8,
+			})
+	});
+	let mut len = 0;
+	for entry in entries {
+		let Some(slot) = buffer.get_mut(len..len + size_of::<AccessEntry>())
+		else {
+			break;
+		};
+		slot.copy_from_slice(entry.as_bytes());
+		len += slot.len();
+	}
+	len
+}
+
+/// Writes the requested page of neighbours, as many as fit in `buffer`
+fn write_neighbours(data: &[u8], context: RequestContext, buffer: &mut [u8]) -> Result<usize> {
+	let (request, _) = NeighboursRequest::ref_from_prefix(data).map_err(|_| Error::PacketParse)?;
+	let neighbours = context.neighbours;
+	neighbours.sort(request.order()?);
+	let prefix_len = (request.prefix_len as usize).min(32);
+
+	let (header, entries) =
+		NeighboursHeader::mut_from_prefix(buffer).map_err(|_| Error::PacketBuild)?;
+	let mut len = 0;
+	let mut count = 0;
+	for neighbour in neighbours
+		.iter()
+		.skip(request.offset.0.get() as usize)
+		.take(request.count as usize)
+	{
+		let heard_secs = (context.now - neighbour.heard).as_secs() as u32;
+		let Some(entry) = entries.get_mut(len..len + prefix_len + 5)
+		else {
+			break;
+		};
+		entry[..prefix_len].copy_from_slice(&neighbour.pub_key[..prefix_len]);
+		entry[prefix_len..prefix_len + 4].copy_from_slice(&heard_secs.to_le_bytes());
+		entry[prefix_len + 4] = neighbour.snr as u8;
+		len += entry.len();
+		count += 1;
+	}
+	header.total = U16::from(neighbours.len() as u16);
+	header.count = U16::from(count);
+	Ok(size_of::<NeighboursHeader>() + len)
+}
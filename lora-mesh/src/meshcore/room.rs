@@ -0,0 +1,155 @@
+use crate::meshcore::{client::Text, contacts::Contacts};
+use embassy_time::{Duration, Instant};
+
+pub const MAX_POSTS: usize = 32;
+/// Members whose pushes go unACKed this many times in a row are skipped until they log in or
+/// send a keep-alive again
+pub const MAX_PUSH_FAILURES: u8 = 3;
+pub const PUSH_ACK_TIMEOUT: Duration = Duration::from_secs(12);
+
+/// Text posted to the room by a member
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Post {
+	pub author: [u8; 32],
+	/// The author's clock when it wrote the post, telling retries apart from new posts
+	pub author_timestamp: u32,
+	/// Our clock when the post arrived, unique across posts so members can sync from it
+	pub timestamp: u32,
+	pub text: Text,
+}
+
+/// A post sent to a member, waiting for its ACK
+pub struct Push {
+	pub member: [u8; 32],
+	pub timestamp: u32,
+	pub ack_hash: [u8; 4],
+	pub deadline: Instant,
+}
+
+/// Bounded history of posts, pushed one at a time to the members that haven't seen them.
+/// Members are the contacts that logged in, each synced up to its `sync_since`.
+pub struct Room {
+	posts: [Option<Post>; MAX_POSTS],
+	next_slot: usize,
+	last_timestamp: u32,
+	push: Option<Push>,
+	next_push: Option<Instant>,
+	next_member: usize,
+}
+
+impl Room {
+	pub const fn new() -> Self {
+		Self {
+			posts: [const { None }; MAX_POSTS],
+			next_slot: 0,
+			last_timestamp: 0,
+			push: None,
+			next_push: None,
+			next_member: 0,
+		}
+	}
+
+	/// Stores a post, replacing the oldest once full, and returns its timestamp.
+	/// A retry of a post we already hold isn't stored again.
+	pub fn add_post(
+		&mut self,
+		author: &[u8; 32],
+		author_timestamp: u32,
+		timestamp: u32,
+		text: Text,
+		now: Instant,
+	) -> Option<u32> {
+		if self
+			.posts
+			.iter()
+			.flatten()
+			.any(|post| &post.author == author && post.author_timestamp == author_timestamp)
+		{
+			return None;
+		}
+		self.last_timestamp = timestamp.max(self.last_timestamp + 1);
+		self.posts[self.next_slot] = Some(Post {
+			author: *author,
+			author_timestamp,
+			timestamp: self.last_timestamp,
+			text,
+		});
+		self.next_slot = (self.next_slot + 1) % MAX_POSTS;
+		self.wake(now);
+		Some(self.last_timestamp)
+	}
+
+	/// Looks for posts to push at `now`, as something changed that may call for one
+	pub fn wake(&mut self, now: Instant) {
+		if self.push.is_none() {
+			self.next_push = Some(now);
+		}
+	}
+
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.push
+			.as_ref()
+			.map(|push| push.deadline)
+			.or(self.next_push)
+	}
+
+	/// Whether it's time to pick the next post to push
+	pub fn push_due(&self, now: Instant) -> bool {
+		self.push.is_none() && self.next_push.is_some_and(|next_push| next_push <= now)
+	}
+
+	/// Picks the next member, round robin, with a post it hasn't seen, along with the oldest such
+	/// post. Goes idle until woken again when nobody is behind.
+	pub fn select_push(&mut self, contacts: &Contacts) -> Option<([u8; 32], Post)> {
+		let count = contacts.iter().count();
+		for offset in 0..count {
+			let index = (self.next_member + offset) % count;
+			let Some(member) = contacts.iter().nth(index)
+			else {
+				continue;
+			};
+			if member.permissions.is_none() || member.push_failures.get() >= MAX_PUSH_FAILURES {
+				continue;
+			}
+			let unseen = self
+				.posts
+				.iter()
+				.flatten()
+				.filter(|post| {
+					post.timestamp > member.sync_since && &post.author != member.pub_key()
+				})
+				.min_by_key(|post| post.timestamp);
+			if let Some(post) = unseen {
+				self.next_member = index + 1;
+				return Some((*member.pub_key(), post.clone()));
+			}
+		}
+		self.next_push = None;
+		None
+	}
+
+	/// Records the push just sent, holding back further pushes until it is ACKed or times out
+	pub fn start_push(&mut self, push: Push) {
+		self.push = Some(push);
+		self.next_push = None;
+	}
+
+	/// Removes and returns the push in flight if `hash` ACKs it
+	pub fn acknowledge(&mut self, hash: &[u8; 4], now: Instant) -> Option<Push> {
+		let push = self.push.take_if(|push| &push.ack_hash == hash)?;
+		self.wake(now);
+		Some(push)
+	}
+
+	/// Removes and returns the push in flight once its ACK is overdue
+	pub fn take_expired(&mut self, now: Instant) -> Option<Push> {
+		let push = self.push.take_if(|push| push.deadline <= now)?;
+		self.wake(now);
+		Some(push)
+	}
+}
+
+impl Default for Room {
+	fn default() -> Self { Self::new() }
+}
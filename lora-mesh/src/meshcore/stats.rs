@@ -0,0 +1,88 @@
+use crate::{
+	airtime::AirtimeParams,
+	error::Result,
+	meshcore::packet::{PacketFlags, RouteType},
+	radio::{MeshRadio, Modulation},
+};
+use embassy_time::Duration;
+use lora_phy::mod_params::PacketStatus;
+
+/// Traffic counters reported to anyone asking for our status
+#[derive(Clone, Default)]
+pub struct NodeStats {
+	pub packets_received: u32,
+	pub packets_sent: u32,
+	pub received_flood: u32,
+	pub received_direct: u32,
+	pub sent_flood: u32,
+	pub sent_direct: u32,
+	pub flood_dups: u32,
+	pub direct_dups: u32,
+	pub last_rssi: i16,
+	pub last_snr: i16,
+	pub tx_airtime: Duration,
+	pub rx_airtime: Duration,
+}
+
+/// Counts the MeshCore frames passing through a radio
+pub struct StatsRadio<M> {
+	radio: M,
+	airtime: Option<AirtimeParams>,
+	pub stats: NodeStats,
+}
+
+impl<M: MeshRadio> StatsRadio<M> {
+	pub fn new(radio: M) -> Self {
+		Self {
+			radio,
+			airtime: None,
+			stats: NodeStats::default(),
+		}
+	}
+
+	fn time_on_air(&self, len: usize) -> Duration {
+		self.airtime
+			.as_ref()
+			.map_or(Duration::from_ticks(0), |airtime| airtime.time_on_air(len))
+	}
+}
+
+fn route_type(frame: &[u8]) -> Option<RouteType> {
+	frame.first().map(|&flags| PacketFlags(flags).route_type())
+}
+
+impl<M: MeshRadio> MeshRadio for StatsRadio<M> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.radio.set_modulation(modulation).await?;
+		self.airtime = Some(modulation.airtime());
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		self.radio.tx(frame).await?;
+		self.stats.packets_sent += 1;
+		match route_type(frame) {
+			Some(RouteType::Flood) => self.stats.sent_flood += 1,
+			Some(RouteType::Direct) => self.stats.sent_direct += 1,
+			_ => (),
+		}
+		self.stats.tx_airtime += self.time_on_air(frame.len());
+		Ok(())
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let (len, status) = self.radio.rx(buffer).await?;
+		self.stats.packets_received += 1;
+		match route_type(&buffer[..len]) {
+			Some(RouteType::Flood) => self.stats.received_flood += 1,
+			Some(RouteType::Direct) => self.stats.received_direct += 1,
+			_ => (),
+		}
+		self.stats.last_rssi = status.rssi;
+		self.stats.last_snr = status.snr;
+		self.stats.rx_airtime += self.time_on_air(len);
+		Ok((len, status))
+	}
+
+	async fn cad(&mut self) -> Result<bool> { self.radio.cad().await }
+}
@@ -15,3 +15,20 @@ pub fn crypt_data_128(data: &mut [u8], key: [u8; 16], nonce: [u8; 16]) {
 	let mut aes = Ctr32BE::<Aes128>::new(&key.into(), &nonce.into());
 	aes.apply_keystream(data);
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::meshtastic::LONGFAST_KEY;
+
+	#[test]
+	fn crypt_round_trips() {
+		let plain = *b"Hello world! and a bit more than a block";
+		let nonce = generate_nonce(0x01020304, 0x0a0b0c0d);
+		let mut data = plain;
+		crypt_data_128(&mut data, LONGFAST_KEY, nonce);
+		assert_ne!(data, plain);
+		crypt_data_128(&mut data, LONGFAST_KEY, nonce);
+		assert_eq!(data, plain);
+	}
+}
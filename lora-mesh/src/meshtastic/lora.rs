@@ -1,49 +1,22 @@
 use crate::{
 	error::{Error, Result},
 	meshtastic::{
-		LONGFAST_KEY, PACKET_BUFFER_SIZE,
+		LONGFAST_KEY, LONGFAST_MODULATION, PACKET_BUFFER_SIZE,
 		crypto::{crypt_data_128, generate_nonce},
 		packet::{Flags, NodeID, PacketHeader},
 	},
 	protobuf::{Data, PortNum},
+	radio::MeshRadio,
 };
-use defmt::*;
 use embassy_time::Timer;
 use femtopb::{EnumValue, Message, UnknownFields};
-use lora_phy::{
-	DelayNs, LoRa, RxMode,
-	mod_params::{Bandwidth, CodingRate, ModulationParams, SpreadingFactor},
-	mod_traits::RadioKind,
-};
+use lora_phy::mod_params::PacketStatus;
 use rand_core::RngCore;
 use zerocopy::FromBytes;
 
-async fn rx_packet<'a, RK: RadioKind, DLY: DelayNs>(
-	lora: &mut LoRa<RK, DLY>,
-	mod_params: &ModulationParams,
-	buffer: &'a mut [u8; PACKET_BUFFER_SIZE as usize],
-	timeout: u16,
-) -> Result<(PacketHeader, Data<'a>)> {
-	let rx_pkt_params = lora
-		.create_rx_packet_params(16, false, buffer.len() as u8, true, false, mod_params)
-		.map_err(Error::RadioError)?;
-
-	lora.prepare_for_rx(RxMode::Single(timeout), mod_params, &rx_pkt_params)
-		.await
-		.map_err(Error::RadioError)?;
-
-	info!("Ready for rx");
-
-	let (received_len, _packet_status) = lora
-		.rx(&rx_pkt_params, buffer)
-		.await
-		.map_err(Error::RadioError)?;
-
-	info!("Rx complete");
-
-	let received_len = received_len as usize;
-	let (header, body) =
-		PacketHeader::mut_from_prefix(&mut buffer[..received_len]).map_err(|_| Error::ZeroCopy)?;
+/// Decrypts a received frame in place and decodes the header and data inside
+pub fn decode_packet(frame: &mut [u8]) -> Result<(PacketHeader, Data<'_>)> {
+	let (header, body) = PacketHeader::mut_from_prefix(frame).map_err(|_| Error::PacketParse)?;
 
 	let nonce = generate_nonce(header.packet_id, header.sender.id());
 	crypt_data_128(body, LONGFAST_KEY, nonce);
@@ -55,13 +28,21 @@ async fn rx_packet<'a, RK: RadioKind, DLY: DelayNs>(
 	Ok((header.clone(), data))
 }
 
-async fn tx_packet<'a, RK: RadioKind, DLY: DelayNs>(
-	lora: &mut LoRa<RK, DLY>,
-	mod_params: &ModulationParams,
+async fn rx_packet<'a, M: MeshRadio>(
+	radio: &mut M,
 	buffer: &'a mut [u8; PACKET_BUFFER_SIZE as usize],
+) -> Result<(PacketHeader, Data<'a>, PacketStatus)> {
+	let (received_len, packet_status) = radio.rx(buffer).await?;
+	let (header, data) = decode_packet(&mut buffer[..received_len])?;
+	Ok((header, data, packet_status))
+}
+
+/// Encodes and encrypts `data` behind `header`, returning the frame to transmit
+pub fn encode_packet<'b>(
+	buffer: &'b mut [u8; PACKET_BUFFER_SIZE as usize],
 	header: PacketHeader,
-	data: &Data<'a>,
-) -> Result<()> {
+	data: &Data<'_>,
+) -> Result<&'b [u8]> {
 	let (packet_header, body_buffer) =
 		PacketHeader::mut_from_prefix(&mut *buffer).map_err(|_| Error::ZeroCopy)?;
 	*packet_header = header;
@@ -74,48 +55,35 @@ async fn tx_packet<'a, RK: RadioKind, DLY: DelayNs>(
 	let nonce = generate_nonce(packet_header.packet_id, packet_header.sender.id());
 	crypt_data_128(body, LONGFAST_KEY, nonce);
 
-	let full_packet = &mut buffer[..PACKET_BUFFER_SIZE as usize - remaining_len];
-
-	let mut tx_pkt_params = lora
-		.create_tx_packet_params(16, false, true, false, mod_params)
-		.map_err(Error::RadioError)?;
-
-	lora.prepare_for_tx(mod_params, &mut tx_pkt_params, 20, full_packet)
-		.await
-		.map_err(Error::RadioError)?;
-
-	info!("Ready for tx");
-
-	lora.tx().await.map_err(Error::RadioError)?;
-
-	info!("Tx complete");
+	Ok(&buffer[..PACKET_BUFFER_SIZE as usize - remaining_len])
+}
 
-	Ok(())
+async fn tx_packet<M: MeshRadio>(
+	radio: &mut M,
+	buffer: &mut [u8; PACKET_BUFFER_SIZE as usize],
+	header: PacketHeader,
+	data: &Data<'_>,
+) -> Result<()> {
+	let full_packet = encode_packet(buffer, header, data)?;
+	radio.tx(full_packet).await
 }
 
-pub async fn lora_loop<RK: RadioKind, DLY: DelayNs, R: RngCore>(
-	mut lora: LoRa<RK, DLY>,
-	mut rng: R,
-) -> ! {
-	let mod_params = lora
-		.create_modulation_params(
-			SpreadingFactor::_11,
-			Bandwidth::_250KHz,
-			CodingRate::_4_7,
-			906_875_000,
-		)
-		.unwrap();
+pub async fn lora_loop<M: MeshRadio, R: RngCore>(mut radio: M, mut rng: R) -> ! {
+	if radio.set_modulation(&LONGFAST_MODULATION).await.is_err() {
+		error!("Failed to set modulation");
+	}
 
 	loop {
 		let mut packet_buffer: [u8; PACKET_BUFFER_SIZE as usize] = [0; PACKET_BUFFER_SIZE as usize];
 
-		let Ok((mut header, data)) = rx_packet(&mut lora, &mod_params, &mut packet_buffer, 0).await
+		let Ok((mut header, data, packet_status)) = rx_packet(&mut radio, &mut packet_buffer).await
 		else {
 			info!("Invalid message");
 			continue;
 		};
 
 		info!("Header: {:02x}", header);
+		info!("RSSI: {}, SNR: {}", packet_status.rssi, packet_status.snr);
 		info!(
 			"Hop limit: {}, hop start: {}",
 			header.flags.get_hop_limit(),
@@ -130,9 +98,12 @@ pub async fn lora_loop<RK: RadioKind, DLY: DelayNs, R: RngCore>(
 			let mut packet_buffer_2: [u8; PACKET_BUFFER_SIZE as usize] =
 				[0; PACKET_BUFFER_SIZE as usize];
 			header.relay_node -= 1;
-			tx_packet(&mut lora, &mod_params, &mut packet_buffer_2, header, &data)
+			if tx_packet(&mut radio, &mut packet_buffer_2, header, &data)
 				.await
-				.unwrap();
+				.is_err()
+			{
+				warn!("Failed to rebroadcast");
+			}
 
 			Timer::after_millis(10).await;
 		}
@@ -161,8 +132,11 @@ pub async fn lora_loop<RK: RadioKind, DLY: DelayNs, R: RngCore>(
 			unknown_fields: UnknownFields::empty(),
 		};
 
-		tx_packet(&mut lora, &mod_params, &mut packet_buffer, msg_header, &msg)
+		if tx_packet(&mut radio, &mut packet_buffer, msg_header, &msg)
 			.await
-			.unwrap();
+			.is_err()
+		{
+			warn!("Failed to send message");
+		}
 	}
 }
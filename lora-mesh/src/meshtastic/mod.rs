@@ -2,9 +2,21 @@ pub mod crypto;
 pub mod lora;
 pub mod packet;
 
+use crate::radio::Modulation;
+
 pub const PACKET_BUFFER_SIZE: u8 = 252;
 pub const MESHTASTIC_SYNCWORD: u8 = 0x2b;
 
 pub const LONGFAST_KEY: [u8; 16] = [
 	0xd4, 0xf1, 0xbb, 0x3a, 0x20, 0x29, 0x07, 0x59, 0xf0, 0xbc, 0xff, 0xab, 0xcf, 0x4e, 0x69, 0x01,
 ];
+
+pub const LONGFAST_MODULATION: Modulation = Modulation {
+	frequency: 906_875_000,
+	spreading_factor: 11,
+	bandwidth_hz: 250_000,
+	coding_rate: 7,
+	preamble_len: 16,
+	crc: true,
+	tx_power: 20,
+};
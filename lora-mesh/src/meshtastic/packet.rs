@@ -0,0 +1,139 @@
+use core::ops::BitOr;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct NodeID(u32);
+
+impl NodeID {
+	pub const BROADCAST: Self = Self(0xffffffff);
+
+	pub const fn id(&self) -> u32 { self.0 }
+
+	pub const fn from_id(id: u32) -> Self { Self(id) }
+}
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct Flags(u8);
+
+impl BitOr for Flags {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self::Output { Self(self.0 | rhs.0) }
+}
+
+impl Flags {
+	pub fn hop_limit(limit: u8) -> Self {
+		debug_assert!(limit < 8);
+		Self(limit & 0b111)
+	}
+
+	pub fn get_hop_limit(&self) -> u8 { self.0 & 0b111 }
+
+	pub fn want_ack(ack: bool) -> Self { Self((ack as u8) << 3) }
+
+	pub fn get_want_ack(&self) -> bool { (self.0 >> 3) & 1 != 0 }
+
+	pub fn via_mqtt(mqtt: bool) -> Self { Self((mqtt as u8) << 4) }
+
+	pub fn get_via_mqtt(&self) -> bool { (self.0 >> 4) & 1 != 0 }
+
+	pub fn hop_start(start: u8) -> Self {
+		debug_assert!(start < 8);
+		Self((start & 0b111) << 5)
+	}
+
+	pub fn get_hop_start(&self) -> u8 { (self.0 >> 5) & 0b111 }
+}
+
+#[derive(Clone, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PacketHeader {
+	pub dest: NodeID,
+	pub sender: NodeID,
+	pub packet_id: u32,
+	pub flags: Flags,
+	pub channel_hash: u8,
+	pub next_hop: u8,
+	pub relay_node: u8,
+}
+
+impl PacketHeader {
+	pub const SIZE: usize = size_of::<Self>();
+}
+
+// pub struct Payload {
+// 	len: u8,
+// 	bytes: [u8; PACKET_BUFFER_SIZE as usize],
+// }
+
+// impl From<&[u8]> for Payload {
+// 	fn from(value: &[u8]) -> Self {
+// 		debug_assert!(value.len() < PACKET_BUFFER_SIZE as usize);
+// 		let mut bytes = [0u8; PACKET_BUFFER_SIZE as usize];
+// 		bytes[..value.len()].copy_from_slice(value);
+// 		Self {
+// 			len: value.len() as _,
+// 			bytes,
+// 		}
+// 	}
+// }
+
+// pub struct Packet {
+// 	pub header: PacketHeader,
+// 	pub portnum: PortNum,
+// 	pub payload: Payload,
+// 	pub want_response: bool,
+// 	pub dest: NodeID,
+// 	pub source: NodeID,
+// 	pub request_id: u32,
+// 	pub reply_id: u32,
+// 	pub emoji: u32,
+// }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn flags_pack_into_their_bits() {
+		// As in the reference firmware's PACKET_FLAGS_* masks
+		let flags = Flags::hop_limit(3) | Flags::want_ack(true) | Flags::hop_start(5);
+		assert_eq!(flags.as_bytes(), [0b1010_1011]);
+		assert_eq!(flags.get_hop_limit(), 3);
+		assert!(flags.get_want_ack());
+		assert!(!flags.get_via_mqtt());
+		assert_eq!(flags.get_hop_start(), 5);
+		assert_eq!(Flags::via_mqtt(true).as_bytes(), [0x10]);
+	}
+
+	#[test]
+	fn header_is_little_endian() {
+		let header = PacketHeader {
+			dest: NodeID::BROADCAST,
+			sender: NodeID::from_id(0x12345678),
+			packet_id: 0xaabbccdd,
+			flags: Flags::hop_limit(3),
+			channel_hash: 0x08,
+			next_hop: 0,
+			relay_node: 0x78,
+		};
+		assert_eq!(PacketHeader::SIZE, 16);
+		let bytes = header.as_bytes();
+		assert_eq!(
+			bytes,
+			[
+				0xff, 0xff, 0xff, 0xff, 0x78, 0x56, 0x34, 0x12, 0xdd, 0xcc, 0xbb, 0xaa, 0x03, 0x08,
+				0x00, 0x78,
+			]
+		);
+
+		let parsed = PacketHeader::ref_from_bytes(bytes).unwrap();
+		assert_eq!(parsed.sender.id(), 0x12345678);
+		assert_eq!(parsed.packet_id, 0xaabbccdd);
+	}
+}
@@ -0,0 +1,213 @@
+pub mod replay;
+pub mod sim;
+
+use crate::{
+	airtime::AirtimeParams,
+	error::{Error, Result},
+};
+use lora_phy::{
+	DelayNs, LoRa, RxMode,
+	mod_params::{Bandwidth, CodingRate, ModulationParams, PacketStatus, SpreadingFactor},
+	mod_traits::RadioKind,
+};
+
+/// Everything needed to put a radio on the same channel as its peers
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Modulation {
+	pub frequency: u32,
+	pub spreading_factor: u8,
+	pub bandwidth_hz: u32,
+	/// Denominator of the 4/x coding rate
+	pub coding_rate: u8,
+	pub preamble_len: u16,
+	pub crc: bool,
+	/// Transmit power in dBm
+	pub tx_power: i32,
+}
+
+impl Modulation {
+	/// Converts to the radio driver's parameters, failing on values the SX126x lacks
+	pub fn lora_params(&self) -> Result<(SpreadingFactor, Bandwidth, CodingRate)> {
+		let spreading_factor = match self.spreading_factor {
+			5 => SpreadingFactor::_5,
+			6 => SpreadingFactor::_6,
+			7 => SpreadingFactor::_7,
+			8 => SpreadingFactor::_8,
+			9 => SpreadingFactor::_9,
+			10 => SpreadingFactor::_10,
+			11 => SpreadingFactor::_11,
+			12 => SpreadingFactor::_12,
+			_ => return Err(Error::InvalidConfig),
+		};
+		let bandwidth = match self.bandwidth_hz {
+			7_810 => Bandwidth::_7KHz,
+			10_420 => Bandwidth::_10KHz,
+			15_630 => Bandwidth::_15KHz,
+			20_830 => Bandwidth::_20KHz,
+			31_250 => Bandwidth::_31KHz,
+			41_670 => Bandwidth::_41KHz,
+			62_500 => Bandwidth::_62KHz,
+			125_000 => Bandwidth::_125KHz,
+			250_000 => Bandwidth::_250KHz,
+			500_000 => Bandwidth::_500KHz,
+			_ => return Err(Error::InvalidConfig),
+		};
+		let coding_rate = match self.coding_rate {
+			5 => CodingRate::_4_5,
+			6 => CodingRate::_4_6,
+			7 => CodingRate::_4_7,
+			8 => CodingRate::_4_8,
+			_ => return Err(Error::InvalidConfig),
+		};
+		Ok((spreading_factor, bandwidth, coding_rate))
+	}
+
+	pub fn airtime(&self) -> AirtimeParams {
+		AirtimeParams {
+			spreading_factor: self.spreading_factor,
+			bandwidth_hz: self.bandwidth_hz,
+			coding_rate: self.coding_rate,
+			preamble_len: self.preamble_len,
+			explicit_header: true,
+			crc: self.crc,
+		}
+	}
+
+	/// Whether a receiver using `self` can demodulate a transmission using `other`
+	pub fn hears(&self, other: &Modulation) -> bool {
+		self.frequency == other.frequency
+			&& self.spreading_factor == other.spreading_factor
+			&& self.bandwidth_hz == other.bandwidth_hz
+	}
+}
+
+/// The operations the protocol loops need from a LoRa transceiver
+// Futures are only ever polled by single threaded executors, so they don't need to be `Send`
+#[allow(async_fn_in_trait)]
+pub trait MeshRadio {
+	/// Applies `modulation` to all later transfers
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()>;
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()>;
+
+	/// Waits for a frame, returning its length and signal quality
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)>;
+
+	/// Channel activity detection, true if a LoRa preamble is currently on air
+	async fn cad(&mut self) -> Result<bool>;
+}
+
+/// A real transceiver driven through lora-phy
+pub struct PhyRadio<RK, DLY> {
+	lora: LoRa<RK, DLY>,
+	modulation: Modulation,
+	mod_params: ModulationParams,
+}
+
+impl<RK: RadioKind, DLY: DelayNs> PhyRadio<RK, DLY> {
+	pub fn new(mut lora: LoRa<RK, DLY>, modulation: &Modulation) -> Result<Self> {
+		let mod_params = create_modulation_params(&mut lora, modulation)?;
+		Ok(Self {
+			lora,
+			modulation: *modulation,
+			mod_params,
+		})
+	}
+}
+
+fn create_modulation_params<RK: RadioKind, DLY: DelayNs>(
+	lora: &mut LoRa<RK, DLY>,
+	modulation: &Modulation,
+) -> Result<ModulationParams> {
+	let (spreading_factor, bandwidth, coding_rate) = modulation.lora_params()?;
+	lora.create_modulation_params(
+		spreading_factor,
+		bandwidth,
+		coding_rate,
+		modulation.frequency,
+	)
+	.map_err(Error::RadioError)
+}
+
+impl<RK: RadioKind, DLY: DelayNs> MeshRadio for PhyRadio<RK, DLY> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.mod_params = create_modulation_params(&mut self.lora, modulation)?;
+		self.modulation = *modulation;
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		let mut tx_pkt_params = self
+			.lora
+			.create_tx_packet_params(
+				self.modulation.preamble_len,
+				false,
+				self.modulation.crc,
+				false,
+				&self.mod_params,
+			)
+			.map_err(Error::RadioError)?;
+
+		self.lora
+			.prepare_for_tx(
+				&self.mod_params,
+				&mut tx_pkt_params,
+				self.modulation.tx_power,
+				frame,
+			)
+			.await
+			.map_err(Error::RadioError)?;
+
+		info!("Ready for tx");
+
+		self.lora.tx().await.map_err(Error::RadioError)?;
+
+		info!("Tx complete");
+
+		Ok(())
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let max_len = buffer.len().min(u8::MAX as usize) as u8;
+		let rx_pkt_params = self
+			.lora
+			.create_rx_packet_params(
+				self.modulation.preamble_len,
+				false,
+				max_len,
+				self.modulation.crc,
+				false,
+				&self.mod_params,
+			)
+			.map_err(Error::RadioError)?;
+
+		self.lora
+			.prepare_for_rx(RxMode::Single(0), &self.mod_params, &rx_pkt_params)
+			.await
+			.map_err(Error::RadioError)?;
+
+		info!("Ready for rx");
+
+		let (received_len, packet_status) = self
+			.lora
+			.rx(&rx_pkt_params, buffer)
+			.await
+			.map_err(Error::RadioError)?;
+
+		info!("Rx complete");
+
+		Ok((received_len as usize, packet_status))
+	}
+
+	async fn cad(&mut self) -> Result<bool> {
+		self.lora
+			.prepare_for_cad(&self.mod_params)
+			.await
+			.map_err(Error::RadioError)?;
+		self.lora
+			.cad(&self.mod_params)
+			.await
+			.map_err(Error::RadioError)
+	}
+}
@@ -0,0 +1,98 @@
+use crate::{
+	capture::{CaptureSink, Direction, MAX_RECORD_SIZE, Record, Records, write_record},
+	error::Result,
+	radio::{MeshRadio, Modulation},
+};
+use embassy_time::{Duration, Instant, Timer};
+use lora_phy::mod_params::PacketStatus;
+
+/// Feeds the received frames of a capture to a protocol loop as if they came off the air,
+/// keeping their original spacing. Frames the loop transmits go to `sent` as capture records.
+///
+/// Once the capture runs out, `rx` waits forever.
+pub struct ReplayRadio<'a, S> {
+	records: Records<'a>,
+	/// Kept until delivered, so a cancelled `rx` doesn't lose it
+	next: Option<Record<'a>>,
+	sent: S,
+	syncword: u8,
+	modulation: Option<Modulation>,
+	/// When replay started, and the capture timestamp it corresponds to
+	start: Option<(Instant, u64)>,
+	buffer: [u8; MAX_RECORD_SIZE],
+}
+
+impl<'a, S: CaptureSink> ReplayRadio<'a, S> {
+	pub fn new(records: Records<'a>, syncword: u8, sent: S) -> Self {
+		Self {
+			records,
+			next: None,
+			sent,
+			syncword,
+			modulation: None,
+			start: None,
+			buffer: [0; MAX_RECORD_SIZE],
+		}
+	}
+
+	pub fn into_sent(self) -> S { self.sent }
+}
+
+impl<S: CaptureSink> MeshRadio for ReplayRadio<'_, S> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.modulation = Some(*modulation);
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		let Some(modulation) = self.modulation
+		else {
+			return Ok(());
+		};
+		let record = write_record(
+			&mut self.buffer,
+			Instant::now(),
+			Direction::Tx,
+			self.syncword,
+			&modulation,
+			PacketStatus { rssi: 0, snr: 0 },
+			frame,
+		)?;
+		self.sent.record(record);
+		Timer::after(modulation.airtime().time_on_air(frame.len())).await;
+		Ok(())
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let record = loop {
+			if let Some(record) = &self.next {
+				break record;
+			}
+			let Some(record) = self.records.next()
+			else {
+				return core::future::pending().await;
+			};
+			let record = record?;
+			if record.direction()? == Direction::Rx
+				&& record.header.syncword == self.syncword
+				&& self
+					.modulation
+					.is_some_and(|m| m.hears(&record.modulation()))
+			{
+				self.next = Some(record);
+			}
+		};
+
+		let timestamp = record.timestamp_us();
+		let (start, first_timestamp) = *self.start.get_or_insert((Instant::now(), timestamp));
+		let offset = timestamp.saturating_sub(first_timestamp);
+		Timer::at(start + Duration::from_micros(offset)).await;
+
+		let record = self.next.take().unwrap();
+		let len = record.frame.len().min(buffer.len());
+		buffer[..len].copy_from_slice(&record.frame[..len]);
+		Ok((len, record.status()))
+	}
+
+	async fn cad(&mut self) -> Result<bool> { Ok(false) }
+}
@@ -0,0 +1,502 @@
+use crate::{
+	error::Result,
+	radio::{MeshRadio, Modulation},
+};
+use core::cell::Cell;
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant, Timer};
+use lora_phy::mod_params::PacketStatus;
+
+pub const MAX_FRAME_SIZE: usize = 255;
+const INBOX_SIZE: usize = 8;
+
+/// How much stronger a frame must be than an overlapping one to still be demodulated
+pub const CAPTURE_THRESHOLD_DB: i16 = 6;
+
+/// Quality of every link in a freshly created medium
+pub const DEFAULT_LINK: Link = Link {
+	status: PacketStatus { rssi: -60, snr: 10 },
+	loss: 0.0,
+};
+
+#[derive(Clone, Copy)]
+pub struct Link {
+	/// Signal quality frames arrive with
+	pub status: PacketStatus,
+	/// Chance in `[0, 1]` that a frame is lost on this link regardless of interference
+	pub loss: f32,
+}
+
+/// What became of the frames sent over a [`SimMedium`]
+#[derive(Clone, Copy, Default)]
+pub struct SimStats {
+	pub transmissions: u32,
+	pub airtime: Duration,
+	pub delivered: u32,
+	/// Receptions destroyed by an overlapping transmission
+	pub collisions: u32,
+	/// Receptions dropped by link loss
+	pub lost: u32,
+	/// Receptions missed because the receiver was transmitting
+	pub deaf: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Transmission {
+	modulation: Modulation,
+	start: Instant,
+	end: Instant,
+}
+
+impl Transmission {
+	fn overlaps(&self, other: &Transmission) -> bool {
+		self.start < other.end && other.start < self.end
+	}
+}
+
+struct SimFrame {
+	from: usize,
+	modulation: Modulation,
+	status: PacketStatus,
+	len: usize,
+	bytes: [u8; MAX_FRAME_SIZE],
+}
+
+/// In-memory medium connecting `N` simulated radios, for running the protocol loops on a host.
+///
+/// Transmissions occupy the channel for their real time on air, then reach every other radio
+/// whose link from the sender is up and whose modulation can hear them. A receiver loses a frame
+/// if it was transmitting itself meanwhile, if another audible transmission overlapped without
+/// being at least [`CAPTURE_THRESHOLD_DB`] weaker, to the link's random loss, or if its inbox is
+/// full. Only each radio's latest transmission is remembered for the overlap checks.
+pub struct SimMedium<const N: usize> {
+	inboxes: [Channel<NoopRawMutex, SimFrame, INBOX_SIZE>; N],
+	links: [[Cell<Option<Link>>; N]; N],
+	/// `None` until the radio is created
+	modulations: [Cell<Option<Modulation>>; N],
+	transmissions: [Cell<Option<Transmission>>; N],
+	/// Xorshift state for link loss
+	rng: Cell<u64>,
+	stats: Cell<SimStats>,
+}
+
+impl<const N: usize> SimMedium<N> {
+	pub const fn new() -> Self {
+		Self {
+			inboxes: [const { Channel::new() }; N],
+			links: [const { [const { Cell::new(Some(DEFAULT_LINK)) }; N] }; N],
+			modulations: [const { Cell::new(None) }; N],
+			transmissions: [const { Cell::new(None) }; N],
+			rng: Cell::new(1),
+			stats: Cell::new(SimStats {
+				transmissions: 0,
+				airtime: Duration::from_ticks(0),
+				delivered: 0,
+				collisions: 0,
+				lost: 0,
+				deaf: 0,
+			}),
+		}
+	}
+
+	/// Sets the quality of the link from `from` to `to`, or takes it down with `None`
+	pub fn set_link(&self, from: usize, to: usize, link: Option<Link>) {
+		self.links[from][to].set(link);
+	}
+
+	/// Reseeds the generator deciding which frames links lose
+	pub fn set_seed(&self, seed: u64) {
+		// Xorshift gets stuck on a zero state
+		self.rng.set(seed | 1);
+	}
+
+	pub fn stats(&self) -> SimStats { self.stats.get() }
+
+	pub fn radio(&self, id: usize, modulation: &Modulation) -> SimRadio<'_, N> {
+		self.modulations[id].set(Some(*modulation));
+		SimRadio { medium: self, id }
+	}
+
+	fn count(&self, update: impl FnOnce(&mut SimStats)) {
+		let mut stats = self.stats.get();
+		update(&mut stats);
+		self.stats.set(stats);
+	}
+
+	/// Uniform in `[0, 1)`
+	fn chance(&self) -> f32 {
+		let mut state = self.rng.get();
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		self.rng.set(state);
+		(state >> 40) as f32 / (1 << 24) as f32
+	}
+
+	async fn transmit(&self, from: usize, modulation: &Modulation, frame: &[u8]) {
+		let len = frame.len().min(MAX_FRAME_SIZE);
+		let airtime = modulation.airtime().time_on_air(len);
+		let start = Instant::now();
+		let transmission = Transmission {
+			modulation: *modulation,
+			start,
+			end: start + airtime,
+		};
+		self.transmissions[from].set(Some(transmission));
+		self.count(|stats| {
+			stats.transmissions += 1;
+			stats.airtime += airtime;
+		});
+		Timer::at(transmission.end).await;
+
+		for (to, inbox) in self.inboxes.iter().enumerate() {
+			if to == from {
+				continue;
+			}
+			let Some(link) = self.links[from][to].get()
+			else {
+				continue;
+			};
+			if !self.modulations[to]
+				.get()
+				.is_some_and(|receiver| receiver.hears(modulation))
+			{
+				continue;
+			}
+
+			if self.transmissions[to]
+				.get()
+				.is_some_and(|own| own.overlaps(&transmission))
+			{
+				self.count(|stats| stats.deaf += 1);
+				continue;
+			}
+			if self.collided(from, to, &transmission, &link) {
+				self.count(|stats| stats.collisions += 1);
+				continue;
+			}
+			if self.chance() < link.loss {
+				self.count(|stats| stats.lost += 1);
+				continue;
+			}
+
+			let mut bytes = [0; MAX_FRAME_SIZE];
+			bytes[..len].copy_from_slice(&frame[..len]);
+			let frame = SimFrame {
+				from,
+				modulation: *modulation,
+				status: link.status,
+				len,
+				bytes,
+			};
+			if inbox.try_send(frame).is_err() {
+				warn!("Radio {} inbox full, dropping frame", to);
+				continue;
+			}
+			self.count(|stats| stats.delivered += 1);
+		}
+	}
+
+	/// Whether another transmission audible at `to` drowned out `transmission` from `from`
+	fn collided(&self, from: usize, to: usize, transmission: &Transmission, link: &Link) -> bool {
+		(0..N)
+			.filter(|&other| other != from && other != to)
+			.any(|other| {
+				let (Some(interference), Some(interferer)) =
+					(self.transmissions[other].get(), self.links[other][to].get())
+				else {
+					return false;
+				};
+				interference.overlaps(transmission)
+					&& transmission.modulation.hears(&interference.modulation)
+					&& link.status.rssi < interferer.status.rssi + CAPTURE_THRESHOLD_DB
+			})
+	}
+
+	/// Whether a transmission `id` can hear is on air right now
+	fn channel_busy(&self, id: usize) -> bool {
+		let Some(modulation) = self.modulations[id].get()
+		else {
+			return false;
+		};
+		let now = Instant::now();
+		(0..N).filter(|&other| other != id).any(|other| {
+			self.links[other][id].get().is_some()
+				&& self.transmissions[other].get().is_some_and(|transmission| {
+					transmission.start <= now
+						&& now < transmission.end
+						&& modulation.hears(&transmission.modulation)
+				})
+		})
+	}
+}
+
+impl<const N: usize> Default for SimMedium<N> {
+	fn default() -> Self { Self::new() }
+}
+
+pub struct SimRadio<'a, const N: usize> {
+	medium: &'a SimMedium<N>,
+	id: usize,
+}
+
+impl<const N: usize> SimRadio<'_, N> {
+	/// Like [`MeshRadio::rx`], also returning which radio sent the frame
+	pub async fn rx_from(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus, usize)> {
+		loop {
+			let frame = self.medium.inboxes[self.id].receive().await;
+			// The modulation may have changed since the frame arrived
+			if !self.medium.modulations[self.id]
+				.get()
+				.is_some_and(|modulation| modulation.hears(&frame.modulation))
+			{
+				continue;
+			}
+
+			let len = frame.len.min(buffer.len());
+			buffer[..len].copy_from_slice(&frame.bytes[..len]);
+			return Ok((len, frame.status, frame.from));
+		}
+	}
+}
+
+impl<const N: usize> MeshRadio for SimRadio<'_, N> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.medium.modulations[self.id].set(Some(*modulation));
+		Ok(())
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> {
+		if let Some(modulation) = self.medium.modulations[self.id].get() {
+			self.medium.transmit(self.id, &modulation, frame).await;
+		}
+		Ok(())
+	}
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let (len, status, _) = self.rx_from(buffer).await?;
+		Ok((len, status))
+	}
+
+	async fn cad(&mut self) -> Result<bool> { Ok(self.medium.channel_busy(self.id)) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		config::Settings,
+		meshcore::{
+			PACKET_BUFFER_SIZE,
+			client::Client,
+			crypto::{SigningKeys, encrypt_message, msg_ack_hash, msg_mac_32},
+			lora::lora_loop,
+			packet::{
+				Packet, PacketBuilder, Payload, PayloadType, RouteType, U32,
+				ack::Ack,
+				advert::{AdvType, Advert},
+				direct_packets::{DirectHeader, DirectPayload},
+				plain_message::{MessageFlags, PlainMessageHeader},
+			},
+			repeater::{RepeaterConfig, forward_packet},
+		},
+	};
+	use embassy_futures::{
+		join::join,
+		select::{Either, select},
+	};
+	use embassy_time::{Duration, with_timeout};
+	use rand_core::RngCore;
+	use zerocopy::{FromBytes, IntoBytes};
+
+	struct CountingRng(u32);
+
+	impl RngCore for CountingRng {
+		fn next_u32(&mut self) -> u32 {
+			self.0 = self.0.wrapping_add(1);
+			self.0
+		}
+
+		fn next_u64(&mut self) -> u64 { self.next_u32() as u64 }
+
+		fn fill_bytes(&mut self, bytes: &mut [u8]) { bytes.fill_with(|| self.next_u32() as u8) }
+	}
+
+	/// Waits for the next frame of `payload_type`, skipping any others
+	async fn receive(
+		radio: &mut SimRadio<'_, 2>,
+		payload_type: PayloadType,
+		buffer: &mut [u8],
+	) -> usize {
+		loop {
+			let (len, _) = radio.rx(buffer).await.unwrap();
+			let packet = Packet::from_bytes(&buffer[..len]).unwrap();
+			if packet.header.flags.payload_type().ok() == Some(payload_type) {
+				return len;
+			}
+		}
+	}
+
+	#[test]
+	fn txt_in_ack_out() {
+		let medium = SimMedium::<2>::new();
+		let settings = Settings::default();
+		let modulation = settings.radio.modulation();
+		let node_keys = SigningKeys::from_bytes(&[1; 32]);
+		let node_key = node_keys.public_key();
+		let node = medium.radio(0, &modulation);
+		let mut peer = medium.radio(1, &modulation);
+		let peer_keys = SigningKeys::from_bytes(&[2; 32]);
+		let client = Client::new();
+
+		let peer_script = async {
+			// Wait out the node's own advert shortly after it starts, it couldn't hear us meanwhile
+			let mut buffer = [0; 256];
+			receive(&mut peer, PayloadType::Advert, &mut buffer).await;
+
+			// Introduce ourselves so the node can work out our shared secret
+			let mut advert = Advert::new(AdvType::Chat, 100).with_name(b"PEER");
+			advert.sign(&peer_keys).unwrap();
+			let mut buffer = [0; 256];
+			let frame = PacketBuilder::new(RouteType::Flood)
+				.build(&Payload::Advert(advert), &mut buffer)
+				.unwrap();
+			peer.tx(frame).await.unwrap();
+
+			let header = PlainMessageHeader {
+				timestamp: U32::from(200),
+				flags: MessageFlags::from(0),
+			};
+			let mut plaintext = [0; PACKET_BUFFER_SIZE];
+			let header_len = header.as_bytes().len();
+			plaintext[..header_len].copy_from_slice(header.as_bytes());
+			plaintext[header_len..header_len + 2].copy_from_slice(b"hi");
+			let expected = msg_ack_hash(&header, None, b"hi", &peer_keys.public_key());
+
+			let secret = peer_keys
+				.calc_shared_secret(&ed25519_dalek::VerifyingKey::from_bytes(&node_key).unwrap());
+			let ciphertext = encrypt_message(&secret.aes_key(), &mut plaintext, header_len + 2);
+			let mac = msg_mac_32(ciphertext, secret.as_bytes()).unwrap();
+			let payload = DirectPayload {
+				header: DirectHeader {
+					dest_hash: node_key[0],
+					src_hash: peer_keys.public_key()[0],
+					mac: [mac[0], mac[1]],
+				},
+				ciphertext,
+			};
+			let mut buffer = [0; 256];
+			let frame = PacketBuilder::new(RouteType::Direct)
+				.build(&Payload::Txt(payload), &mut buffer)
+				.unwrap();
+			peer.tx(frame).await.unwrap();
+
+			let mut buffer = [0; 256];
+			let len = receive(&mut peer, PayloadType::Ack, &mut buffer).await;
+			let packet = Packet::from_bytes(&buffer[..len]).unwrap();
+			let ack = Ack::ref_from_bytes(packet.payload).unwrap();
+			assert_eq!(ack.hash, expected);
+		};
+
+		let result = embassy_futures::block_on(with_timeout(
+			Duration::from_secs(10),
+			select(
+				lora_loop(
+					node,
+					CountingRng(0),
+					&client,
+					node_keys,
+					settings,
+					RepeaterConfig::default(),
+				),
+				peer_script,
+			),
+		));
+		assert!(matches!(result, Ok(Either::Second(()))));
+	}
+
+	#[test]
+	fn own_flood_is_not_repeated() {
+		let medium = SimMedium::<2>::new();
+		let settings = Settings::default();
+		let modulation = settings.radio.modulation();
+		let node = medium.radio(0, &modulation);
+		let mut peer = medium.radio(1, &modulation);
+		let peer_hash = SigningKeys::from_bytes(&[2; 32]).public_key()[0];
+		let client = Client::new();
+		let repeater = RepeaterConfig {
+			enabled: true,
+			..Default::default()
+		};
+
+		let peer_script = async {
+			let mut buffer = [0; 256];
+			receive(&mut peer, PayloadType::Advert, &mut buffer).await;
+
+			// Relay the node's flooded advert back to it, as any repeater in range would
+			client.send_advert(true).await;
+			let len = receive(&mut peer, PayloadType::Advert, &mut buffer).await;
+			let packet = Packet::from_bytes(&buffer[..len]).unwrap();
+			assert_eq!(packet.header.flags.route_type(), RouteType::Flood);
+			let mut relay_buffer = [0; 256];
+			let relayed = forward_packet(&packet, peer_hash, &mut relay_buffer).unwrap();
+			peer.tx(relayed).await.unwrap();
+
+			let mut buffer = [0; 256];
+			let heard = with_timeout(Duration::from_secs(5), peer.rx(&mut buffer)).await;
+			assert!(heard.is_err(), "node repeated its own flood");
+		};
+
+		let result = embassy_futures::block_on(with_timeout(
+			Duration::from_secs(20),
+			select(
+				lora_loop(
+					node,
+					CountingRng(0),
+					&client,
+					SigningKeys::from_bytes(&[1; 32]),
+					settings,
+					repeater,
+				),
+				peer_script,
+			),
+		));
+		assert!(matches!(result, Ok(Either::Second(()))));
+	}
+
+	/// Transmits a frame from each radio at the same time
+	fn send_together(a: &mut SimRadio<'_, 3>, b: &mut SimRadio<'_, 3>) {
+		let (a, b) = embassy_futures::block_on(join(a.tx(&[1; 20]), b.tx(&[2; 20])));
+		a.unwrap();
+		b.unwrap();
+	}
+
+	#[test]
+	fn overlapping_frames_collide_unless_captured() {
+		let medium = SimMedium::<3>::new();
+		let modulation = Settings::default().radio.modulation();
+		let mut a = medium.radio(0, &modulation);
+		let mut b = medium.radio(1, &modulation);
+		let _receiver = medium.radio(2, &modulation);
+
+		// Both senders are as loud at the receiver and can't hear each other while sending
+		send_together(&mut a, &mut b);
+		let stats = medium.stats();
+		assert_eq!(stats.collisions, 2);
+		assert_eq!(stats.deaf, 2);
+		assert_eq!(stats.delivered, 0);
+
+		let weak = Link {
+			status: PacketStatus {
+				rssi: DEFAULT_LINK.status.rssi - CAPTURE_THRESHOLD_DB,
+				snr: 0,
+			},
+			loss: 0.0,
+		};
+		medium.set_link(1, 2, Some(weak));
+		send_together(&mut a, &mut b);
+		let stats = medium.stats();
+		assert_eq!(stats.collisions, 3);
+		assert_eq!(stats.delivered, 1);
+	}
+}
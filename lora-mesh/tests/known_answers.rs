@@ -0,0 +1,171 @@
+//! Known-answer tests pinning our crypto so it can't drift unnoticed.
+//!
+//! The Ed25519 keys and signatures are RFC 8032's test vectors. The other expected values were
+//! computed with Python's `cryptography` package from our reading of the reference firmwares'
+//! algorithms, so they don't prove byte for byte compatibility with them. That still needs
+//! frames captured from the official MeshCore and Meshtastic firmwares.
+
+use lora_mesh::{
+	meshcore::{
+		PACKET_BUFFER_SIZE,
+		crypto::{
+			PUBLIC_GROUP_PSK, SigningKeys, calculate_channel_hash, encrypt_message, msg_ack_hash,
+			msg_mac_16, msg_mac_32,
+		},
+		packet::{
+			U32,
+			advert::{AdvType, Advert},
+			plain_message::{MessageFlags, PlainMessageHeader},
+		},
+	},
+	meshtastic::{
+		LONGFAST_KEY,
+		crypto::{crypt_data_128, generate_nonce},
+	},
+};
+
+const TIMESTAMP: u32 = 1_700_000_000;
+
+/// RFC 8032 section 7.1, test 1
+const RFC8032_TEST1_SECRET: &str =
+	"9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+const RFC8032_TEST1_PUBLIC: &str =
+	"d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+/// RFC 8032 section 7.1, test 2
+const RFC8032_TEST2_SECRET: &str =
+	"4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb";
+const RFC8032_TEST2_PUBLIC: &str =
+	"3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+
+fn hex(hex: &str) -> Vec<u8> {
+	(0..hex.len())
+		.step_by(2)
+		.map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+		.collect()
+}
+
+fn keys(secret: &str) -> SigningKeys { SigningKeys::from_bytes(&hex(secret).try_into().unwrap()) }
+
+fn message_header() -> PlainMessageHeader {
+	PlainMessageHeader {
+		timestamp: U32::from(TIMESTAMP),
+		flags: MessageFlags::from(0),
+	}
+}
+
+/// Writes a text's plaintext, the header followed by `text`, returning its length
+fn plaintext(text: &[u8], buffer: &mut [u8; PACKET_BUFFER_SIZE]) -> usize {
+	buffer[..4].copy_from_slice(&TIMESTAMP.to_le_bytes());
+	buffer[4] = 0;
+	buffer[5..5 + text.len()].copy_from_slice(text);
+	5 + text.len()
+}
+
+#[test]
+fn rfc8032_vectors() {
+	let vectors = [
+		(
+			RFC8032_TEST1_SECRET,
+			RFC8032_TEST1_PUBLIC,
+			"",
+			"e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bac\
+			 c61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+		),
+		(
+			RFC8032_TEST2_SECRET,
+			RFC8032_TEST2_PUBLIC,
+			"72",
+			"92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e\
+			 458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+		),
+		(
+			"c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+			"fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+			"af82",
+			"6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290\
+			 ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+		),
+	];
+	for (secret, public, message, signature) in vectors {
+		let keys = keys(secret);
+		assert_eq!(keys.public_key().to_vec(), hex(public));
+		assert_eq!(keys.sign_message(&hex(message)).to_vec(), hex(signature));
+	}
+}
+
+#[test]
+fn advert_signature() {
+	let keys = keys(RFC8032_TEST1_SECRET);
+	let mut advert = Advert::new(AdvType::Chat, TIMESTAMP).with_name(b"KAT");
+	advert.sign(&keys).unwrap();
+
+	assert_eq!(advert.header.pub_key.to_vec(), hex(RFC8032_TEST1_PUBLIC));
+	assert_eq!(
+		advert.header.signature.to_vec(),
+		hex(
+			"c86c1184dcb4005ef53628e7f17554ee32bff35b6dd10b40e541b3b8f81d232c\
+		     621a8aa7b7369314760f14f3e61a4c0936f68efbc99fd03282f0b7da680d9205"
+		)
+	);
+	assert!(advert.verify_signature().is_ok());
+}
+
+#[test]
+fn public_channel() {
+	// The reference firmware's default channel, shared as base64 "izOH6cXN6mrJ5e26oRXNcg=="
+	assert_eq!(
+		PUBLIC_GROUP_PSK.to_vec(),
+		hex("8b3387e9c5cdea6ac9e5edbaa115cd72")
+	);
+	assert_eq!(calculate_channel_hash(&PUBLIC_GROUP_PSK), 0x11);
+
+	let mut buffer = [0; PACKET_BUFFER_SIZE];
+	let len = plaintext(b"Alice: hi", &mut buffer);
+	let ciphertext = encrypt_message(&PUBLIC_GROUP_PSK, &mut buffer, len);
+	assert_eq!(ciphertext.to_vec(), hex("9d57fb5a44debb4bda192a91b77ed233"));
+	assert_eq!(
+		msg_mac_16(ciphertext, &PUBLIC_GROUP_PSK).unwrap()[..2],
+		[0xd8, 0x55]
+	);
+}
+
+#[test]
+fn direct_text_and_ack() {
+	let alice = keys(RFC8032_TEST1_SECRET);
+	let bob = keys(RFC8032_TEST2_SECRET);
+	let bob_key = ed25519_dalek::VerifyingKey::from_bytes(&bob.public_key()).unwrap();
+	let secret = alice.calc_shared_secret(&bob_key);
+	assert_eq!(
+		secret.as_bytes().to_vec(),
+		hex("5166f24a6918368e2af831a4affadd97af0ac326bdf143596c045967cc00230e")
+	);
+
+	let mut buffer = [0; PACKET_BUFFER_SIZE];
+	let len = plaintext(b"hello", &mut buffer);
+	let ciphertext = encrypt_message(&secret.aes_key(), &mut buffer, len);
+	assert_eq!(ciphertext.to_vec(), hex("b08fe620676c1c4e7d4a9292e6d6e77a"));
+	assert_eq!(
+		msg_mac_32(ciphertext, secret.as_bytes()).unwrap()[..2],
+		[0x3f, 0xc0]
+	);
+
+	// Bob acknowledges with a hash over the text and Alice's public key
+	let ack = msg_ack_hash(&message_header(), None, b"hello", &alice.public_key());
+	assert_eq!(ack, [0x51, 0x8c, 0x2b, 0x0e]);
+}
+
+#[test]
+fn meshtastic_longfast() {
+	// The default channel's key, shared as base64 "1PG7OiApB1nwvP+rz05pAQ=="
+	assert_eq!(
+		LONGFAST_KEY.to_vec(),
+		hex("d4f1bb3a20290759f0bcffabcf4e6901")
+	);
+	// The packet id is widened to 64 bits, followed by the sender and a zero block counter
+	let nonce = generate_nonce(0x12345678, 0xdeadbeef);
+	assert_eq!(nonce.to_vec(), hex("7856341200000000efbeadde00000000"));
+
+	let mut text = *b"Hello world!";
+	crypt_data_128(&mut text, LONGFAST_KEY, nonce);
+	assert_eq!(text.to_vec(), hex("ebc657fde9541e51dbb8f423"));
+}
@@ -1,7 +0,0 @@
-MEMORY
-{
-  /* NOTE 1 K = 1 KiBi = 1024 bytes */
-  /* NRF52840 with Softdevice S140 7.3.0 */
-  FLASH : ORIGIN = 0x00000000 + 112K, LENGTH = 1024K - 112K
-  RAM : ORIGIN = 0x20000000 + 0x6e28, LENGTH = 256K - 0x6e28
-}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use lora_mesh::{
+	capture::{Direction, FileHeader, Records},
+	config::Settings,
+	meshcore::crypto::SigningKeys,
+};
+use simulator::capture;
+use std::{env, fs, process};
+use zerocopy::IntoBytes;
+
+const USAGE: &str = "\
+Usage: lora-capture <command> ...
+  convert <log> <out.lcap>              extract capture lines from a firmware log
+  dump <capture>                        list the frames in a capture
+  pcap <capture> <out.pcap>             export for Wireshark with LoRaTap headers
+  replay <capture> [identity] [out.lcap]
+                                        play received frames into the protocol stack and
+                                        list what it sends, with a 32 byte private key file
+
+A capture is a capture file, or a log with capture lines.";
+
+fn fail(message: &str) -> ! {
+	eprintln!("{message}\n\n{USAGE}");
+	process::exit(2);
+}
+
+fn read_stream(path: &str) -> Vec<u8> {
+	let bytes = fs::read(path).unwrap_or_else(|e| fail(&format!("Can't read {path}: {e}")));
+	capture::load(&bytes).unwrap_or_else(|e| fail(&format!("Can't load {path}: {e}")))
+}
+
+fn write_capture(path: &str, stream: &[u8]) {
+	let file = [FileHeader::CURRENT.as_bytes(), stream].concat();
+	fs::write(path, file).unwrap_or_else(|e| fail(&format!("Can't write {path}: {e}")));
+}
+
+fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
+
+fn dump(stream: &[u8]) {
+	for record in Records::from_stream(stream) {
+		let Ok(record) = record
+		else {
+			println!("Malformed record, stopping");
+			return;
+		};
+		let direction = match record.direction() {
+			Ok(Direction::Rx) => "rx",
+			Ok(Direction::Tx) => "tx",
+			Err(_) => "??",
+		};
+		let status = record.status();
+		println!(
+			"{:>12.6} {direction} sync {:02x} {} Hz SF{} rssi {} snr {} len {} {}",
+			record.timestamp_us() as f64 / 1e6,
+			record.header.syncword,
+			record.header.frequency.get(),
+			record.header.spreading_factor,
+			status.rssi,
+			status.snr,
+			record.frame.len(),
+			to_hex(record.frame)
+		);
+	}
+}
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(String::as_str).collect();
+	match args.as_slice() {
+		["convert", log, out] => {
+			let stream = read_stream(log);
+			write_capture(out, &stream);
+			println!("Wrote {} records", Records::from_stream(&stream).count());
+		}
+		["dump", path] => dump(&read_stream(path)),
+		["pcap", path, out] => {
+			let stream = read_stream(path);
+			let mut file =
+				fs::File::create(out).unwrap_or_else(|e| fail(&format!("Can't create {out}: {e}")));
+			let count = capture::write_pcap(&stream, &mut file)
+				.unwrap_or_else(|e| fail(&format!("Can't write {out}: {e}")));
+			println!("Wrote {count} packets");
+		}
+		["replay", path, rest @ ..] if rest.len() <= 2 => {
+			let stream = read_stream(path);
+			let private_key = match rest.first() {
+				Some(identity) => fs::read(identity)
+					.ok()
+					.and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+					.unwrap_or_else(|| fail(&format!("{identity} isn't a 32 byte key file"))),
+				None => [0; 32],
+			};
+			let sent = capture::replay(
+				&stream,
+				SigningKeys::from_bytes(&private_key),
+				Settings::default(),
+			)
+			.unwrap_or_else(|e| fail(&format!("Replay failed: {e}")));
+			dump(&sent);
+			if let Some(out) = rest.get(1) {
+				write_capture(out, &sent);
+			}
+		}
+		_ => fail("Invalid arguments"),
+	}
+}
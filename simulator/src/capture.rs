@@ -0,0 +1,280 @@
+use crate::{clock, executor::Executor, rng::XorShift};
+use embassy_time::{Duration, Instant};
+use lora_mesh::{
+	capture::{CAPTURE_MAGIC, CaptureSink, FileHeader, Records},
+	config::Settings,
+	error::{Error, Result},
+	meshcore::{
+		self, MESHCORE_SYNCWORD, client::Client, crypto::SigningKeys, repeater::RepeaterConfig,
+	},
+	meshtastic::{self, MESHTASTIC_SYNCWORD},
+	radio::replay::ReplayRadio,
+};
+use std::{
+	cell::RefCell,
+	io::{self, Write},
+};
+
+/// Marks a capture record in a firmware log, followed by its bytes as a list of hex numbers
+pub const LOG_PREFIX: &str = "CAPTURE ";
+
+/// How long replay keeps running after the last captured frame, for retries and timeouts
+const REPLAY_TAIL: Duration = Duration::from_secs(60);
+
+/// Link type for LoRaTap headers, which Wireshark decodes
+const LINKTYPE_LORATAP: u32 = 270;
+const LORATAP_HEADER_SIZE: u16 = 15;
+
+/// Collects capture records in memory
+pub struct SharedSink<'a>(pub &'a RefCell<Vec<u8>>);
+
+impl CaptureSink for SharedSink<'_> {
+	fn record(&mut self, record: &[u8]) { self.0.borrow_mut().extend_from_slice(record); }
+}
+
+/// Pulls the capture records out of a firmware log, returning them as a stream
+pub fn parse_log(log: &str) -> Vec<u8> {
+	let mut stream = Vec::new();
+	for line in log.lines() {
+		let Some((_, record)) = line.split_once(LOG_PREFIX)
+		else {
+			continue;
+		};
+		let record = record.trim().trim_start_matches('[').trim_end_matches(']');
+		let bytes: Option<Vec<u8>> = record
+			.split(',')
+			.map(|byte| u8::from_str_radix(byte.trim().trim_start_matches("0x"), 16).ok())
+			.collect();
+		match bytes {
+			Some(bytes) => stream.extend(bytes),
+			None => eprintln!("Skipping malformed capture line: {line}"),
+		}
+	}
+	stream
+}
+
+/// Accepts either a capture file or a log with capture lines, returning the record stream
+pub fn load(bytes: &[u8]) -> Result<Vec<u8>> {
+	if bytes.starts_with(&CAPTURE_MAGIC) {
+		Records::from_file(bytes)?;
+		return Ok(bytes[size_of::<FileHeader>()..].to_vec());
+	}
+	let log = str::from_utf8(bytes).map_err(|_| Error::InvalidCapture)?;
+	Ok(parse_log(log))
+}
+
+fn loratap_rssi(rssi: i16) -> u8 { rssi.saturating_add(139).clamp(0, u8::MAX as i16) as u8 }
+
+/// SNR in quarter dB
+fn loratap_snr(snr: i16) -> u8 {
+	snr.saturating_mul(4).clamp(i8::MIN as i16, i8::MAX as i16) as i8 as u8
+}
+
+/// LoRaTap counts bandwidth in 125 kHz steps, so narrower channels round up to one step rather
+/// than showing as 0
+fn loratap_bandwidth(bandwidth_hz: u32) -> u8 {
+	bandwidth_hz.div_ceil(125_000).clamp(1, u8::MAX as u32) as u8
+}
+
+/// Writes every record in `stream` as a pcap packet behind a LoRaTap v0 header.
+/// Timestamps count from the capturing device's boot. Returns the number of packets written.
+pub fn write_pcap(stream: &[u8], out: &mut impl Write) -> io::Result<usize> {
+	out.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+	out.write_all(&2u16.to_le_bytes())?;
+	out.write_all(&4u16.to_le_bytes())?;
+	out.write_all(&0i32.to_le_bytes())?;
+	out.write_all(&0u32.to_le_bytes())?;
+	out.write_all(&65535u32.to_le_bytes())?;
+	out.write_all(&LINKTYPE_LORATAP.to_le_bytes())?;
+
+	let mut count = 0;
+	for record in Records::from_stream(stream) {
+		let Ok(record) = record
+		else {
+			eprintln!("Capture ends with a malformed record");
+			break;
+		};
+		let header = record.header;
+		let timestamp = record.timestamp_us();
+		let len = LORATAP_HEADER_SIZE as u32 + record.frame.len() as u32;
+
+		out.write_all(&((timestamp / 1_000_000) as u32).to_le_bytes())?;
+		out.write_all(&((timestamp % 1_000_000) as u32).to_le_bytes())?;
+		out.write_all(&len.to_le_bytes())?;
+		out.write_all(&len.to_le_bytes())?;
+
+		let rssi = loratap_rssi(header.rssi.get());
+		out.write_all(&[0, 0])?;
+		out.write_all(&LORATAP_HEADER_SIZE.to_be_bytes())?;
+		out.write_all(&header.frequency.get().to_be_bytes())?;
+		out.write_all(&[
+			loratap_bandwidth(header.bandwidth_hz.get()),
+			header.spreading_factor,
+			rssi,
+			rssi,
+			rssi,
+			loratap_snr(header.snr.get()),
+			header.syncword,
+		])?;
+		out.write_all(record.frame)?;
+		count += 1;
+	}
+	Ok(count)
+}
+
+/// Plays the received frames in `stream` into the protocol loop matching their syncword,
+/// on the virtual clock. Returns the records of the frames the loop sent in response.
+pub fn replay(stream: &[u8], identity: SigningKeys, settings: Settings) -> Result<Vec<u8>> {
+	let mut timestamps = Records::from_stream(stream).filter_map(|record| record.ok());
+	let Some(first) = timestamps.next()
+	else {
+		return Ok(Vec::new());
+	};
+	let syncword = first.header.syncword;
+	let first_timestamp = first.timestamp_us();
+	let last_timestamp = timestamps
+		.last()
+		.map_or(first_timestamp, |r| r.timestamp_us());
+	// Out of order timestamps are replayed without delay, as by `ReplayRadio`
+	let span = Duration::from_micros(last_timestamp.saturating_sub(first_timestamp));
+
+	let _run = clock::reset();
+	let sent = RefCell::new(Vec::new());
+	let radio = ReplayRadio::new(Records::from_stream(stream), syncword, SharedSink(&sent));
+	let rng = XorShift::new(0);
+	let client = Client::new();
+	let mut executor = Executor::new();
+	match syncword {
+		MESHCORE_SYNCWORD => {
+			let repeater = RepeaterConfig {
+				enabled: settings.repeater_enabled,
+				..RepeaterConfig::default()
+			};
+			executor.spawn(async {
+				meshcore::lora::lora_loop(radio, rng, &client, identity, settings, repeater).await;
+			});
+		}
+		MESHTASTIC_SYNCWORD => executor.spawn(async {
+			meshtastic::lora::lora_loop(radio, rng).await;
+		}),
+		_ => return Err(Error::InvalidCapture),
+	}
+	executor.run_until(Instant::from_ticks(0) + span + REPLAY_TAIL);
+	drop(executor);
+
+	Ok(sent.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use lora_mesh::{
+		capture::{Direction, MAX_RECORD_SIZE, write_record},
+		meshcore::{
+			PACKET_BUFFER_SIZE,
+			crypto::{encrypt_message, msg_ack_hash, msg_mac_32},
+			packet::{
+				Packet, PacketBuilder, Payload, PayloadType, RouteType, U32,
+				ack::Ack,
+				advert::{AdvType, Advert},
+				direct_packets::{DirectHeader, DirectPayload},
+				plain_message::{MessageFlags, PlainMessageHeader},
+			},
+		},
+	};
+	use lora_phy::mod_params::PacketStatus;
+	use zerocopy::{FromBytes, IntoBytes};
+
+	/// Appends a record of receiving `payload` at `secs` into the capture
+	fn receive(stream: &mut Vec<u8>, secs: u64, route_type: RouteType, payload: &Payload) {
+		let mut buffer = [0; PACKET_BUFFER_SIZE];
+		let frame = PacketBuilder::new(route_type)
+			.build(payload, &mut buffer)
+			.unwrap();
+		let mut record = [0; MAX_RECORD_SIZE];
+		let record = write_record(
+			&mut record,
+			Instant::from_secs(secs),
+			Direction::Rx,
+			MESHCORE_SYNCWORD,
+			&Settings::default().radio.modulation(),
+			PacketStatus { rssi: -80, snr: 8 },
+			frame,
+		)
+		.unwrap();
+		stream.extend_from_slice(record);
+	}
+
+	#[test]
+	fn replayed_text_is_acked() {
+		let node_keys = SigningKeys::from_bytes(&[1; 32]);
+		let node_key = node_keys.public_key();
+		let peer_keys = SigningKeys::from_bytes(&[2; 32]);
+		let mut stream = Vec::new();
+
+		let mut advert = Advert::new(AdvType::Chat, 100).with_name(b"PEER");
+		advert.sign(&peer_keys).unwrap();
+		receive(&mut stream, 60, RouteType::Flood, &Payload::Advert(advert));
+
+		let header = PlainMessageHeader {
+			timestamp: U32::from(200),
+			flags: MessageFlags::from(0),
+		};
+		let mut plaintext = [0; PACKET_BUFFER_SIZE];
+		let header_len = header.as_bytes().len();
+		plaintext[..header_len].copy_from_slice(header.as_bytes());
+		plaintext[header_len..header_len + 2].copy_from_slice(b"hi");
+		let secret = peer_keys
+			.calc_shared_secret(&ed25519_dalek::VerifyingKey::from_bytes(&node_key).unwrap());
+		let ciphertext = encrypt_message(&secret.aes_key(), &mut plaintext, header_len + 2);
+		let mac = msg_mac_32(ciphertext, secret.as_bytes()).unwrap();
+		let payload = DirectPayload {
+			header: DirectHeader {
+				dest_hash: node_key[0],
+				src_hash: peer_keys.public_key()[0],
+				mac: [mac[0], mac[1]],
+			},
+			ciphertext,
+		};
+		receive(&mut stream, 70, RouteType::Direct, &Payload::Txt(payload));
+
+		let sent = replay(&stream, node_keys, Settings::default()).unwrap();
+		let expected = msg_ack_hash(&header, None, b"hi", &peer_keys.public_key());
+		let acked = Records::from_stream(&sent).any(|record| {
+			let record = record.unwrap();
+			assert!(matches!(record.direction(), Ok(Direction::Tx)));
+			let packet = Packet::from_bytes(record.frame).unwrap();
+			packet.header.flags.payload_type().ok() == Some(PayloadType::Ack)
+				&& Ack::ref_from_bytes(packet.payload).unwrap().hash == expected
+		});
+		assert!(acked);
+	}
+
+	#[test]
+	fn replays_out_of_order_timestamps() {
+		let mut stream = Vec::new();
+		let mut advert = Advert::new(AdvType::Chat, 100).with_name(b"PEER");
+		advert.sign(&SigningKeys::from_bytes(&[2; 32])).unwrap();
+		receive(
+			&mut stream,
+			70,
+			RouteType::Flood,
+			&Payload::Advert(advert.clone()),
+		);
+		receive(&mut stream, 60, RouteType::Flood, &Payload::Advert(advert));
+
+		let keys = SigningKeys::from_bytes(&[1; 32]);
+		assert!(replay(&stream, keys, Settings::default()).is_ok());
+	}
+
+	#[test]
+	fn loratap_fields_saturate() {
+		assert_eq!(loratap_rssi(i16::MIN), 0);
+		assert_eq!(loratap_rssi(i16::MAX), u8::MAX);
+		assert_eq!(loratap_snr(i16::MIN), i8::MIN as u8);
+		assert_eq!(loratap_snr(i16::MAX), i8::MAX as u8);
+		assert_eq!(loratap_snr(-5), -20i8 as u8);
+		assert_eq!(loratap_bandwidth(62_500), 1);
+		assert_eq!(loratap_bandwidth(250_000), 2);
+	}
+}
@@ -0,0 +1,78 @@
+use embassy_time::Instant;
+use embassy_time_driver::Driver;
+use std::{
+	sync::{Mutex, MutexGuard, PoisonError},
+	task::Waker,
+};
+
+/// embassy-time driver whose time only moves when the executor runs out of work
+struct VirtualClock {
+	state: Mutex<ClockState>,
+}
+
+struct ClockState {
+	now: u64,
+	timers: Vec<(u64, Waker)>,
+}
+
+impl Driver for VirtualClock {
+	fn now(&self) -> u64 { self.state.lock().unwrap().now }
+
+	fn schedule_wake(&self, at: u64, waker: &Waker) {
+		let mut state = self.state.lock().unwrap();
+		if at <= state.now {
+			waker.wake_by_ref();
+			return;
+		}
+		// A task only needs waking at its earliest deadline, it re-arms any later ones when polled
+		match state.timers.iter_mut().find(|(_, w)| w.will_wake(waker)) {
+			Some(timer) => timer.0 = timer.0.min(at),
+			None => state.timers.push((at, waker.clone())),
+		}
+	}
+}
+
+embassy_time_driver::time_driver_impl!(static CLOCK: VirtualClock = VirtualClock {
+	state: Mutex::new(ClockState {
+		now: 0,
+		timers: Vec::new(),
+	}),
+});
+
+/// Held by the run using the clock
+static RUN: Mutex<()> = Mutex::new(());
+
+/// Rewinds to zero and forgets all timers, for starting a new run. Runs on other threads wait
+/// until the returned guard drops, since there's only one clock.
+pub fn reset() -> MutexGuard<'static, ()> {
+	let run = RUN.lock().unwrap_or_else(PoisonError::into_inner);
+	let mut state = CLOCK.state.lock().unwrap();
+	state.now = 0;
+	state.timers.clear();
+	run
+}
+
+/// Jumps to the earliest timer due no later than `limit` and wakes everything due then.
+/// Returns false if no timer is due by `limit`.
+pub fn advance(limit: Instant) -> bool {
+	let due = {
+		let mut state = CLOCK.state.lock().unwrap();
+		let Some(next) = state.timers.iter().map(|(at, _)| *at).min()
+		else {
+			return false;
+		};
+		if next > limit.as_ticks() {
+			return false;
+		}
+
+		state.now = next;
+		let (due, pending) = state.timers.drain(..).partition(|(at, _)| *at <= next);
+		state.timers = pending;
+		due
+	};
+
+	for (_, waker) in due {
+		waker.wake();
+	}
+	true
+}
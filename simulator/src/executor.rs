@@ -0,0 +1,65 @@
+use crate::clock;
+use embassy_time::Instant;
+use std::{
+	pin::Pin,
+	sync::{
+		Arc,
+		atomic::{AtomicBool, Ordering},
+	},
+	task::{Context, Poll, Wake, Waker},
+};
+
+struct ReadyFlag(AtomicBool);
+
+impl Wake for ReadyFlag {
+	fn wake(self: Arc<Self>) { self.0.store(true, Ordering::Relaxed); }
+}
+
+struct Task<'a> {
+	future: Pin<Box<dyn Future<Output = ()> + 'a>>,
+	ready: Arc<ReadyFlag>,
+	waker: Waker,
+	done: bool,
+}
+
+/// Single threaded executor that polls every ready task before letting virtual time move on,
+/// so all nodes see the same clock
+#[derive(Default)]
+pub struct Executor<'a> {
+	tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Executor<'a> {
+	pub fn new() -> Self { Self { tasks: Vec::new() } }
+
+	pub fn spawn(&mut self, future: impl Future<Output = ()> + 'a) {
+		let ready = Arc::new(ReadyFlag(AtomicBool::new(true)));
+		self.tasks.push(Task {
+			future: Box::pin(future),
+			waker: Waker::from(ready.clone()),
+			ready,
+			done: false,
+		});
+	}
+
+	/// Runs until `end`, or until every task is waiting on something that will never happen
+	pub fn run_until(&mut self, end: Instant) {
+		loop {
+			let mut polled = false;
+			for task in self.tasks.iter_mut().filter(|task| !task.done) {
+				if !task.ready.0.swap(false, Ordering::Relaxed) {
+					continue;
+				}
+				polled = true;
+				let mut cx = Context::from_waker(&task.waker);
+				if let Poll::Ready(()) = task.future.as_mut().poll(&mut cx) {
+					task.done = true;
+				}
+			}
+
+			if !polled && !clock::advance(end) {
+				return;
+			}
+		}
+	}
+}
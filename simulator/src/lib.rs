@@ -0,0 +1,9 @@
+//! Runs many copies of the MeshCore stack against a shared virtual medium and clock, to check
+//! flooding, duplicate suppression and path learning without a desk full of boards.
+
+pub mod capture;
+pub mod clock;
+pub mod executor;
+pub mod medium;
+pub mod rng;
+pub mod scenario;
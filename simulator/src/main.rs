@@ -0,0 +1,79 @@
+use embassy_time::Duration;
+use lora_phy::mod_params::PacketStatus;
+use simulator::{
+	medium::{Link, MAX_NODES, Topology},
+	scenario::{self, Scenario},
+};
+use std::{env, process, str::FromStr};
+
+const USAGE: &str = "\
+Usage: simulator [options]
+  --topology line|ring|grid|full  (default line)
+  --nodes N                       (default 5, at most 64)
+  --loss P                        chance of losing a frame on each link (default 0)
+  --rssi DBM                      (default -90)
+  --snr DB                        (default 5)
+  --messages M                    (default 10)
+  --duration SECONDS              virtual time to run for (default 600)
+  --seed S                        (default 1)";
+
+fn fail(message: &str) -> ! {
+	eprintln!("{message}\n\n{USAGE}");
+	process::exit(2);
+}
+
+fn parse<T: FromStr>(name: &str, value: Option<String>) -> T {
+	value
+		.and_then(|value| value.parse().ok())
+		.unwrap_or_else(|| fail(&format!("Invalid value for {name}")))
+}
+
+fn main() {
+	let mut topology = String::from("line");
+	let mut nodes = 5;
+	let mut link = Link {
+		status: PacketStatus { rssi: -90, snr: 5 },
+		loss: 0.0,
+	};
+	let mut messages = 10;
+	let mut duration_secs = 600;
+	let mut seed = 1;
+
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--topology" => topology = parse(&arg, args.next()),
+			"--nodes" => nodes = parse(&arg, args.next()),
+			"--loss" => link.loss = parse(&arg, args.next()),
+			"--rssi" => link.status.rssi = parse(&arg, args.next()),
+			"--snr" => link.status.snr = parse(&arg, args.next()),
+			"--messages" => messages = parse(&arg, args.next()),
+			"--duration" => duration_secs = parse(&arg, args.next()),
+			"--seed" => seed = parse(&arg, args.next()),
+			"--help" | "-h" => {
+				println!("{USAGE}");
+				return;
+			}
+			_ => fail(&format!("Unknown argument {arg}")),
+		}
+	}
+
+	if !(2..=MAX_NODES).contains(&nodes) {
+		fail("Need 2 to 64 nodes");
+	}
+	let topology = match topology.as_str() {
+		"line" => Topology::line(nodes, link),
+		"ring" => Topology::ring(nodes, link),
+		"grid" => Topology::grid(nodes, link),
+		"full" => Topology::full(nodes, link),
+		_ => fail("Unknown topology"),
+	};
+
+	let report = scenario::run(&Scenario {
+		topology,
+		messages,
+		duration: Duration::from_secs(duration_secs),
+		seed,
+	});
+	println!("{report}");
+}
@@ -0,0 +1,182 @@
+use lora_mesh::{
+	error::Result,
+	radio::{
+		MeshRadio, Modulation,
+		sim::{SimMedium, SimRadio, SimStats},
+	},
+};
+use lora_phy::mod_params::PacketStatus;
+use std::{cell::RefCell, collections::HashMap};
+
+pub use lora_mesh::radio::sim::Link;
+
+/// Most nodes a simulation can have
+pub const MAX_NODES: usize = 64;
+
+/// Identifies a frame across every retransmission of it, or `None` if it can't be parsed
+pub type FrameKey = fn(&[u8]) -> Option<u64>;
+
+/// Which nodes can hear each other, and how well
+#[derive(Clone)]
+pub struct Topology {
+	links: Vec<Vec<Option<Link>>>,
+}
+
+impl Topology {
+	/// `nodes` nodes that can't hear each other
+	pub fn new(nodes: usize) -> Self {
+		Self {
+			links: vec![vec![None; nodes]; nodes],
+		}
+	}
+
+	pub fn nodes(&self) -> usize { self.links.len() }
+
+	/// Links `a` and `b` in both directions
+	pub fn connect(&mut self, a: usize, b: usize, link: Link) {
+		self.links[a][b] = Some(link);
+		self.links[b][a] = Some(link);
+	}
+
+	pub fn link(&self, from: usize, to: usize) -> Option<Link> { self.links[from][to] }
+
+	pub fn line(nodes: usize, link: Link) -> Self {
+		let mut topology = Self::new(nodes);
+		for i in 1..nodes {
+			topology.connect(i - 1, i, link);
+		}
+		topology
+	}
+
+	pub fn ring(nodes: usize, link: Link) -> Self {
+		let mut topology = Self::line(nodes, link);
+		if nodes > 2 {
+			topology.connect(nodes - 1, 0, link);
+		}
+		topology
+	}
+
+	/// Nodes laid out row by row on the smallest square grid that fits them,
+	/// each linked to its horizontal and vertical neighbours
+	pub fn grid(nodes: usize, link: Link) -> Self {
+		let width = nodes.isqrt() + usize::from(nodes.isqrt().pow(2) < nodes);
+		let mut topology = Self::new(nodes);
+		for i in 0..nodes {
+			if i % width != 0 {
+				topology.connect(i - 1, i, link);
+			}
+			if i >= width {
+				topology.connect(i - width, i, link);
+			}
+		}
+		topology
+	}
+
+	pub fn full(nodes: usize, link: Link) -> Self {
+		let mut topology = Self::new(nodes);
+		for a in 0..nodes {
+			for b in a + 1..nodes {
+				topology.connect(a, b, link);
+			}
+		}
+		topology
+	}
+}
+
+/// Where one frame has been
+struct Trace {
+	origin: usize,
+	/// Hops taken by the first copy each node received
+	hops: Vec<Option<u32>>,
+}
+
+/// A [`SimMedium`] laid out as a [`Topology`], following frames from hop to hop
+pub struct Medium {
+	sim: Box<SimMedium<MAX_NODES>>,
+	nodes: usize,
+	key: FrameKey,
+	traces: RefCell<HashMap<u64, Trace>>,
+}
+
+impl Medium {
+	/// Panics if `topology` has more than [`MAX_NODES`] nodes
+	pub fn new(topology: &Topology, key: FrameKey, seed: u64) -> Self {
+		let nodes = topology.nodes();
+		assert!(nodes <= MAX_NODES, "At most {MAX_NODES} nodes");
+		let sim = Box::new(SimMedium::new());
+		sim.set_seed(seed);
+		for from in 0..MAX_NODES {
+			for to in 0..MAX_NODES {
+				let link = (from < nodes && to < nodes)
+					.then(|| topology.link(from, to))
+					.flatten();
+				sim.set_link(from, to, link);
+			}
+		}
+		Self {
+			sim,
+			nodes,
+			key,
+			traces: RefCell::new(HashMap::new()),
+		}
+	}
+
+	pub fn radio(&self, id: usize, modulation: &Modulation) -> VirtualRadio<'_> {
+		VirtualRadio {
+			radio: self.sim.radio(id, modulation),
+			medium: self,
+			id,
+		}
+	}
+
+	pub fn stats(&self) -> SimStats { self.sim.stats() }
+
+	/// Hop counts of the first copy of each frame to reach each node, for every frame seen
+	pub fn hop_counts(&self) -> Vec<u32> {
+		let traces = self.traces.borrow();
+		traces
+			.values()
+			.flat_map(|trace| trace.hops.iter().flatten().copied())
+			.collect()
+	}
+
+	/// Notes that `to` received `frame` from `from`, one hop further than `from` got it
+	fn trace(&self, from: usize, to: usize, frame: &[u8]) {
+		let Some(key) = (self.key)(frame)
+		else {
+			return;
+		};
+		let mut traces = self.traces.borrow_mut();
+		let trace = traces.entry(key).or_insert_with(|| Trace {
+			origin: from,
+			hops: vec![None; self.nodes],
+		});
+		let hops = trace.hops[from].map_or(1, |hops| hops + 1);
+		if to != trace.origin {
+			trace.hops[to].get_or_insert(hops);
+		}
+	}
+}
+
+/// One node's radio on a [`Medium`]
+pub struct VirtualRadio<'a> {
+	radio: SimRadio<'a, MAX_NODES>,
+	medium: &'a Medium,
+	id: usize,
+}
+
+impl MeshRadio for VirtualRadio<'_> {
+	async fn set_modulation(&mut self, modulation: &Modulation) -> Result<()> {
+		self.radio.set_modulation(modulation).await
+	}
+
+	async fn tx(&mut self, frame: &[u8]) -> Result<()> { self.radio.tx(frame).await }
+
+	async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, PacketStatus)> {
+		let (len, status, from) = self.radio.rx_from(buffer).await?;
+		self.medium.trace(from, self.id, &buffer[..len]);
+		Ok((len, status))
+	}
+
+	async fn cad(&mut self) -> Result<bool> { self.radio.cad().await }
+}
@@ -0,0 +1,39 @@
+use rand_core::RngCore;
+
+/// Small deterministic generator so a run can be reproduced from its seed
+#[derive(Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+	pub fn new(seed: u64) -> Self {
+		// Spread the seed with splitmix64, xorshift gets stuck on a zero state
+		let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		Self((z ^ (z >> 31)) | 1)
+	}
+
+	/// Uniform in `[0, 1)`
+	pub fn next_f32(&mut self) -> f32 { (self.next_u32() >> 8) as f32 / (1 << 24) as f32 }
+
+	/// Uniform in `[0, n)`
+	pub fn below(&mut self, n: usize) -> usize { (self.next_u64() % n as u64) as usize }
+}
+
+impl RngCore for XorShift {
+	fn next_u32(&mut self) -> u32 { (self.next_u64() >> 32) as u32 }
+
+	fn next_u64(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn fill_bytes(&mut self, dst: &mut [u8]) {
+		for chunk in dst.chunks_mut(8) {
+			let bytes = self.next_u64().to_le_bytes();
+			chunk.copy_from_slice(&bytes[..chunk.len()]);
+		}
+	}
+}
@@ -0,0 +1,228 @@
+use crate::{
+	clock,
+	executor::Executor,
+	medium::{Medium, Topology},
+	rng::XorShift,
+};
+use core::fmt;
+use embassy_time::{Duration, Instant, Timer};
+use lora_mesh::{
+	config::Settings,
+	meshcore::{
+		adverts::MAX_ADVERT_JITTER,
+		client::{Client, Event},
+		crypto::SigningKeys,
+		lora::lora_loop,
+		packet::Packet,
+		repeater::RepeaterConfig,
+	},
+	radio::sim::SimStats,
+};
+use rand_core::RngCore;
+use std::cell::RefCell;
+
+/// Gap between nodes' first flood adverts, long enough for one to cross the mesh before the next
+const ADVERT_STAGGER: Duration = Duration::from_secs(10);
+/// Time for the last advert to spread before messages start
+const SETTLE_TIME: Duration = Duration::from_secs(30);
+const MESSAGE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A MeshCore mesh to run
+pub struct Scenario {
+	pub topology: Topology,
+	/// Direct texts sent between random pairs of nodes
+	pub messages: usize,
+	/// Virtual time to run for
+	pub duration: Duration,
+	pub seed: u64,
+}
+
+pub struct Report {
+	pub nodes: usize,
+	pub sent: usize,
+	/// Messages ACKed by their recipient
+	pub delivered: usize,
+	pub failed: usize,
+	pub hops: Vec<u32>,
+	pub stats: SimStats,
+}
+
+impl Report {
+	pub fn delivery_ratio(&self) -> f32 {
+		if self.sent == 0 {
+			return 0.0;
+		}
+		self.delivered as f32 / self.sent as f32
+	}
+
+	pub fn average_hops(&self) -> f32 {
+		if self.hops.is_empty() {
+			return 0.0;
+		}
+		self.hops.iter().sum::<u32>() as f32 / self.hops.len() as f32
+	}
+
+	pub fn max_hops(&self) -> u32 { self.hops.iter().copied().max().unwrap_or(0) }
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "nodes:         {}", self.nodes)?;
+		writeln!(
+			f,
+			"delivery:      {:.1}% of {} messages ({} failed)",
+			self.delivery_ratio() * 100.0,
+			self.sent,
+			self.failed
+		)?;
+		writeln!(
+			f,
+			"hops:          avg {:.2}, max {}",
+			self.average_hops(),
+			self.max_hops()
+		)?;
+		writeln!(
+			f,
+			"airtime:       {:.3} s over {} transmissions",
+			self.stats.airtime.as_micros() as f64 / 1e6,
+			self.stats.transmissions
+		)?;
+		write!(
+			f,
+			"receptions:    {} delivered, {} collided, {} lost, {} missed while transmitting",
+			self.stats.delivered, self.stats.collisions, self.stats.lost, self.stats.deaf
+		)
+	}
+}
+
+/// Picks two different nodes
+fn random_pair(rng: &mut XorShift, nodes: usize) -> (usize, usize) {
+	let from = rng.below(nodes);
+	let to = (from + 1 + rng.below(nodes - 1)) % nodes;
+	(from, to)
+}
+
+fn frame_key(frame: &[u8]) -> Option<u64> {
+	let packet = Packet::from_bytes(frame).ok()?;
+	Some(u64::from_le_bytes(packet.hash()))
+}
+
+#[derive(Default)]
+struct Outcomes {
+	sent: usize,
+	delivered: usize,
+	failed: usize,
+}
+
+/// Every node repeats, floods an advert so the others learn it, then texts flow between
+/// random pairs. Delivery is judged by the sender getting an ACK.
+pub fn run(scenario: &Scenario) -> Report {
+	let _run = clock::reset();
+	let nodes = scenario.topology.nodes();
+	let medium = Medium::new(&scenario.topology, frame_key, scenario.seed);
+	let clients: Vec<Client> = (0..nodes).map(|_| Client::new()).collect();
+	let outcomes = RefCell::new(Outcomes::default());
+
+	let mut rng = XorShift::new(scenario.seed);
+	let mut public_keys = Vec::new();
+	let mut executor = Executor::new();
+
+	for (i, client) in clients.iter().enumerate() {
+		let mut private_key = [0; 32];
+		rng.fill_bytes(&mut private_key);
+		let identity = SigningKeys::from_bytes(&private_key);
+		public_keys.push(identity.public_key());
+
+		let mut settings = Settings::default();
+		settings.set_name(format!("node{i}").as_bytes()).unwrap();
+		let repeater = RepeaterConfig {
+			enabled: true,
+			..RepeaterConfig::default()
+		};
+		let node_rng = XorShift::new(rng.next_u64());
+		let radio = medium.radio(i, &settings.radio.modulation());
+		executor.spawn(async move {
+			lora_loop(radio, node_rng, client, identity, settings, repeater).await;
+		});
+
+		let outcomes = &outcomes;
+		executor.spawn(async move {
+			loop {
+				match client.events.receive().await {
+					Event::MessageDelivered { .. } => outcomes.borrow_mut().delivered += 1,
+					Event::MessageFailed { .. } => outcomes.borrow_mut().failed += 1,
+					_ => (),
+				}
+			}
+		});
+	}
+
+	let clients = &clients;
+	let public_keys = &public_keys;
+	let outcomes_ref = &outcomes;
+	executor.spawn(async move {
+		// Nodes advertise to their neighbours at boot, which would deafen them to the floods
+		Timer::after(MAX_ADVERT_JITTER).await;
+		for client in clients {
+			Timer::after(ADVERT_STAGGER).await;
+			client.send_advert(true).await;
+		}
+		Timer::after(SETTLE_TIME).await;
+
+		for message in 0..scenario.messages {
+			let (from, to) = random_pair(&mut rng, nodes);
+			let text = format!("Message {message}");
+			if clients[from]
+				.send_direct_text(&public_keys[to], &text)
+				.await
+				.is_ok()
+			{
+				outcomes_ref.borrow_mut().sent += 1;
+			}
+			Timer::after(MESSAGE_INTERVAL).await;
+		}
+	});
+
+	executor.run_until(Instant::from_ticks(0) + scenario.duration);
+	drop(executor);
+
+	let outcomes = outcomes.into_inner();
+	Report {
+		nodes,
+		sent: outcomes.sent,
+		delivered: outcomes.delivered,
+		failed: outcomes.failed,
+		hops: medium.hop_counts(),
+		stats: medium.stats(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::medium::Link;
+	use lora_phy::mod_params::PacketStatus;
+
+	#[test]
+	fn texts_cross_a_line_of_repeaters() {
+		let link = Link {
+			status: PacketStatus { rssi: -90, snr: 5 },
+			loss: 0.0,
+		};
+		let report = run(&Scenario {
+			topology: Topology::line(4, link),
+			messages: 5,
+			duration: Duration::from_secs(300),
+			seed: 1,
+		});
+
+		assert_eq!(report.sent, 5);
+		// Relays on a line collide now and then, costing the odd message its ACK
+		assert!(report.delivered >= 4);
+		assert_eq!(report.delivered + report.failed, report.sent);
+		// Adverts flood from one end of the line to the other
+		assert_eq!(report.max_hops(), 3);
+		assert!(report.stats.transmissions > 0);
+		assert_eq!(report.stats.lost, 0);
+	}
+}